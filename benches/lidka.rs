@@ -0,0 +1,136 @@
+//! Compares [Field::merge()] with the former merge rescanning the whole tree after every merged
+//! cluster. Both merge the same unmerged generations of Lidka, [WINDOW] consecutive ones every
+//! [CHECK_EVERY] generations
+//!
+//! Run with `cargo bench --bench lidka`
+use rstar::{Envelope, RTree, RTreeObject, SelectionFunction, AABB};
+use rust_cell::groups::{field::Field, group::Group, rule::Rule, Coord};
+use rust_cell::library;
+use std::time::{Duration, Instant};
+
+const GENERATIONS: u64 = 20000;
+const CHECK_EVERY: u64 = 2000;
+/// Consecutive generations merged at every check
+const WINDOW: u64 = 50;
+
+struct SmartSelection<'a> {
+    data: &'a Group,
+    rule: &'a Rule,
+}
+
+impl SelectionFunction<Group> for SmartSelection<'_> {
+    fn should_unpack_parent(&self, envelope: &AABB<(i64, i64)>) -> bool {
+        self.data.envelope().intersects(envelope)
+    }
+
+    fn should_unpack_leaf(&self, leaf: &Group) -> bool {
+        self.data.intersects_smart(leaf, self.rule)
+    }
+}
+
+struct EnvelopeSelection<'a> {
+    data: &'a AABB<(i64, i64)>,
+}
+
+impl SelectionFunction<Group> for EnvelopeSelection<'_> {
+    fn should_unpack_parent(&self, envelope: &AABB<(i64, i64)>) -> bool {
+        self.data.intersects(envelope)
+    }
+
+    fn should_unpack_leaf(&self, leaf: &Group) -> bool {
+        *self.data == leaf.envelope()
+    }
+}
+
+/// Merge finding one cluster per scan of the tree
+fn merge_rescan(tree: &mut RTree<Group>, rule: &Rule) {
+    loop {
+        let envelope = tree
+            .iter()
+            .find(|piece| {
+                tree.locate_with_selection_function(SmartSelection { data: piece, rule })
+                    .count()
+                    > 1
+            })
+            .map(Group::envelope);
+        let envelope = match envelope {
+            None => break,
+            Some(val) => val,
+        };
+        for cur in tree
+            .drain_with_selection_function(EnvelopeSelection { data: &envelope })
+            .collect::<Vec<Group>>()
+        {
+            let merged = tree
+                .drain_with_selection_function(SmartSelection { data: &cur, rule })
+                .collect::<Vec<Group>>()
+                .into_iter()
+                .fold(cur, Group::merge);
+            tree.insert(merged);
+        }
+    }
+}
+
+/// Runs *merge* on the groups, returns its time and the number of groups left
+fn time_merge(groups: Vec<Group>, merge: impl FnOnce(Vec<Group>) -> usize) -> (Duration, usize) {
+    let started = Instant::now();
+    let count = merge(groups);
+    (started.elapsed(), count)
+}
+
+fn main() {
+    let rule = Rule::conway();
+    let mut tree = RTree::new();
+    tree.insert(library::get("lidka").unwrap().group(Coord { x: 0, y: 0 }));
+    let mut field = Field::new(tree);
+
+    println!("generations  groups  rescan ms  single-pass ms");
+    let (mut rescan_total, mut single_total) = (Duration::ZERO, Duration::ZERO);
+    while field.generation() < GENERATIONS {
+        for _ in WINDOW..CHECK_EVERY {
+            field.step();
+        }
+        let (mut rescan, mut single) = (Duration::ZERO, Duration::ZERO);
+        for _ in 0..WINDOW {
+            //next generation before merging
+            let unmerged: Vec<Group> = field
+                .field
+                .iter()
+                .cloned()
+                .filter_map(|group| group.step(&rule))
+                .flatten()
+                .collect();
+
+            let (time, rescan_count) = time_merge(unmerged.clone(), |groups| {
+                let mut tree = RTree::bulk_load(groups);
+                merge_rescan(&mut tree, &rule);
+                tree.size()
+            });
+            rescan += time;
+            let (time, single_count) = time_merge(unmerged, |groups| {
+                let mut field = Field::new(RTree::bulk_load(groups));
+                field.merge();
+                field.group_count()
+            });
+            single += time;
+            assert_eq!(rescan_count, single_count);
+            field.step();
+        }
+
+        println!(
+            "{:>11}  {:>6}  {:>9.3}  {:>14.3}",
+            format!("{}-{}", field.generation() - WINDOW + 1, field.generation()),
+            field.group_count(),
+            rescan.as_secs_f64() * 1000.0,
+            single.as_secs_f64() * 1000.0
+        );
+        rescan_total += rescan;
+        single_total += single;
+    }
+    println!(
+        "total: rescan {:.1} ms, single-pass {:.1} ms, {:.1}x faster",
+        rescan_total.as_secs_f64() * 1000.0,
+        single_total.as_secs_f64() * 1000.0,
+        rescan_total.as_secs_f64() / single_total.as_secs_f64()
+    );
+}
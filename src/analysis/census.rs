@@ -0,0 +1,235 @@
+//! Contains object census of a [Field] with <a href="https://conwaylife.com/wiki/Apgcode" target="_blank">apgcodes</a>
+//! compatible with Catagolue
+use super::period::{classify, normalize, Classification};
+use crate::groups::{field::Field, group::Group, rule::Rule, Coord};
+use std::collections::BTreeMap;
+
+/// Code given to groups that did not repeat within the period limit
+pub const PATHOLOGICAL: &str = "PATHOLOGICAL";
+
+/// Symbols of 5-cell columns in extended Wechsler format
+const COLUMN_SYMBOLS: &[u8] = b"0123456789abcdefghijklmnopqrstuv";
+
+/// Symbols following `y` for runs of 4 to 39 empty columns
+const GAP_SYMBOLS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Common names of frequent objects in B3/S23
+const NAMES: &[(&str, &str)] = &[
+    ("xs4_33", "block"),
+    ("xs6_696", "beehive"),
+    ("xs7_2596", "loaf"),
+    ("xs5_253", "boat"),
+    ("xs6_356", "ship"),
+    ("xs4_252", "tub"),
+    ("xs8_6996", "pond"),
+    ("xs6_25a4", "barge"),
+    ("xs7_25ac", "long boat"),
+    ("xp2_7", "blinker"),
+    ("xp2_7e", "toad"),
+    ("xp2_318c", "beacon"),
+    ("xp15_4r4z4r4", "pentadecathlon"),
+    ("xq4_153", "glider"),
+    ("xq4_6frc", "lightweight spaceship"),
+    ("xq4_27dee6", "middleweight spaceship"),
+    ("xq4_27deee6", "heavyweight spaceship"),
+];
+
+/// Returns the common name of an object with given apgcode if it is known
+pub fn common_name(apgcode: &str) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .find(|(code, _)| *code == apgcode)
+        .map(|(_, name)| *name)
+}
+
+/// Encodes cells with non negative coords in extended Wechsler format. Rows go down
+fn wechsler(cells: &[(i64, i64)]) -> String {
+    let width = cells.iter().map(|c| c.0).max().unwrap_or(-1) + 1;
+    let height = cells.iter().map(|c| c.1).max().unwrap_or(-1) + 1;
+    let strip_count = (height + 4) / 5;
+
+    let mut columns = vec![vec![0u8; width as usize]; strip_count as usize];
+    for (x, y) in cells {
+        columns[(y / 5) as usize][*x as usize] |= 1 << (y % 5);
+    }
+
+    let mut strips = Vec::new();
+    for strip in columns {
+        let mut code = String::new();
+        let mut zeroes = 0;
+        for column in strip {
+            if column == 0 {
+                zeroes += 1;
+                continue;
+            }
+            while zeroes > 0 {
+                let run = std::cmp::min(zeroes, 39);
+                match run {
+                    1 => code.push('0'),
+                    2 => code.push('w'),
+                    3 => code.push('x'),
+                    _ => {
+                        code.push('y');
+                        code.push(GAP_SYMBOLS[run - 4] as char);
+                    }
+                }
+                zeroes -= run;
+            }
+            code.push(COLUMN_SYMBOLS[column as usize] as char);
+        }
+        strips.push(code);
+    }
+    strips.join("z")
+}
+
+/// Returns the shortest (then lexicographically smallest) Wechsler code of cells among all 8
+/// rotations and reflections
+fn canonical_code(cells: &[Coord]) -> String {
+    let mut best: Option<String> = None;
+    for transform in 0..8 {
+        let mut moved: Vec<(i64, i64)> = cells
+            .iter()
+            .map(|c| {
+                let (x, y) = if transform & 4 != 0 {
+                    (c.y, c.x)
+                } else {
+                    (c.x, c.y)
+                };
+                let x = if transform & 1 != 0 { -x } else { x };
+                let y = if transform & 2 != 0 { -y } else { y };
+                (x, y)
+            })
+            .collect();
+        let x_min = moved.iter().map(|c| c.0).min().unwrap_or(0);
+        let y_min = moved.iter().map(|c| c.1).min().unwrap_or(0);
+        for cell in moved.iter_mut() {
+            *cell = (cell.0 - x_min, cell.1 - y_min);
+        }
+
+        let code = wechsler(&moved);
+        best = match best {
+            Some(val) if (val.len(), &val) <= (code.len(), &code) => Some(val),
+            _ => Some(code),
+        };
+    }
+    best.unwrap_or_default()
+}
+
+/// Returns apgcode of a group (e.g. `xs4_33`, `xp2_7`, `xq4_153`) under given [Rule]
+///
+/// Returns [None] if the group vanishes and [PATHOLOGICAL] if it does not repeat within
+/// *max_period* generations
+pub fn apgcode(group: &Group, rule: &Rule, max_period: u32) -> Option<String> {
+    let classification = classify(group, rule, max_period);
+    let (prefix, period) = match classification {
+        Classification::StillLife => ("xs", 1),
+        Classification::Oscillator { period } => ("xp", period),
+        Classification::Spaceship { period, .. } => ("xq", period),
+        Classification::Vanishes { .. } => return None,
+        Classification::Unknown => return Some(PATHOLOGICAL.to_string()),
+    };
+
+    let (mut block, coord) = normalize(group)?;
+    let mut best: Option<String> = None;
+    let mut population = 0;
+    for _ in 0..period {
+        let cells = Group::new(coord, block.clone()).alive_cells();
+        population = cells.len();
+        let code = canonical_code(&cells);
+        best = match best {
+            Some(val) if (val.len(), &val) <= (code.len(), &code) => Some(val),
+            _ => Some(code),
+        };
+        block.step(rule);
+        block.resize();
+    }
+
+    let code = best.unwrap_or_default();
+    match classification {
+        Classification::StillLife => Some(format!("{}{}_{}", prefix, population, code)),
+        _ => Some(format!("{}{}_{}", prefix, period, code)),
+    }
+}
+
+/// Counts objects of the field by their apgcodes. Every [Group] of the field is treated as one
+/// object, so the field should be stabilized for meaningful results
+pub fn census(field: &Field, max_period: u32) -> BTreeMap<String, u64> {
+    let rule = field.rule();
+    let mut counts = BTreeMap::new();
+    for group in &field.field {
+        if let Some(code) = apgcode(group, &rule, max_period) {
+            *counts.entry(code).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::{block::Block, rle};
+    use rstar::RTree;
+
+    fn group(pattern: &str) -> Group {
+        Group::new(Coord { x: 0, y: 0 }, rle::parse(pattern).unwrap().block)
+    }
+
+    fn code(pattern: &str) -> String {
+        apgcode(&group(pattern), &Rule::conway(), 100).unwrap()
+    }
+
+    #[test]
+    fn census_apgcode() {
+        assert_eq!(code("x = 2, y = 2\n2o$2o!"), "xs4_33");
+        assert_eq!(code("x = 3, y = 4\nbo$obo$obo$bo!"), "xs6_696");
+        assert_eq!(code("x = 4, y = 4\nb2o$o2bo$bobo$2bo!"), "xs7_2596");
+        assert_eq!(code("x = 3, y = 3\n2o$obo$bo!"), "xs5_253");
+        assert_eq!(code("x = 4, y = 4\nb2o$o2bo$o2bo$b2o!"), "xs8_6996");
+        assert_eq!(code("x = 3, y = 1\n3o!"), "xp2_7");
+        assert_eq!(code("x = 4, y = 2\nb3o$3o!"), "xp2_7e");
+        assert_eq!(code("x = 4, y = 4\n2o$o$3bo$2b2o!"), "xp2_318c");
+        assert_eq!(code("x = 3, y = 3\nbo$2bo$3o!"), "xq4_153");
+        assert_eq!(code("x = 5, y = 4\nbo2bo$o4b$o3bo$4o!"), "xq4_6frc");
+        assert_eq!(
+            code("x = 10, y = 3\n2bo4bo2b$2ob4ob2o$2bo4bo!"),
+            "xp15_4r4z4r4"
+        );
+        assert_eq!(
+            apgcode(&group("x = 3, y = 3\nb2o$2o$bo!"), &Rule::conway(), 100),
+            Some(PATHOLOGICAL.to_string())
+        );
+        assert_eq!(
+            apgcode(&group("x = 1, y = 1\no!"), &Rule::conway(), 100),
+            None
+        );
+    }
+
+    #[test]
+    fn census_wechsler_gaps() {
+        let cells: Vec<(i64, i64)> = vec![(0, 0), (5, 0), (47, 6)];
+        assert_eq!(wechsler(&cells), "1y01zyzy42");
+    }
+
+    #[test]
+    fn census_field() {
+        let mut block = Block::new(4, 4);
+        block[(1, 1)] = 1;
+        block[(1, 2)] = 1;
+        block[(2, 1)] = 1;
+        block[(2, 2)] = 1;
+
+        let mut tree = RTree::new();
+        tree.insert(Group::new(Coord { x: 0, y: 0 }, block.clone()));
+        tree.insert(Group::new(Coord { x: 10, y: 0 }, block));
+        tree.insert(Group::new(
+            Coord { x: 20, y: 0 },
+            rle::parse("x = 3, y = 1\n3o!").unwrap().block,
+        ));
+        let field = Field::new(tree);
+
+        let counts = census(&field, 100);
+        assert_eq!(counts.get("xs4_33"), Some(&2));
+        assert_eq!(counts.get("xp2_7"), Some(&1));
+        assert_eq!(common_name("xp2_7"), Some("blinker"));
+    }
+}
@@ -0,0 +1,6 @@
+//! Analyzers of [groups](crate::groups::group::Group) and [fields](crate::groups::field::Field)
+
+pub mod census;
+pub mod period;
+pub mod stability;
+pub mod stats;
@@ -0,0 +1,174 @@
+//! Contains period and displacement detection for a single [Group]
+use crate::groups::{block::Block, group::Group, rule::Rule, Coord};
+
+/// Long-term behaviour of a [Group] evolving in isolation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Classification {
+    /// Does not change
+    StillLife,
+    /// Returns to the same state at the same place every *period* generations
+    Oscillator { period: u32 },
+    /// Returns to the same state moved by *(dx, dy)* cells every *period* generations
+    Spaceship { period: u32, dx: i64, dy: i64 },
+    /// All cells die at *generation*
+    Vanishes { generation: u32 },
+    /// No repetition found within the given number of generations
+    Unknown,
+}
+
+impl Classification {
+    /// Returns the period for still lifes (1), oscillators and spaceships
+    pub fn period(&self) -> Option<u32> {
+        match self {
+            Classification::StillLife => Some(1),
+            Classification::Oscillator { period } => Some(*period),
+            Classification::Spaceship { period, .. } => Some(*period),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Classification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Classification::StillLife => write!(f, "still life"),
+            Classification::Oscillator { period } => write!(f, "p{} oscillator", period),
+            Classification::Spaceship { period, dx, dy } => {
+                write!(f, "p{} spaceship moving ({}, {})", period, dx, dy)
+            }
+            Classification::Vanishes { generation } => {
+                write!(f, "vanishes at generation {}", generation)
+            }
+            Classification::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Returns block of the group normalized to have exactly one empty cell border together with its
+/// new global coords. [None] if the group has no alive cells
+pub fn normalize(group: &Group) -> Option<(Block, Coord)> {
+    let mut block = group.block.clone();
+    let offset = block.resize()?;
+    Some((block, group.global_coord + offset))
+}
+
+/// Steps a copy of **group** up to *max_period* generations under given [Rule] looking for the
+/// first generation where its normalized [Block] repeats
+///
+/// The whole group is stepped as one block, so pieces it emits (e.g. gliders) stay a part of it
+/// and prevent repetition
+pub fn classify(group: &Group, rule: &Rule, max_period: u32) -> Classification {
+    let (start, start_coord) = match normalize(group) {
+        None => return Classification::Vanishes { generation: 0 },
+        Some(val) => val,
+    };
+
+    let mut block = start.clone();
+    let mut coord = start_coord;
+    for generation in 1..=max_period {
+        block.step(rule);
+        coord = match block.resize() {
+            None => return Classification::Vanishes { generation },
+            Some(offset) => coord + offset,
+        };
+
+        if block == start {
+            let dx = coord.x - start_coord.x;
+            let dy = coord.y - start_coord.y;
+            return match (generation, dx, dy) {
+                (1, 0, 0) => Classification::StillLife,
+                (period, 0, 0) => Classification::Oscillator { period },
+                (period, dx, dy) => Classification::Spaceship { period, dx, dy },
+            };
+        }
+    }
+    Classification::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::rle;
+
+    fn group(pattern: &str) -> Group {
+        let mut group = Group::new(Coord { x: 0, y: 0 }, rle::parse(pattern).unwrap().block);
+        group.reverse_y();
+        group
+    }
+
+    #[test]
+    fn classify_still_life() {
+        let beehive = group("x = 4, y = 3\nb2o$o2bo$b2o!");
+        assert_eq!(
+            classify(&beehive, &Rule::conway(), 10),
+            Classification::StillLife
+        );
+    }
+
+    #[test]
+    fn classify_oscillator() {
+        let blinker = group("x = 3, y = 1\n3o!");
+        assert_eq!(
+            classify(&blinker, &Rule::conway(), 10),
+            Classification::Oscillator { period: 2 }
+        );
+
+        let pulsar = group(
+            "x = 13, y = 13\n2b3o3b3o2b2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2b2$2b3o3b3o2b$\
+             o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+        );
+        assert_eq!(classify(&pulsar, &Rule::conway(), 10).period(), Some(3));
+    }
+
+    #[test]
+    fn classify_spaceship() {
+        let glider = group("x = 3, y = 3\nbo$2bo$3o!");
+        assert_eq!(
+            classify(&glider, &Rule::conway(), 10),
+            Classification::Spaceship {
+                period: 4,
+                dx: 1,
+                dy: -1
+            }
+        );
+
+        let lwss = group("x = 5, y = 4\nbo2bo$o4b$o3bo$4o!");
+        assert_eq!(
+            classify(&lwss, &Rule::conway(), 10),
+            Classification::Spaceship {
+                period: 4,
+                dx: -2,
+                dy: 0
+            }
+        );
+    }
+
+    #[test]
+    fn classification_display() {
+        let glider = Classification::Spaceship {
+            period: 4,
+            dx: 1,
+            dy: -1,
+        };
+        assert_eq!(glider.to_string(), "p4 spaceship moving (1, -1)");
+        assert_eq!(
+            Classification::Oscillator { period: 2 }.to_string(),
+            "p2 oscillator"
+        );
+    }
+
+    #[test]
+    fn classify_other() {
+        let diehard = group("x = 8, y = 3\n6bob$2o6b$bo3b3o!");
+        assert_eq!(
+            classify(&diehard, &Rule::conway(), 200),
+            Classification::Vanishes { generation: 130 }
+        );
+
+        let r_pentomino = group("x = 3, y = 3\nb2o$2o$bo!");
+        assert_eq!(
+            classify(&r_pentomino, &Rule::conway(), 100),
+            Classification::Unknown
+        );
+    }
+}
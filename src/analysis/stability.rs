@@ -0,0 +1,190 @@
+//! Contains stabilization detection for a whole [Field]
+use super::period::{classify, normalize, Classification};
+use crate::groups::{field::Field, group::Group, rule::Rule, topology::Topology, Coord};
+
+/// Envelope of a group over one period together with its velocity
+struct Motion {
+    lower: Coord,
+    upper: Coord,
+    ///Displacement per period
+    dx: i64,
+    dy: i64,
+    period: u32,
+}
+
+impl Motion {
+    /// Steps a copy of **group** through one period collecting envelopes of all phases
+    fn new(group: &Group, rule: &Rule, period: u32, dx: i64, dy: i64) -> Option<Motion> {
+        let (mut block, mut coord) = normalize(group)?;
+        let mut lower = coord;
+        let mut upper = Group::new(coord, block.clone()).top_right();
+        for _ in 0..period {
+            block.step(rule);
+            coord = coord + block.resize()?;
+            let top_right = Group::new(coord, block.clone()).top_right();
+            lower = Coord {
+                x: std::cmp::min(lower.x, coord.x),
+                y: std::cmp::min(lower.y, coord.y),
+            };
+            upper = Coord {
+                x: std::cmp::max(upper.x, top_right.x),
+                y: std::cmp::max(upper.y, top_right.y),
+            };
+        }
+        Some(Motion {
+            lower,
+            upper,
+            dx,
+            dy,
+            period,
+        })
+    }
+
+    fn velocity(&self) -> (f64, f64) {
+        (
+            self.dx as f64 / self.period as f64,
+            self.dy as f64 / self.period as f64,
+        )
+    }
+
+    /// Checks if envelopes of **self** and *other* can ever overlap while both keep moving.
+    /// Envelopes include the empty border of the blocks, so overlapping means cells are close
+    /// enough to interact
+    fn may_meet(&self, other: &Motion) -> bool {
+        let (self_vx, self_vy) = self.velocity();
+        let (other_vx, other_vy) = other.velocity();
+
+        //time interval on which the envelopes overlap along one axis
+        let overlap = |lower: i64, upper: i64, other_lower: i64, other_upper: i64, v: f64| {
+            let (start, end) = ((other_lower - upper) as f64, (other_upper - lower) as f64);
+            if v == 0.0 {
+                match start <= 0.0 && end >= 0.0 {
+                    true => (f64::NEG_INFINITY, f64::INFINITY),
+                    false => (f64::INFINITY, f64::NEG_INFINITY),
+                }
+            } else if v > 0.0 {
+                (start / v, end / v)
+            } else {
+                (end / v, start / v)
+            }
+        };
+
+        let x = overlap(
+            self.lower.x,
+            self.upper.x,
+            other.lower.x,
+            other.upper.x,
+            self_vx - other_vx,
+        );
+        let y = overlap(
+            self.lower.y,
+            self.upper.y,
+            other.lower.y,
+            other.upper.y,
+            self_vy - other_vy,
+        );
+        let start = x.0.max(y.0).max(0.0);
+        let end = x.1.min(y.1);
+        start <= end
+    }
+}
+
+/// Checks if the field has stabilized: every [Group] is a still life, an oscillator or a
+/// spaceship with period up to *max_period*, and no spaceship is on its way to meet another
+/// group.
+///
+/// Groups that do not move relative to each other are not checked against each other, as
+/// [Field::merge()] already joins them when they interact. Spaceships never escape from a finite
+/// [Topology], so such fields are stable only without them
+pub fn is_stable(field: &Field, max_period: u32) -> bool {
+    let rule = field.rule();
+    let mut motions = Vec::new();
+    for group in &field.field {
+        let motion = match classify(group, &rule, max_period) {
+            Classification::StillLife => Motion::new(group, &rule, 1, 0, 0),
+            Classification::Oscillator { period } => Motion::new(group, &rule, period, 0, 0),
+            Classification::Spaceship { period, dx, dy } => {
+                Motion::new(group, &rule, period, dx, dy)
+            }
+            Classification::Vanishes { .. } | Classification::Unknown => return false,
+        };
+        match motion {
+            Some(val) => motions.push(val),
+            None => return false,
+        }
+    }
+
+    for (index, ship) in motions.iter().enumerate() {
+        if ship.dx == 0 && ship.dy == 0 {
+            continue;
+        }
+        if field.topology() != Topology::Plane {
+            return false;
+        }
+        for (other_index, other) in motions.iter().enumerate() {
+            if index != other_index && ship.velocity() != other.velocity() && ship.may_meet(other) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::rle;
+    use rstar::RTree;
+
+    fn field(groups: &[(&str, Coord)]) -> Field {
+        let mut tree = RTree::new();
+        for (pattern, coord) in groups {
+            tree.insert(Group::new(*coord, rle::parse(pattern).unwrap().block));
+        }
+        let mut field = Field::new(tree);
+        field.merge();
+        field
+    }
+
+    const BLOCK: &str = "x = 2, y = 2\n2o$2o!";
+    const BLINKER: &str = "x = 3, y = 1\n3o!";
+    //moves by (1, -1) every 4 generations in field coordinates
+    const GLIDER: &str = "x = 3, y = 3\n3o$2bo$bo!";
+
+    #[test]
+    fn stable_ash() {
+        let ash = field(&[
+            (BLOCK, Coord { x: 0, y: 0 }),
+            (BLINKER, Coord { x: 10, y: 0 }),
+        ]);
+        assert!(is_stable(&ash, 10));
+        assert!(is_stable(&field(&[]), 10));
+    }
+
+    #[test]
+    fn stable_spaceships() {
+        let escaping = field(&[
+            (BLOCK, Coord { x: 0, y: 0 }),
+            (GLIDER, Coord { x: 10, y: -10 }),
+        ]);
+        assert!(is_stable(&escaping, 10));
+
+        let incoming = field(&[
+            (BLOCK, Coord { x: 0, y: 0 }),
+            (GLIDER, Coord { x: -20, y: 20 }),
+        ]);
+        assert!(!is_stable(&incoming, 10));
+
+        let passing = field(&[
+            (BLOCK, Coord { x: 0, y: 0 }),
+            (GLIDER, Coord { x: -40, y: 20 }),
+        ]);
+        assert!(is_stable(&passing, 10));
+    }
+
+    #[test]
+    fn stable_active() {
+        let r_pentomino = field(&[("x = 3, y = 3\nb2o$2o$bo!", Coord { x: 0, y: 0 })]);
+        assert!(!is_stable(&r_pentomino, 10));
+    }
+}
@@ -0,0 +1,151 @@
+//! Contains per generation statistics of a [Field] recorded with [Field::record_stats()]
+use crate::groups::{field::Field, Coord};
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Metrics of one generation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sample {
+    pub generation: u64,
+    pub population: u64,
+    pub groups: usize,
+    /// Bottom left and top right corners of the envelope of all groups (including their empty
+    /// borders). [None] if the field is empty
+    pub bounds: Option<(Coord, Coord)>,
+    /// Time spent on the step that produced this generation. Zero for the first sample
+    pub step_time: Duration,
+}
+
+impl Sample {
+    /// Takes metrics of the current generation of the field
+    pub fn new(field: &Field, step_time: Duration) -> Sample {
+        Sample {
+            generation: field.generation(),
+            population: field.population(),
+            groups: field.group_count(),
+            bounds: field.bottom_left().zip(field.top_right()),
+            step_time,
+        }
+    }
+}
+
+/// Samples of consecutive generations
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    samples: Vec<Sample>,
+}
+
+impl Stats {
+    /// Creates an empty recorder
+    pub fn new() -> Self {
+        Stats {
+            samples: Vec::new(),
+        }
+    }
+
+    /// Adds a sample of the current generation of the field
+    pub fn record(&mut self, field: &Field, step_time: Duration) {
+        self.samples.push(Sample::new(field, step_time));
+    }
+
+    /// Returns recorded samples in order of recording
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// Drops samples of generations after *generation*
+    pub fn truncate(&mut self, generation: u64) {
+        self.samples
+            .retain(|sample| sample.generation <= generation);
+    }
+
+    /// Exports samples as CSV with a header line. Bounds of an empty field are left empty
+    pub fn to_csv(&self) -> String {
+        let mut out =
+            String::from("generation,population,groups,x_min,y_min,x_max,y_max,step_us\n");
+        for sample in &self.samples {
+            let bounds = match sample.bounds {
+                Some((bl, tr)) => format!("{},{},{},{}", bl.x, bl.y, tr.x, tr.y),
+                None => String::from(",,,"),
+            };
+            writeln!(
+                out,
+                "{},{},{},{},{}",
+                sample.generation,
+                sample.population,
+                sample.groups,
+                bounds,
+                sample.step_time.as_micros()
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// Exports samples as JSON lines, one object per generation. Bounds are
+    /// `[x_min, y_min, x_max, y_max]` or `null` for an empty field
+    pub fn to_json_lines(&self) -> String {
+        let mut out = String::new();
+        for sample in &self.samples {
+            let bounds = match sample.bounds {
+                Some((bl, tr)) => format!("[{},{},{},{}]", bl.x, bl.y, tr.x, tr.y),
+                None => String::from("null"),
+            };
+            writeln!(
+                out,
+                "{{\"generation\":{},\"population\":{},\"groups\":{},\"bounds\":{},\"step_us\":{}}}",
+                sample.generation,
+                sample.population,
+                sample.groups,
+                bounds,
+                sample.step_time.as_micros()
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::{group::Group, rle};
+    use rstar::RTree;
+
+    #[test]
+    fn stats_export() {
+        let mut tree = RTree::new();
+        tree.insert(Group::new(
+            Coord { x: 0, y: 0 },
+            rle::parse("x = 3, y = 1\n3o!").unwrap().block,
+        ));
+        let mut field = Field::new(tree);
+        field.record_stats();
+        field.step();
+        field.step();
+
+        let stats = field.stats().unwrap();
+        assert_eq!(stats.samples().len(), 3);
+        assert_eq!(stats.samples()[0].step_time, Duration::ZERO);
+        assert!(stats
+            .samples()
+            .iter()
+            .all(|s| s.population == 3 && s.groups == 1));
+
+        let csv = stats.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("0,3,1,0,0,2,0,"));
+        assert!(lines[2].starts_with("1,3,1,0,-2,2,2,"));
+
+        let json = stats.to_json_lines();
+        assert!(json.lines().nth(2).unwrap().starts_with(
+            "{\"generation\":2,\"population\":3,\"groups\":1,\"bounds\":[-1,-1,3,1],"
+        ));
+
+        let mut empty = Stats::new();
+        empty.record(&Field::new(RTree::new()), Duration::ZERO);
+        assert_eq!(empty.to_csv().lines().nth(1), Some("0,0,0,,,,,0"));
+        assert!(empty.to_json_lines().contains("\"bounds\":null"));
+    }
+}
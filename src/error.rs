@@ -0,0 +1,132 @@
+//! Contains [Error] returned by fallible operations of the crate
+use crate::groups::{format::FormatError, rle::RleError, rule::RuleError, topology::TopologyError};
+use crate::hashlife::macrocell::MacrocellError;
+
+/// Crate-wide error. Parse errors of every format are wrapped as they are, so their line and
+/// column can still be inspected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Rle(RleError),
+    Format(FormatError),
+    Macrocell(MacrocellError),
+    Rule(RuleError),
+    Topology(TopologyError),
+    /// Name not found in the [library](crate::library)
+    UnknownPattern(String),
+    /// Block would have more cells than can be indexed with `u32`
+    BlockTooLarge {
+        x_size: u64,
+        y_size: u64,
+    },
+    /// Reading or writing the file at the path failed for the reason
+    Io {
+        path: String,
+        reason: String,
+    },
+    /// Side of a GIF image is longer than 65535 pixels
+    GifTooLarge {
+        width: u32,
+        height: u32,
+    },
+    /// Worker thread of [ThreadPool](crate::groups::pool::ThreadPool) stopped before returning
+    /// its groups
+    WorkerDied,
+    /// Stepping groups in a worker thread panicked with the message
+    WorkerPanicked(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Rle(err) => err.fmt(f),
+            Error::Format(err) => err.fmt(f),
+            Error::Macrocell(err) => err.fmt(f),
+            Error::Rule(err) => err.fmt(f),
+            Error::Topology(err) => err.fmt(f),
+            Error::UnknownPattern(name) => write!(f, "unknown pattern '{}'", name),
+            Error::BlockTooLarge { x_size, y_size } => {
+                write!(f, "block of {}x{} cells is too large", x_size, y_size)
+            }
+            Error::Io { path, reason } => write!(f, "cannot access {}: {}", path, reason),
+            Error::GifTooLarge { width, height } => write!(
+                f,
+                "image of {}x{} pixels is too large for GIF, save it as APNG (.png) instead",
+                width, height
+            ),
+            Error::WorkerDied => write!(f, "worker thread died before returning its groups"),
+            Error::WorkerPanicked(message) => write!(f, "worker thread panicked: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Rle(err) => Some(err),
+            Error::Format(err) => Some(err),
+            Error::Macrocell(err) => Some(err),
+            Error::Rule(err) => Some(err),
+            Error::Topology(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Wraps an I/O error of the file at *path*. [std::io::Error] is neither [Clone] nor [Eq], so
+    /// only its message is kept
+    pub fn io(path: &str, err: std::io::Error) -> Error {
+        Error::Io {
+            path: path.to_string(),
+            reason: err.to_string(),
+        }
+    }
+}
+
+impl From<RleError> for Error {
+    fn from(err: RleError) -> Self {
+        Error::Rle(err)
+    }
+}
+
+impl From<FormatError> for Error {
+    fn from(err: FormatError) -> Self {
+        Error::Format(err)
+    }
+}
+
+impl From<MacrocellError> for Error {
+    fn from(err: MacrocellError) -> Self {
+        Error::Macrocell(err)
+    }
+}
+
+impl From<RuleError> for Error {
+    fn from(err: RuleError) -> Self {
+        Error::Rule(err)
+    }
+}
+
+impl From<TopologyError> for Error {
+    fn from(err: TopologyError) -> Self {
+        Error::Topology(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::rule::Rule;
+
+    #[test]
+    fn error_conversion() {
+        let parse = |rule: &str| -> Result<Rule, Error> { Ok(rule.parse::<Rule>()?) };
+        let err = parse("B9/S23").unwrap_err();
+        assert!(matches!(err, Error::Rule(_)));
+        assert!(std::error::Error::source(&err).is_some());
+        assert_eq!(
+            Error::UnknownPattern(String::from("gun")).to_string(),
+            "unknown pattern 'gun'"
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! Contains [BitBlock] struct - bit-packed cell storage of [Block](super::block::Block)
+use super::rule::Rule;
+
+/// Number of cells packed into one word
+const WORD_BITS: u32 = u64::BITS;
+
+/// A rectangular block of cells packed 64 cells per word, row by row.
+///
+/// Cell (x, y) is stored in bit `x % 64` of word `y * words_per_row + x / 64`. Bits past
+/// **x_size** in the last word of a row are always zero. Stepping processes 64 cells at once with
+/// bitwise adders instead of counting neighbours one by one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitBlock {
+    pub x_size: u32,
+    pub y_size: u32,
+    words_per_row: u32,
+    data: Vec<u64>,
+}
+
+impl BitBlock {
+    /// Creates an empty block with given size
+    pub fn new(x_size: u32, y_size: u32) -> BitBlock {
+        let words_per_row = x_size.div_ceil(WORD_BITS);
+        BitBlock {
+            x_size,
+            y_size,
+            words_per_row,
+            data: vec![0; words_per_row as usize * y_size as usize],
+        }
+    }
+
+    /// Returns cell state
+    /// # Panics
+    /// When index is out of bounds
+    pub fn get(&self, x: u32, y: u32) -> bool {
+        assert!(x < self.x_size, "BitBlock indexed on not existing cell");
+        self.row(y)[(x / WORD_BITS) as usize] >> (x % WORD_BITS) & 1 == 1
+    }
+
+    /// Sets cell state
+    /// # Panics
+    /// When index is out of bounds
+    pub fn set(&mut self, x: u32, y: u32, alive: bool) {
+        assert!(x < self.x_size, "BitBlock indexed on not existing cell");
+        let word = &mut self.row_mut(y)[(x / WORD_BITS) as usize];
+        let mask = 1 << (x % WORD_BITS);
+        match alive {
+            true => *word |= mask,
+            false => *word &= !mask,
+        }
+    }
+
+    /// Returns number of alive cells
+    pub fn population(&self) -> u64 {
+        self.data.iter().map(|word| word.count_ones() as u64).sum()
+    }
+
+    /// Returns number of alive cells in a row
+    /// # Panics
+    /// When index is out of bounds
+    pub fn row_population(&self, y: u32) -> u32 {
+        self.row(y).iter().map(|word| word.count_ones()).sum()
+    }
+
+    /// Returns number of alive cells in a column
+    /// # Panics
+    /// When index is out of bounds
+    pub fn column_population(&self, x: u32) -> u32 {
+        assert!(x < self.x_size, "BitBlock indexed on not existing cell");
+        let (word, shift) = ((x / WORD_BITS) as usize, x % WORD_BITS);
+        self.data
+            .chunks_exact(self.words_per_row as usize)
+            .map(|row| (row[word] >> shift & 1) as u32)
+            .sum()
+    }
+
+    /// Returns a copy of rows from *from* to *to* (not included)
+    /// # Panics
+    /// When the rows are out of bounds
+    pub fn rows(&self, from: u32, to: u32) -> BitBlock {
+        let words = self.words_per_row as usize;
+        BitBlock {
+            x_size: self.x_size,
+            y_size: to - from,
+            words_per_row: self.words_per_row,
+            data: self.data[from as usize * words..to as usize * words].to_vec(),
+        }
+    }
+
+    /// Overwrites rows starting from *at* with rows from *from* to *to* (not included) of
+    /// *other* block of the same width
+    /// # Panics
+    /// When widths differ or the rows are out of bounds
+    pub fn set_rows(&mut self, at: u32, other: &BitBlock, from: u32, to: u32) {
+        assert_eq!(self.x_size, other.x_size, "Blocks of different width");
+        let words = self.words_per_row as usize;
+        let len = (to - from) as usize * words;
+        let (start, other_start) = (at as usize * words, from as usize * words);
+        self.data[start..start + len].copy_from_slice(&other.data[other_start..other_start + len]);
+    }
+
+    /// Returns a copy of *x_size* by *y_size* cells starting from (*x*, *y*)
+    /// # Panics
+    /// When the cells are out of bounds
+    pub fn sub(&self, x: u32, y: u32, x_size: u32, y_size: u32) -> BitBlock {
+        assert!(
+            x + x_size <= self.x_size,
+            "BitBlock indexed on not existing cell"
+        );
+        let mut piece = BitBlock::new(x_size, y_size);
+        for row in 0..y_size {
+            or_bits(piece.row_mut(row), 0, self.row(y + row), x, x_size);
+        }
+        piece
+    }
+
+    /// Adds alive cells of *other* to the block, with its bottom left cell at (*x*, *y*)
+    /// # Panics
+    /// When *other* does not fit into the block
+    pub fn or_at(&mut self, x: u32, y: u32, other: &BitBlock) {
+        assert!(
+            x + other.x_size <= self.x_size,
+            "BitBlock indexed on not existing cell"
+        );
+        for row in 0..other.y_size {
+            or_bits(self.row_mut(y + row), x, other.row(row), 0, other.x_size);
+        }
+    }
+
+    /// Enlarges the block in place to *x_size* by *y_size* cells (not smaller than the current
+    /// size), adding empty columns on the right and empty rows on the top
+    pub fn grow(&mut self, x_size: u32, y_size: u32) {
+        let old = self.words_per_row as usize;
+        let words_per_row = x_size.div_ceil(WORD_BITS);
+        let new = words_per_row as usize;
+        self.data.resize(new * y_size as usize, 0);
+        if new != old {
+            //rows move up, so the top ones are moved first
+            for y in (0..self.y_size as usize).rev() {
+                self.data.copy_within(y * old..(y + 1) * old, y * new);
+                self.data[y * new + old..(y + 1) * new].fill(0);
+            }
+        }
+        self.x_size = x_size;
+        self.y_size = y_size;
+        self.words_per_row = words_per_row;
+    }
+
+    /// Shrinks the block in place to its first *x_size* columns and *y_size* rows
+    /// # Panics
+    /// When the new size is larger than the current one
+    pub fn truncate(&mut self, x_size: u32, y_size: u32) {
+        assert!(
+            x_size <= self.x_size && y_size <= self.y_size,
+            "BitBlock indexed on not existing cell"
+        );
+        let old = self.words_per_row as usize;
+        let words_per_row = x_size.div_ceil(WORD_BITS);
+        let new = words_per_row as usize;
+        if new != old {
+            //rows move down, so the bottom ones are moved first
+            for y in 1..y_size as usize {
+                self.data.copy_within(y * old..y * old + new, y * new);
+            }
+        }
+        self.data.truncate(new * y_size as usize);
+        let narrower = x_size != self.x_size;
+        self.x_size = x_size;
+        self.y_size = y_size;
+        self.words_per_row = words_per_row;
+        if narrower && new > 0 {
+            let mask = self.word_mask(new - 1);
+            for row in self.data.chunks_exact_mut(new) {
+                row[new - 1] &= mask;
+            }
+        }
+    }
+
+    /// Returns the lowest row and the leftmost cell in it of alive cells, [None] if there are none
+    pub fn first_alive(&self) -> Option<(u32, u32)> {
+        let words = self.words_per_row as usize;
+        let index = self.data.iter().position(|word| *word != 0)?;
+        let x = (index % words) as u32 * WORD_BITS + self.data[index].trailing_zeros();
+        Some((x, (index / words) as u32))
+    }
+
+    /// Kills cells alive in *other* block of the same size
+    pub fn remove(&mut self, other: &BitBlock) {
+        for (word, other) in self.data.iter_mut().zip(&other.data) {
+            *word &= !other;
+        }
+    }
+
+    /// Returns alive cells reached from the alive cell (*x*, *y*) through steps of at most 2
+    /// cells in any direction. Cells further apart have no common neighbours, so these are the
+    /// cells interacting with the given one. Reached cells grow in all directions 2 cells at a
+    /// time, row by row
+    pub fn component(&self, x: u32, y: u32) -> BitBlock {
+        let words = self.words_per_row as usize;
+        let mut reached = BitBlock::new(self.x_size, self.y_size);
+        reached.set(x, y, true);
+        let mut spread = vec![0; self.data.len()];
+        loop {
+            //cells within 2 columns of reached ones
+            for (row, out) in reached
+                .data
+                .chunks_exact(words)
+                .zip(spread.chunks_exact_mut(words))
+            {
+                for word in 0..words {
+                    let (left, right) = (
+                        row.get(word.wrapping_sub(1)).copied().unwrap_or(0),
+                        row.get(word + 1).copied().unwrap_or(0),
+                    );
+                    out[word] = row[word]
+                        | row[word] << 1
+                        | row[word] << 2
+                        | left >> (WORD_BITS - 1)
+                        | left >> (WORD_BITS - 2)
+                        | row[word] >> 1
+                        | row[word] >> 2
+                        | right << (WORD_BITS - 1)
+                        | right << (WORD_BITS - 2);
+                }
+            }
+
+            //alive cells within 2 rows of those
+            let mut grown = false;
+            for y in 0..self.y_size as usize {
+                let near = y.saturating_sub(2)..std::cmp::min(y + 3, self.y_size as usize);
+                for word in 0..words {
+                    let spread = near
+                        .clone()
+                        .fold(0, |val, row| val | spread[row * words + word]);
+                    let index = y * words + word;
+                    let next = spread & self.data[index];
+                    if next != reached.data[index] {
+                        reached.data[index] = next;
+                        grown = true;
+                    }
+                }
+            }
+            if !grown {
+                return reached;
+            }
+        }
+    }
+
+    /// Returns words of a row
+    fn row(&self, y: u32) -> &[u64] {
+        let start = (y * self.words_per_row) as usize;
+        &self.data[start..start + self.words_per_row as usize]
+    }
+
+    /// Returns mutable words of a row
+    fn row_mut(&mut self, y: u32) -> &mut [u64] {
+        let start = (y * self.words_per_row) as usize;
+        &mut self.data[start..start + self.words_per_row as usize]
+    }
+
+    /// Mask of bits belonging to the block in given word of a row
+    fn word_mask(&self, word: usize) -> u64 {
+        let tail = self.x_size % WORD_BITS;
+        if word + 1 == self.words_per_row as usize && tail != 0 {
+            (1 << tail) - 1
+        } else {
+            u64::MAX
+        }
+    }
+
+    /// Changes the state of the block to the next generation under given [Rule] (does not change
+    /// size). Cells outside of the block are considered dead
+    pub fn step(&mut self, rule: &Rule) {
+        let empty = vec![0; self.words_per_row as usize];
+        let mut next = vec![0; self.data.len()];
+
+        for y in 0..self.y_size {
+            let below = match y {
+                0 => &empty[..],
+                _ => self.row(y - 1),
+            };
+            let above = match y + 1 == self.y_size {
+                true => &empty[..],
+                false => self.row(y + 1),
+            };
+            let center = self.row(y);
+
+            for word in 0..self.words_per_row as usize {
+                //bit planes of the neighbour count
+                let mut count = [0u64; 4];
+                for row in [below, center, above] {
+                    let left = row.get(word.wrapping_sub(1)).map_or(0, |w| w >> 63);
+                    let right = row.get(word + 1).map_or(0, |w| w << 63);
+                    add_bits(&mut count, row[word] << 1 | left);
+                    add_bits(&mut count, row[word] >> 1 | right);
+                }
+                add_bits(&mut count, below[word]);
+                add_bits(&mut count, above[word]);
+
+                let alive = center[word];
+                let mut result = 0;
+                for neighbours in 0..=8u8 {
+                    let survive = rule.survives(neighbours);
+                    let born = rule.born(neighbours);
+                    if !survive && !born {
+                        continue;
+                    }
+                    let matching = count_equals(&count, neighbours);
+                    if survive {
+                        result |= matching & alive;
+                    }
+                    if born {
+                        result |= matching & !alive;
+                    }
+                }
+                next[y as usize * self.words_per_row as usize + word] =
+                    result & self.word_mask(word);
+            }
+        }
+        self.data = next;
+    }
+}
+
+/// Returns 64 bits of the row starting from bit *start*. Bits past the row are zero
+fn bits_at(row: &[u64], start: u32) -> u64 {
+    let (word, shift) = ((start / WORD_BITS) as usize, start % WORD_BITS);
+    let low = row.get(word).map_or(0, |val| val >> shift);
+    match shift {
+        0 => low,
+        _ => {
+            low | row
+                .get(word + 1)
+                .map_or(0, |val| val << (WORD_BITS - shift))
+        }
+    }
+}
+
+/// Adds *len* bits of *src* row starting from bit *from* to *dst* row starting from bit *at*
+fn or_bits(dst: &mut [u64], at: u32, src: &[u64], from: u32, len: u32) {
+    let mut done = 0;
+    while done < len {
+        let (word, shift) = (((at + done) / WORD_BITS) as usize, (at + done) % WORD_BITS);
+        let take = std::cmp::min(WORD_BITS - shift, len - done);
+        let mask = match take {
+            WORD_BITS => u64::MAX,
+            _ => (1 << take) - 1,
+        };
+        dst[word] |= (bits_at(src, from + done) & mask) << shift;
+        done += take;
+    }
+}
+
+/// Adds one bit per cell to 4-plane counters
+fn add_bits(count: &mut [u64; 4], bits: u64) {
+    let mut carry = bits;
+    for plane in count.iter_mut() {
+        let next_carry = *plane & carry;
+        *plane ^= carry;
+        carry = next_carry;
+    }
+}
+
+/// Returns mask of cells which counter equals *value*
+fn count_equals(count: &[u64; 4], value: u8) -> u64 {
+    let mut mask = u64::MAX;
+    for (bit, plane) in count.iter().enumerate() {
+        mask &= match value >> bit & 1 {
+            1 => *plane,
+            _ => !*plane,
+        };
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::{block::Block, UCoord};
+
+    /// Pseudo random block, dense enough to have all neighbour counts
+    fn noise(x_size: u32, y_size: u32) -> Block {
+        let mut state: u32 = 12345;
+        let cells: Vec<u8> = (0..x_size * y_size)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16 & 1) as u8
+            })
+            .collect();
+        Block::from_cells(x_size, y_size, &cells)
+    }
+
+    #[test]
+    fn bitblock_conversion() {
+        let block = noise(130, 7);
+        let packed = BitBlock::from(&block);
+        assert_eq!(packed.get(129, 6), block[(129, 6)] == 1);
+        let mut population = 0;
+        for x in 0..block.x_size {
+            for y in 0..block.y_size {
+                population += block[(x, y)] as u64;
+            }
+        }
+        assert_eq!(packed.population(), population);
+        assert_eq!(Block::from(&packed), block);
+    }
+
+    #[test]
+    fn bitblock_words() {
+        let block = noise(150, 6);
+        let packed = BitBlock::from(&block);
+        //pieces crossing word boundaries at unaligned offsets
+        let piece = packed.sub(61, 2, 70, 3);
+        let mut grown = BitBlock::new(10, 2);
+        grown.grow(200, 8);
+        grown.or_at(127, 4, &piece);
+        for x in 0..70 {
+            for y in 0..3 {
+                assert_eq!(piece.get(x, y), packed.get(x + 61, y + 2));
+                assert_eq!(grown.get(x + 127, y + 4), packed.get(x + 61, y + 2));
+            }
+        }
+        assert_eq!(grown.population(), piece.population());
+        assert_eq!(
+            (0..70).map(|x| piece.column_population(x)).sum::<u32>(),
+            (0..3).map(|y| piece.row_population(y)).sum::<u32>()
+        );
+
+        let mut truncated = packed.clone();
+        truncated.truncate(70, 4);
+        assert_eq!(truncated, packed.sub(0, 0, 70, 4));
+    }
+
+    #[test]
+    fn bitblock_step() {
+        for rule in ["B3/S23", "B36/S23", "B3678/S34678", "B2/S", "B1/S012345678"] {
+            let rule: Rule = rule.parse().unwrap();
+            let block = noise(131, 9);
+            let mut packed = BitBlock::from(&block);
+            packed.step(&rule);
+
+            for x in 0..block.x_size {
+                for y in 0..block.y_size {
+                    let coord = UCoord { x, y };
+                    let expected = rule.next(block[coord] == 1, block.neighbour_count(coord));
+                    assert_eq!(packed.get(x, y), expected, "{} at {};{}", rule, x, y);
+                }
+            }
+        }
+    }
+}
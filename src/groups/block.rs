@@ -1,24 +1,51 @@
 //! Contains [Block] struct and its methods
+use super::bitblock::BitBlock;
+use super::format::{self, PatternFormat};
+use super::rle;
+use super::rule::Rule;
 use super::*;
+use crate::Error;
+use std::borrow::Cow;
+
+/// Way of splitting a [Block] into independent pieces
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SplitMode {
+    /// Cuts along pairs of empty columns, then pairs of empty rows within every column piece.
+    /// See [Block::split()]
+    Stripes,
+    /// Splits into stripes first, then separates groups of cells within 2 cells of each other in
+    /// sparse stripes. Dense stripes and stripes at most 3 cells wide are kept whole. See
+    /// [Block::split_components()]
+    #[default]
+    Components,
+}
+
+/// Values of cells returned by indexing
+const DEAD: u8 = 0;
+const ALIVE: u8 = 1;
 
 /// A rectangular block of cells with (u32, u32) or [UCoord] indexing.
 ///
+/// Cells are stored bit-packed in a [BitBlock]. A cell borrowed mutably by indexing is kept
+/// aside as a byte and stored back into the bits on the next access to the block
+///
 /// After each game step it is resized and padded to have a 1-cell empty cell border
-#[derive(Eq)]
+#[derive(Clone, Eq)]
 pub struct Block {
     pub x_size: u32,
     pub y_size: u32,
-    pub data: std::vec::Vec<u8>,
+    cells: BitBlock,
+    /// Cell borrowed by [IndexMut](std::ops::IndexMut) or [Block::get_mut()]
+    staged: Option<UCoord>,
+    /// Value of the staged cell
+    staged_value: u8,
 }
 
 impl std::ops::Index<UCoord> for Block {
     type Output = u8;
 
     fn index(&self, index: UCoord) -> &Self::Output {
-        let linear_coord = index.y * self.x_size + index.x;
-        self.data
-            .get(linear_coord as usize)
-            .expect("Block indexed on not existing cell")
+        self.get(index).expect("Block indexed on not existing cell")
     }
 }
 
@@ -26,34 +53,56 @@ impl std::ops::Index<(u32, u32)> for Block {
     type Output = u8;
 
     fn index(&self, index: (u32, u32)) -> &Self::Output {
-        let linear_coord = index.1 * self.x_size + index.0;
-        self.data
-            .get(linear_coord as usize)
-            .expect("Block indexed on not existing cell")
+        &self[UCoord {
+            x: index.0,
+            y: index.1,
+        }]
     }
 }
 
 impl std::ops::IndexMut<UCoord> for Block {
     fn index_mut(&mut self, index: UCoord) -> &mut Self::Output {
-        let linear_coord = index.y * self.x_size + index.x;
-        self.data
-            .get_mut(linear_coord as usize)
+        self.get_mut(index)
             .expect("Block indexed on not existing cell")
     }
 }
 
 impl std::ops::IndexMut<(u32, u32)> for Block {
     fn index_mut(&mut self, index: (u32, u32)) -> &mut Self::Output {
-        let linear_coord = index.1 * self.x_size + index.0;
-        self.data
-            .get_mut(linear_coord as usize)
-            .expect("Block indexed on not existing cell")
+        &mut self[UCoord {
+            x: index.0,
+            y: index.1,
+        }]
     }
 }
 
 impl std::cmp::PartialEq for Block {
     fn eq(&self, other: &Block) -> bool {
-        self.x_size == other.x_size && self.y_size == other.y_size && self.data == other.data
+        self.x_size == other.x_size && self.y_size == other.y_size && self.bits() == other.bits()
+    }
+}
+
+impl From<BitBlock> for Block {
+    fn from(cells: BitBlock) -> Self {
+        Block {
+            x_size: cells.x_size,
+            y_size: cells.y_size,
+            cells,
+            staged: None,
+            staged_value: 0,
+        }
+    }
+}
+
+impl From<&BitBlock> for Block {
+    fn from(cells: &BitBlock) -> Self {
+        Block::from(cells.clone())
+    }
+}
+
+impl From<&Block> for BitBlock {
+    fn from(block: &Block) -> Self {
+        block.bits().into_owned()
     }
 }
 
@@ -63,13 +112,13 @@ impl std::fmt::Debug for Block {
         for _x in 0..self.x_size {
             write!(f, " -")?;
         }
-        write!(f, "\n")?;
+        writeln!(f)?;
         for y in 1..=self.y_size {
             write!(f, "| ")?;
             for x in 0..self.x_size {
                 write!(f, "{} ", self[(x, self.y_size - y)])?;
             }
-            write!(f, "|\n")?;
+            writeln!(f, "|")?;
         }
         write!(f, " ")?;
         for _x in 0..self.x_size {
@@ -79,28 +128,130 @@ impl std::fmt::Debug for Block {
     }
 }
 
+/// Stripes with more cells than one per this many are not split into components
+const SPARSE_AREA: u64 = 4;
+/// Widest stripe, border included, that always holds one component. Its cells are within 2
+/// columns of each other, so separate components would need 2 empty rows between them, which
+/// [Block::split()] cuts along
+const NARROW_STRIPE: u32 = 5;
+
+/// Checks that a block of the given size can be indexed with `u32`
+fn check_size(x_size: u64, y_size: u64) -> Result<(u32, u32), Error> {
+    match (u32::try_from(x_size), u32::try_from(y_size)) {
+        (Ok(x), Ok(y)) if x.checked_mul(y).is_some() => Ok((x, y)),
+        _ => Err(Error::BlockTooLarge { x_size, y_size }),
+    }
+}
+
 impl Block {
     /// Creates a block with given size and allocs memory for its contents
+    /// # Panics
+    /// When the block would have more cells than can be indexed, see [Block::try_new()]
     pub fn new(x_size: u32, y_size: u32) -> Block {
-        let linear_size = x_size * y_size;
-        let mut new_block = Block {
-            x_size: x_size,
-            y_size: y_size,
-            data: std::vec::Vec::with_capacity(linear_size as usize),
-        };
-        new_block.data.resize(linear_size as usize, 0);
-        new_block
+        assert!(
+            x_size.checked_mul(y_size).is_some(),
+            "Block of {}x{} cells is too large",
+            x_size,
+            y_size
+        );
+        Block::from(BitBlock::new(x_size, y_size))
     }
 
-    /// Safely gets a cell value by coordinates (uses [Vec::get](slice::get())
+    /// Creates a block from cell values row by row, row 0 first. Non-zero values are alive
+    /// # Panics
+    /// When the number of values does not match the size
+    pub fn from_cells(x_size: u32, y_size: u32, cells: &[u8]) -> Block {
+        assert_eq!(
+            cells.len(),
+            x_size as usize * y_size as usize,
+            "Cells do not match block size"
+        );
+        let mut block = BitBlock::new(x_size, y_size);
+        for (index, cell) in cells.iter().enumerate() {
+            if *cell != 0 {
+                let index = index as u32;
+                block.set(index % x_size, index / x_size, true);
+            }
+        }
+        Block::from(block)
+    }
+
+    /// Creates an empty block with given size
+    ///
+    /// Returns [Error::BlockTooLarge] when it would have more cells than can be indexed
+    pub fn try_new(x_size: u64, y_size: u64) -> Result<Block, Error> {
+        let (x_size, y_size) = check_size(x_size, y_size)?;
+        Ok(Block::new(x_size, y_size))
+    }
+
+    /// Safely gets a cell value by coordinates. [None] when the cell is out of the block
     pub fn get(&self, index: UCoord) -> Option<&u8> {
-        let linear_coord = index.y * self.x_size + index.x;
-        self.data.get(linear_coord as usize)
+        if index.x >= self.x_size || index.y >= self.y_size {
+            return None;
+        }
+        if self.staged == Some(index) {
+            return Some(&self.staged_value);
+        }
+        match self.cells.get(index.x, index.y) {
+            true => Some(&ALIVE),
+            false => Some(&DEAD),
+        }
+    }
+
+    /// Safely gets a mutable cell value by coordinates. [None] when the cell is out of the block
+    pub fn get_mut(&mut self, index: UCoord) -> Option<&mut u8> {
+        if index.x >= self.x_size || index.y >= self.y_size {
+            return None;
+        }
+        self.store_staged();
+        self.staged_value = self.cells.get(index.x, index.y) as u8;
+        self.staged = Some(index);
+        Some(&mut self.staged_value)
+    }
+
+    /// Stores the cell borrowed by [Block::get_mut()] into the bits
+    fn store_staged(&mut self) {
+        if let Some(index) = self.staged.take() {
+            self.cells.set(index.x, index.y, self.staged_value != 0);
+        }
+    }
+
+    /// Returns the bit-packed cells of the block
+    pub fn bits(&self) -> Cow<'_, BitBlock> {
+        match self.staged {
+            None => Cow::Borrowed(&self.cells),
+            Some(index) => {
+                let mut cells = self.cells.clone();
+                cells.set(index.x, index.y, self.staged_value != 0);
+                Cow::Owned(cells)
+            }
+        }
+    }
+
+    /// Returns a copy of rows from *from* to *to* (not included) of the block
+    /// # Panics
+    /// When the rows are out of bounds
+    pub fn rows(&self, from: u32, to: u32) -> Block {
+        Block::from(self.bits().rows(from, to))
+    }
+
+    /// Overwrites rows starting from *at* with rows from *from* to *to* (not included) of
+    /// *other* block of the same width
+    /// # Panics
+    /// When widths differ or the rows are out of bounds
+    pub fn set_rows(&mut self, at: u32, other: &Block, from: u32, to: u32) {
+        self.store_staged();
+        self.cells.set_rows(at, &other.bits(), from, to);
+    }
+
+    /// Returns number of alive cells
+    pub fn population(&self) -> u64 {
+        self.bits().population()
     }
 
     /// Dumps block contents to stdout
     pub fn dump_data(&self) {
-        for y in self.y_size..0 {
+        for y in (0..self.y_size).rev() {
             for x in 0..self.x_size {
                 print!("{} ", self[(x, y)]);
             }
@@ -147,50 +298,25 @@ impl Block {
         count
     }
 
-    /// Changes the state of the block to the next generations (does not change size)
-    pub fn step(&mut self) {
-        let length = self.x_size * self.y_size;
-        let mut new_block = Block {
-            data: std::vec::Vec::with_capacity(length as usize),
-            ..*self
-        };
-        for _i in 0..length {
-            new_block.data.push(0);
-        }
-
-        for x in 0..self.x_size {
-            for y in 0..self.y_size {
-                let coord = UCoord { x: x, y: y };
-                match self.neighbour_count(coord) {
-                    2 => new_block[coord] = self[coord],
-                    3 => new_block[coord] = 1,
-                    _ => new_block[coord] = 0,
-                }
-            }
-        }
-        *self = new_block;
+    /// Changes the state of the block to the next generation under given [Rule] (does not change
+    /// size). 64 cells are stepped at once, see [BitBlock::step()]
+    pub fn step(&mut self, rule: &Rule) {
+        self.store_staged();
+        self.cells.step(rule);
     }
 
     /// Counts alive cells in a row
     /// # Panics
     /// When index is out of bounds
     fn row_alive(&self, index_y: u32) -> u32 {
-        let mut count: u32 = 0;
-        for x in 0..self.x_size {
-            count += self[(x, index_y)] as u32;
-        }
-        count
+        self.bits().row_population(index_y)
     }
 
     /// Counts alive cells in a column
     /// # Panics
     /// When index is out of bounds
     fn column_alive(&self, index_x: u32) -> u32 {
-        let mut count: u32 = 0;
-        for y in 0..self.y_size {
-            count += self[(index_x, y)] as u32;
-        }
-        count
+        self.bits().column_population(index_x)
     }
 
     /// Checks if there are any alive cells on the border
@@ -203,43 +329,33 @@ impl Block {
             return true;
         }
 
-        return false;
+        false
     }
 
     /// Inserts other block in **self**. Resizes **self** if necessary
     /// # Panics
-    /// When insertion place is out of bounds
+    /// When the resized block is too large, see [Block::try_insert()]
     pub fn insert(&mut self, place: UCoord, other: &Block) {
-        let mut new_x = place.x + other.x_size;
-        new_x = if new_x < self.x_size {
-            self.x_size
-        } else {
-            new_x
-        };
-
-        let mut new_y = place.y + other.y_size;
-        new_y = if new_y < self.y_size {
-            self.y_size
-        } else {
-            new_y
-        };
-
-        let mut new = Block::new(new_x, new_y);
-        for x in 0..self.x_size {
-            for y in 0..self.y_size {
-                new[(x, y)] = self[(x, y)];
-            }
+        if let Err(err) = self.try_insert(place, other) {
+            panic!("{}", err);
         }
+    }
 
-        for x in 0..other.x_size {
-            for y in 0..other.y_size {
-                new[(x + place.x, y + place.y)] = match other[(x, y)] {
-                    1 => 1,
-                    _ => new[(x + place.x, y + place.y)],
-                }
-            }
-        }
-        *self = new;
+    /// Inserts other block in **self**. Resizes **self** if necessary
+    ///
+    /// Returns [Error::BlockTooLarge] and leaves **self** unchanged when the resized block would
+    /// have more cells than can be indexed
+    pub fn try_insert(&mut self, place: UCoord, other: &Block) -> Result<(), Error> {
+        let (new_x, new_y) = check_size(
+            std::cmp::max(place.x as u64 + other.x_size as u64, self.x_size as u64),
+            std::cmp::max(place.y as u64 + other.y_size as u64, self.y_size as u64),
+        )?;
+        self.store_staged();
+        self.cells.grow(new_x, new_y);
+        self.x_size = new_x;
+        self.y_size = new_y;
+        self.cells.or_at(place.x, place.y, &other.bits());
+        Ok(())
     }
 
     /// Removes excessive empty rows/columns from the sides of the block
@@ -255,9 +371,7 @@ impl Block {
         loop {
             //count empty columns from the left
             if self.column_alive(x_offset) != 0 {
-                if x_offset != 0 {
-                    x_offset -= 1; //reserve one empty column for borders
-                }
+                x_offset = x_offset.saturating_sub(1); //reserve one empty column for borders
                 break;
             }
             if x_offset == self.x_size - 1 {
@@ -270,9 +384,7 @@ impl Block {
         loop {
             //count empty rows from the bottom
             if self.row_alive(y_offset) != 0 {
-                if y_offset != 0 {
-                    y_offset -= 1; //reserve one empty row for borders
-                }
+                y_offset = y_offset.saturating_sub(1); //reserve one empty row for borders
                 break;
             }
             y_offset += 1;
@@ -280,9 +392,7 @@ impl Block {
 
         loop {
             if self.column_alive(self.x_size - 1 - right_x_offset) != 0 {
-                if right_x_offset != 0 {
-                    right_x_offset -= 1;
-                }
+                right_x_offset = right_x_offset.saturating_sub(1);
                 break;
             }
             right_x_offset += 1;
@@ -290,9 +400,7 @@ impl Block {
 
         loop {
             if self.row_alive(self.y_size - 1 - top_y_offset) != 0 {
-                if top_y_offset != 0 {
-                    top_y_offset -= 1;
-                }
+                top_y_offset = top_y_offset.saturating_sub(1);
                 break;
             }
             top_y_offset += 1;
@@ -301,14 +409,7 @@ impl Block {
         let new_x_size = self.x_size - x_offset - right_x_offset;
         let new_y_size = self.y_size - y_offset - top_y_offset;
 
-        let mut new_block = Block::new(new_x_size, new_y_size);
-        for x in x_offset..self.x_size - right_x_offset {
-            for y in y_offset..self.y_size - top_y_offset {
-                new_block[(x - x_offset, y - y_offset)] = self[(x, y)];
-            }
-        }
-
-        *self = new_block;
+        *self = Block::from(self.bits().sub(x_offset, y_offset, new_x_size, new_y_size));
         Some(UCoord {
             x: x_offset,
             y: y_offset,
@@ -339,14 +440,9 @@ impl Block {
             _ => 1,
         };
 
-        let mut new_block = Block::new(self.x_size + left + right, self.y_size + bottom + top);
-        for x in 0..self.x_size {
-            for y in 0..self.y_size {
-                new_block[(x + left, y + bottom)] = self[(x, y)];
-            }
-        }
-
-        *self = new_block;
+        let mut cells = BitBlock::new(self.x_size + left + right, self.y_size + bottom + top);
+        cells.or_at(left, bottom, &self.bits());
+        *self = Block::from(cells);
         Coord {
             x: 0 - left as i64,
             y: 0 - bottom as i64,
@@ -374,10 +470,7 @@ impl Block {
     /// Returns [None] if block was empty. Otherwise returns vector of new blocks and their
     /// offsets
     pub fn split(mut self) -> Option<Vec<(Block, Coord)>> {
-        let resize_offset = match self.resize() {
-            None => return Option::None,
-            Some(i) => i,
-        };
+        let resize_offset = self.resize()?;
 
         let mut vert_splits: Vec<u32> = Vec::new();
         vert_splits.push(0);
@@ -419,18 +512,82 @@ impl Block {
         Some(pieces)
     }
 
+    /// Splits the block into not intersecting pieces the given way. Consumes **self**
+    ///
+    /// Returns [None] if block was empty. Otherwise returns vector of new blocks and their
+    /// offsets
+    pub fn split_with(self, mode: SplitMode) -> Option<Vec<(Block, Coord)>> {
+        match mode {
+            SplitMode::Stripes => self.split(),
+            SplitMode::Components => self.split_components(),
+        }
+    }
+
+    /// Splits the block into groups of cells connected within 2 cells of each other. Cells
+    /// further apart have no common neighbours, so the pieces do not interact in the next
+    /// generation. Every piece gets the 1-cell empty border. Consumes **self**
+    ///
+    /// The block is split into [stripes](Block::split()) first. Only sparse stripes, which may hold
+    /// several components side by side (e.g. a diagonal row of gliders), are split further.
+    /// Dense and narrow ones are kept whole, looking for components there costs more than it
+    /// saves
+    ///
+    /// Returns [None] if block was empty. Otherwise returns vector of new blocks and their
+    /// offsets
+    pub fn split_components(self) -> Option<Vec<(Block, Coord)>> {
+        let stripes = self.split()?;
+        let mut pieces = Vec::new();
+        for (stripe, offset) in stripes {
+            let area = stripe.x_size as u64 * stripe.y_size as u64;
+            if stripe.x_size <= NARROW_STRIPE || area <= stripe.population() * SPARSE_AREA {
+                pieces.push((stripe, offset));
+                continue;
+            }
+            for (piece, inner) in stripe.components() {
+                pieces.push((piece, offset + inner));
+            }
+        }
+        Some(pieces)
+    }
+
+    /// Separates cells of a block into components, see [Block::split_components()]. Consumes
+    /// **self**, which is returned as it is when it holds one component
+    ///
+    /// Returns blocks of components with empty borders and their offsets, in order of their
+    /// lowest cells
+    fn components(self) -> Vec<(Block, Coord)> {
+        let cells = self.bits();
+        let Some((x, y)) = cells.first_alive() else {
+            return Vec::new();
+        };
+        let first = cells.component(x, y);
+        if first.population() == cells.population() {
+            drop(cells);
+            return vec![(self, Coord { x: 0, y: 0 })];
+        }
+
+        let mut rest = cells.into_owned();
+        let mut component = first;
+        let mut pieces = Vec::new();
+        loop {
+            rest.remove(&component);
+            let mut piece = Block::from(component);
+            //not empty, it holds the first cell
+            let offset = piece.resize().unwrap();
+            pieces.push((piece, offset));
+            match rest.first_alive() {
+                Some((x, y)) => component = rest.component(x, y),
+                None => return pieces,
+            }
+        }
+    }
+
     /// Cuts block by a horizontal line. **Self** contains lower part. Returns upper part
     /// # Panics
     /// When cut line is out of bounds
     fn cut_block_top(&mut self, cut_line: u32) -> Block {
-        let mut piece = Block::new(self.x_size, self.y_size - cut_line);
-        for x in 0..piece.x_size {
-            for y in 0..piece.y_size {
-                piece[(x, y)] = self[(x, y + cut_line)];
-            }
-        }
-        self.y_size = cut_line;
-        self.data.truncate((self.x_size * self.y_size) as usize);
+        let piece = self.rows(cut_line, self.y_size);
+        self.truncate(self.x_size, cut_line);
         piece
     }
 
@@ -438,75 +595,56 @@ impl Block {
     /// # Panics
     /// When cut line is out of bounds
     fn cut_block_right(&mut self, cut_line: u32) -> Block {
-        let mut piece = Block::new(self.x_size - cut_line, self.y_size);
-        for x in 0..piece.x_size {
-            for y in 0..piece.y_size {
-                piece[(x, y)] = self[(x + cut_line, y)];
-            }
-        }
+        let piece = Block::from(
+            self.bits()
+                .sub(cut_line, 0, self.x_size - cut_line, self.y_size),
+        );
         if cut_line == 0 {
             return piece;
         }
-        let mut temp_block = Block::new(cut_line, self.y_size);
-        for x in 0..temp_block.x_size {
-            for y in 0..temp_block.y_size {
-                temp_block[(x, y)] = self[(x, y)];
-            }
-        }
-        *self = temp_block;
+        self.truncate(cut_line, self.y_size);
         piece
     }
 
-    /// Imports a pattern from RLE string
-    pub fn rle_import(pattern: &str) -> Option<Block> {
-        let mut x_size = 0;
-        let mut y_size = 0;
-        for line in pattern.lines() {
-            if line.starts_with("#") {
-                continue;
-            }
-            if line.starts_with("x") {
-                for piece in line.split(',') {
-                    if let Some(val) = piece.strip_prefix("x = ") {
-                        x_size = val.parse().unwrap();
-                    }
-                    if let Some(val) = piece.strip_prefix(" y = ") {
-                        y_size = val.parse().unwrap();
-                    }
-                }
-                continue;
-            }
-            let mut y = 0;
-            let mut block = Block::new(x_size, y_size);
-            for piece in line.split("$") {
-                let mut x = 0;
-                let mut run_tag = 0;
-                for c in piece.chars() {
-                    if c.is_digit(10) {
-                        run_tag = run_tag * 10 + c.to_digit(10).unwrap();
-                    }
-                    if c.is_alphabetic() {
-                        if c == '!' {
-                            return Some(block);
-                        }
-                        if run_tag == 0 {
-                            run_tag = 1;
-                        }
-                        for _i in 0..run_tag {
-                            block[(x, y)] = match c {
-                                'b' => 0,
-                                _ => 1,
-                            };
-                            x += 1;
-                        }
-                        run_tag = 0;
-                    }
+    /// Shrinks the block in place to its first *x_size* columns and *y_size* rows
+    fn truncate(&mut self, x_size: u32, y_size: u32) {
+        self.store_staged();
+        self.cells.truncate(x_size, y_size);
+        self.x_size = x_size;
+        self.y_size = y_size;
+    }
+
+    /// Imports a pattern from RLE string. Row 0 of the block is the first row of RLE body.
+    ///
+    /// See [rle::parse()] to also get the rule and comments of the pattern
+    pub fn rle_import(pattern: &str) -> Result<Block, rle::RleError> {
+        rle::parse(pattern).map(|pattern| pattern.block)
+    }
+
+    /// Exports block contents to RLE string. Row 0 is written first, so the result is read back
+    /// by [Block::rle_import()] unchanged
+    pub fn rle_export(&self, rule: &Rule) -> String {
+        self.export(rule, PatternFormat::Rle)
+    }
+
+    /// Exports block contents to given pattern format. Row 0 is written first
+    pub fn export(&self, rule: &Rule, format: PatternFormat) -> String {
+        let mut cells = Vec::new();
+        for y in 0..self.y_size {
+            for x in 0..self.x_size {
+                if self[(x, y)] == 1 {
+                    cells.push((x as u64, y as u64));
                 }
-                y += 1;
             }
-            return Some(block);
         }
-        None
+        format::encode(
+            &cells,
+            self.x_size.into(),
+            self.y_size.into(),
+            rule,
+            None,
+            format,
+        )
     }
 }
 
@@ -515,11 +653,7 @@ mod tests {
     use super::*;
     #[test]
     fn block_indexing() {
-        let block = Block {
-            x_size: 2,
-            y_size: 2,
-            data: vec![0, 1, 1, 0],
-        };
+        let block = Block::from_cells(2, 2, &[0, 1, 1, 0]);
         assert_eq!(block[(0, 0)], 0);
         assert_eq!(block[(0, 1)], 1);
         assert_eq!(block[(1, 0)], 1);
@@ -528,22 +662,21 @@ mod tests {
 
     #[test]
     fn block_mutability() {
-        let mut block = Block {
-            x_size: 2,
-            y_size: 2,
-            data: vec![0, 1, 1, 0],
-        };
+        let mut block = Block::from_cells(2, 2, &[0, 1, 1, 0]);
         block[(0, 1)] = 1;
         assert_eq!(block[(0, 1)], 1);
+        //the written cell is seen before it is stored into the bits
+        block[(1, 1)] = 1;
+        assert_eq!(block.population(), 3);
+        assert_eq!(block, Block::from_cells(2, 2, &[0, 1, 1, 1]));
+        *block.get_mut(UCoord { x: 1, y: 0 }).unwrap() = 0;
+        block.step(&Rule::conway());
+        assert_eq!(block, Block::from_cells(2, 2, &[0, 0, 0, 0]));
     }
 
     #[test]
     fn block_count_neighbours() {
-        let block = Block {
-            x_size: 3,
-            y_size: 3,
-            data: vec![1, 0, 1, 0, 0, 1, 1, 1, 0],
-        };
+        let block = Block::from_cells(3, 3, &[1, 0, 1, 0, 0, 1, 1, 1, 0]);
         //1 1 0
         //0 0 1
         //1 0 1
@@ -561,31 +694,40 @@ mod tests {
 
     #[test]
     fn block_step() {
-        let mut block = Block {
-            x_size: 3,
-            y_size: 3,
-            data: vec![1, 0, 1, 0, 0, 1, 1, 1, 0],
-        };
+        let mut block = Block::from_cells(3, 3, &[1, 0, 1, 0, 0, 1, 1, 1, 0]);
         //1 1 0
         //0 0 1
         //1 0 1
         assert_eq!(block.neighbour_count(UCoord { x: 1, y: 0 }), 3);
-        block.step();
-        let next_block = Block {
-            x_size: 3,
-            y_size: 3,
-            data: vec![0, 1, 0, 1, 0, 1, 0, 1, 0],
-        };
+        block.step(&Rule::conway());
+        let next_block = Block::from_cells(3, 3, &[0, 1, 0, 1, 0, 1, 0, 1, 0]);
         assert_eq!(block, next_block);
     }
 
+    #[test]
+    fn block_step_rule() {
+        let mut block = Block::new(5, 5);
+        //0 0 0 0 0
+        //0 1 1 1 0
+        //0 0 0 0 0
+        //0 1 1 1 0
+        //0 0 0 0 0
+        for x in 1..4 {
+            block[(x, 1)] = 1;
+            block[(x, 3)] = 1;
+        }
+        let mut highlife = Block::new(5, 5);
+        highlife.insert(UCoord { x: 0, y: 0 }, &block);
+
+        block.step(&Rule::conway());
+        highlife.step(&"B36/S23".parse().unwrap());
+        assert_eq!(block[(2, 2)], 0);
+        assert_eq!(highlife[(2, 2)], 1);
+    }
+
     #[test]
     fn block_line_count() {
-        let block = Block {
-            x_size: 3,
-            y_size: 3,
-            data: vec![1, 0, 1, 1, 0, 1, 1, 1, 0],
-        };
+        let block = Block::from_cells(3, 3, &[1, 0, 1, 1, 0, 1, 1, 1, 0]);
         //1 1 0
         //1 0 1
         //1 0 1
@@ -599,55 +741,30 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn block_need_expand() {
-        let block = Block {
-            x_size: 3,
-            y_size: 3,
-            data: vec![1, 0, 1, 1, 0, 1, 1, 1, 0],
-        };
+        let block = Block::from_cells(3, 3, &[1, 0, 1, 1, 0, 1, 1, 1, 0]);
         //1 1 0
         //1 0 1
         //1 0 1
         assert_eq!(block.need_expand(), true);
 
-        let block_no_expand = Block {
-            x_size: 3,
-            y_size: 3,
-            data: vec![0, 0, 0, 0, 1, 0, 0, 0, 0],
-        };
+        let block_no_expand = Block::from_cells(3, 3, &[0, 0, 0, 0, 1, 0, 0, 0, 0]);
         assert_eq!(block_no_expand.need_expand(), false)
     }
 
     #[test]
     fn block_new() {
         let mut block = Block::new(3, 3);
-        assert_eq!(
-            block,
-            Block {
-                x_size: 3,
-                y_size: 3,
-                data: vec![0; 9]
-            }
-        );
+        assert_eq!(block, Block::from_cells(3, 3, &[0; 9]));
         block[(1, 1)] = 1;
-        assert_eq!(
-            block,
-            Block {
-                x_size: 3,
-                y_size: 3,
-                data: vec![0, 0, 0, 0, 1, 0, 0, 0, 0]
-            }
-        );
+        assert_eq!(block, Block::from_cells(3, 3, &[0, 0, 0, 0, 1, 0, 0, 0, 0]));
     }
 
     #[test]
     fn block_insert() {
         let mut block = Block::new(5, 5);
-        let insert = Block {
-            x_size: 3,
-            y_size: 4,
-            data: vec![0, 1, 0, 1, 1, 0, 0, 0, 0, 0, 1, 1],
-        };
+        let insert = Block::from_cells(3, 4, &[0, 1, 0, 1, 1, 0, 0, 0, 0, 0, 1, 1]);
         //0 1 1
         //0 0 0
         //1 1 0
@@ -689,6 +806,17 @@ mod tests {
         result[(7, 5)] = 1;
 
         assert_eq!(block, result);
+
+        let far = UCoord { x: 70000, y: 70000 };
+        assert_eq!(
+            block.try_insert(far, &insert),
+            Err(Error::BlockTooLarge {
+                x_size: 70003,
+                y_size: 70004
+            })
+        );
+        assert_eq!(block, result);
+        assert_eq!(block.get(UCoord { x: 8, y: 0 }), None);
     }
 
     #[test]
@@ -929,6 +1057,43 @@ mod tests {
         assert_eq!(pieces.len(), 2);
     }
 
+    #[test]
+    fn block_split_components() {
+        let mut block = Block::new(7, 7);
+        //1 1 0 0 0 0 1
+        //1 1 0 0 0 0 1
+        //0 0 0 0 0 0 1
+        //0 0 0 0 0 0 1
+        //0 0 0 0 0 0 1
+        //0 0 0 0 0 0 1
+        //1 1 1 1 1 1 1
+        for i in 0..7 {
+            block[(i, 0)] = 1;
+            block[(6, i)] = 1;
+        }
+        for (x, y) in [(0, 5), (1, 5), (0, 6), (1, 6)] {
+            block[(x, y)] = 1;
+        }
+        assert_eq!(block.clone().split().unwrap().len(), 1);
+
+        let pieces = block.split_components().unwrap();
+        assert_eq!(pieces.len(), 2);
+        let (corner, offset) = &pieces[0];
+        assert_eq!((corner.x_size, corner.y_size), (9, 9));
+        assert_eq!(corner.population(), 13);
+        assert_eq!(*offset, Coord { x: -1, y: -1 });
+        let (still, offset) = &pieces[1];
+        assert_eq!((still.x_size, still.y_size), (4, 4));
+        assert_eq!(still.population(), 4);
+        assert_eq!(*offset, Coord { x: -1, y: 4 });
+
+        //cells 2 apart share neighbours
+        let mut block = Block::new(3, 3);
+        block[(0, 0)] = 1;
+        block[(2, 2)] = 1;
+        assert_eq!(block.split_with(SplitMode::Components).unwrap().len(), 1);
+    }
+
     #[test]
     fn block_rle_import() {
         let pattern = "#C This is a glider.\nx = 3, y = 3\nbo$2bo$3o!".to_string();
@@ -942,4 +1107,18 @@ mod tests {
 
         assert_eq!(glider, block.unwrap());
     }
+
+    #[test]
+    fn block_rle_export() {
+        let mut block = Block::new(5, 4);
+        block[(1, 0)] = 1;
+        block[(2, 1)] = 1;
+        block[(0, 2)] = 1;
+        block[(1, 2)] = 1;
+        block[(2, 2)] = 1;
+
+        let rle = block.rle_export(&Rule::conway());
+        assert_eq!(rle, "x = 5, y = 4, rule = B3/S23\nbo$2bo$3o!\n");
+        assert_eq!(Block::rle_import(&rle).unwrap(), block);
+    }
 }
@@ -0,0 +1,107 @@
+//! Contains plaintext (`.cells`) parser and encoding. Rows are made of `.` (dead) and `O` (alive)
+//! characters, lines starting with `!` are comments
+use super::block::Block;
+use super::format::{FormatError, PatternFormat};
+use super::rle::RlePattern;
+
+/// Parses plaintext pattern into [RlePattern]. `!Name:` and `!Author:` comment lines fill name
+/// and author, other comments are kept as they are. `*` is accepted as an alive cell as well
+pub fn parse(text: &str) -> Result<RlePattern, FormatError> {
+    let mut pattern = RlePattern::default();
+
+    let mut rows: Vec<Vec<bool>> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if let Some(comment) = line.strip_prefix('!') {
+            if let Some(name) = comment.strip_prefix("Name:") {
+                pattern.name = Some(name.trim().to_string());
+            } else if let Some(author) = comment.strip_prefix("Author:") {
+                pattern.author = Some(author.trim().to_string());
+            } else {
+                pattern.comments.push(comment.trim().to_string());
+            }
+            continue;
+        }
+
+        let mut row = Vec::with_capacity(line.len());
+        for (column, char) in line.chars().enumerate() {
+            match char {
+                '.' => row.push(false),
+                'O' | 'o' | '*' => row.push(true),
+                _ => {
+                    return Err(FormatError {
+                        format: PatternFormat::Cells,
+                        line: index + 1,
+                        column: column + 1,
+                        reason: format!("unexpected character '{}'", char),
+                    })
+                }
+            }
+        }
+        rows.push(row);
+    }
+
+    //trailing empty lines are not part of the pattern
+    while rows.last().is_some_and(|row| row.is_empty()) {
+        rows.pop();
+    }
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut block = Block::try_new(width as u64, rows.len() as u64)
+        .map_err(|err| FormatError::too_large(PatternFormat::Cells, err))?;
+    for (y, row) in rows.iter().enumerate() {
+        for (x, alive) in row.iter().enumerate() {
+            if *alive {
+                block[(x as u32, y as u32)] = 1;
+            }
+        }
+    }
+    pattern.block = block;
+    Ok(pattern)
+}
+
+/// Encodes alive cells into plaintext
+///
+/// *cells* are `(column, row)` offsets from the top left corner of the pattern sorted by row and
+/// then by column, as for [rle::encode()](super::rle::encode()). Trailing dead cells of rows are
+/// not written
+pub fn encode(cells: &[(u64, u64)], y_size: u64) -> String {
+    let mut out = String::new();
+    let mut cells = cells.iter().peekable();
+    for y in 0..y_size {
+        let mut x = 0;
+        while let Some((col, _)) = cells.next_if(|(_, row)| *row == y) {
+            out.extend(std::iter::repeat_n('.', (col - x) as usize));
+            out.push('O');
+            x = col + 1;
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_roundtrip() {
+        let text =
+            "!Name: Glider\n!Author: Richard K. Guy\n!The smallest spaceship\n.O\n..O\nOOO\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(pattern.name.as_deref(), Some("Glider"));
+        assert_eq!(pattern.author.as_deref(), Some("Richard K. Guy"));
+        assert_eq!(pattern.comments, ["The smallest spaceship"]);
+        assert_eq!((pattern.block.x_size, pattern.block.y_size), (3, 3));
+        assert_eq!(
+            pattern.block,
+            Block::rle_import("x = 3, y = 3\nbo$2bo$3o!").unwrap()
+        );
+
+        let cells = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        assert_eq!(encode(&cells, 3), ".O\n..O\nOOO\n");
+        assert_eq!(encode(&[(0, 0), (2, 2)], 3), "O\n\n..O\n");
+
+        let err = parse("..O\n.x.\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+    }
+}
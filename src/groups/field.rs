@@ -1,16 +1,24 @@
 //! Contains [Field] struct and its methods
 
-use crate::groups::{group::Group, Coord};
-use crossbeam_channel as channel;
+use crate::analysis::{stability, stats::Stats};
+use crate::groups::format::{self, PatternFormat};
+use crate::groups::{group::Group, pool::ThreadPool, rule::Rule, topology::Topology, Coord};
+use crate::hashlife::macrocell::{self, MacrocellError};
+use crate::hashlife::HashLife;
+use crate::render::svg::{self as svg_render, SvgConfig, SvgMode};
+use crate::Error;
 use rstar::{Envelope, RTree, RTreeObject, AABB};
-use std::num::NonZeroUsize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::thread;
-use svg::node::element::Rectangle;
+use std::time::Instant;
 use svg::Document;
 
-///Selection function for R-tree that uses [Group::intersects_smart]
+///Selection function for R-tree that uses [Group::intersects_smart]. The group itself is not
+///selected
 struct SmartSelection<'a> {
     data: &'a Group,
+    rule: &'a Rule,
 }
 
 impl rstar::SelectionFunction<Group> for SmartSelection<'_> {
@@ -19,7 +27,7 @@ impl rstar::SelectionFunction<Group> for SmartSelection<'_> {
     }
 
     fn should_unpack_leaf(&self, leaf: &Group) -> bool {
-        self.data.intersects_smart(leaf)
+        !std::ptr::eq(self.data, leaf) && self.data.intersects_smart(leaf, self.rule)
     }
 }
 
@@ -48,62 +56,255 @@ impl rstar::SelectionFunction<Group> for EnvelopeSelection<'_> {
     }
 }
 
-/// Duplex channel to thread
-struct ThreadChannel {
-    to_thread: channel::Sender<Vec<Group>>,
-    from_thread: channel::Receiver<Vec<Group>>,
-    active: bool,
+/// Union-find over envelopes of groups, collects clusters of intersecting groups. Groups are
+/// told apart by their envelopes, as in [EnvelopeSelection]
+#[derive(Default)]
+struct Clusters {
+    indices: BTreeMap<AABB<(i64, i64)>, usize>,
+    envelopes: Vec<AABB<(i64, i64)>>,
+    parents: Vec<usize>,
+}
+
+impl Clusters {
+    /// Returns the index of *envelope*, adding it as a cluster of its own when new
+    fn index(&mut self, envelope: AABB<(i64, i64)>) -> usize {
+        let len = self.envelopes.len();
+        *self.indices.entry(envelope).or_insert_with(|| {
+            self.envelopes.push(envelope);
+            self.parents.push(len);
+            len
+        })
+    }
+
+    /// Returns the representative of the cluster containing *index*
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    /// Joins the clusters of the two groups
+    fn join(&mut self, a: AABB<(i64, i64)>, b: AABB<(i64, i64)>) {
+        let (a, b) = (self.index(a), self.index(b));
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a] = b;
+    }
+
+    /// Returns envelopes of groups of every cluster
+    fn into_clusters(mut self) -> Vec<Vec<AABB<(i64, i64)>>> {
+        let mut clusters = vec![Vec::new(); self.envelopes.len()];
+        for index in 0..self.envelopes.len() {
+            let root = self.find(index);
+            clusters[root].push(self.envelopes[index]);
+        }
+        clusters.retain(|cluster| !cluster.is_empty());
+        clusters
+    }
 }
 
 pub struct Field {
     ///Game [Field} itself
     pub field: RTree<Group>,
 
-    children: Option<Vec<ThreadChannel>>,
+    rule: Rule,
+    topology: Topology,
+    generation: u64,
+    stats: Option<Stats>,
+    pool: Option<Arc<ThreadPool>>,
     req_child_count: Option<u8>,
 }
 
+/// Clones the groups, rule, topology and generation. The clone shares the thread pool of
+/// [Field::step_parallel()]. Statistics are not cloned
+impl Clone for Field {
+    fn clone(&self) -> Self {
+        Field {
+            field: self.field.clone(),
+            rule: self.rule,
+            topology: self.topology,
+            generation: self.generation,
+            stats: None,
+            pool: self.pool.clone(),
+            req_child_count: self.req_child_count,
+        }
+    }
+}
+
 impl Field {
-    /// Creates a [Field] struct
+    /// Creates a [Field] struct running Conway's "Game of Life"
     pub fn new(field: RTree<Group>) -> Self {
         Field {
             field,
-            children: None,
+            rule: Rule::conway(),
+            topology: Topology::Plane,
+            generation: 0,
+            stats: None,
+            pool: None,
             req_child_count: None,
         }
     }
 
+    /// Sets the [Rule] used by [Field::step()] and [Field::step_parallel()]
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+    }
+
+    /// Returns the [Rule] the field is running
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// Sets the [Topology] of the universe. Groups are moved inside of it, cells behind dead edges
+    /// are removed
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        let groups = Field::tree_to_vec(std::mem::take(&mut self.field));
+        self.field = RTree::bulk_load(self.place(groups));
+        self.merge();
+    }
+
+    /// Returns the [Topology] of the universe
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Places groups inside of the universe with [Topology::place()]
+    fn place(&self, groups: Vec<Group>) -> Vec<Group> {
+        if self.topology == Topology::Plane {
+            return groups;
+        }
+        groups
+            .into_iter()
+            .filter_map(|group| self.topology.place(group))
+            .flatten()
+            .collect()
+    }
+
+    /// Steps the whole universe with [Topology::step_folded()] if any group is larger than it.
+    /// Such group may interact with others across several edges at once, so they are all stepped
+    /// together
+    ///
+    /// Returns the next generation and leaves the tree empty, or returns an empty vector if no
+    /// group needs folding
+    fn step_folded(&mut self) -> Vec<Group> {
+        if !self
+            .field
+            .iter()
+            .any(|group| self.topology.needs_fold(group))
+        {
+            return Vec::new();
+        }
+        let groups = Field::tree_to_vec(std::mem::take(&mut self.field));
+        self.topology.step_folded(groups, &self.rule)
+    }
+
+    /// Returns the number of generations the field has been advanced by
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Sets the generation counter, e.g. for a field converted from another backend
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    /// Returns the number of alive cells
+    pub fn population(&self) -> u64 {
+        self.field
+            .iter()
+            .map(|group| group.block.population())
+            .sum()
+    }
+
+    /// Returns the number of independent [groups](Group)
+    pub fn group_count(&self) -> usize {
+        self.field.size()
+    }
+
+    /// Starts recording [Stats] every generation. The current generation is recorded right away
+    pub fn record_stats(&mut self) {
+        let mut stats = Stats::new();
+        stats.record(self, std::time::Duration::ZERO);
+        self.stats = Some(stats);
+    }
+
+    /// Returns recorded statistics, [None] if recording was not started
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// Finishes a step started at *started*: advances the generation counter and records
+    /// statistics
+    fn finish_step(&mut self, started: Instant) {
+        self.generation += 1;
+        if let Some(mut stats) = self.stats.take() {
+            stats.record(self, started.elapsed());
+            self.stats = Some(stats);
+        }
+    }
+
+    /// Checks if the pattern has stabilized: all [groups](Group) are still lifes, oscillators or
+    /// escaping spaceships with period up to *max_period*. See [stability::is_stable()]
+    pub fn is_stable(&self, max_period: u32) -> bool {
+        stability::is_stable(self, max_period)
+    }
+
+    /// Advances the field until it stabilizes, but at most to generation *max_generation*.
+    /// Stability is checked every *max_period* generations, then the exact generation is found
+    /// by stepping again from the last unstable check.
+    ///
+    /// Returns the generation at which the pattern stabilized (its lifespan) and leaves the field
+    /// at that generation, or returns [None] leaving the field at *max_generation*
+    pub fn run_until_stable(
+        &mut self,
+        max_generation: u64,
+        max_period: u32,
+        parallel: bool,
+    ) -> Option<u64> {
+        let interval = std::cmp::max(max_period as u64, 1);
+        let mut checkpoint = self.clone();
+        loop {
+            if self.is_stable(max_period) {
+                break;
+            }
+            if self.generation >= max_generation {
+                return None;
+            }
+            checkpoint = self.clone();
+            let target = std::cmp::min(self.generation + interval, max_generation);
+            while self.generation < target {
+                match parallel {
+                    true => self.step_parallel(),
+                    false => self.step(),
+                }
+            }
+        }
+
+        //the checkpoint was unstable, so the exact generation lies after it
+        while checkpoint.generation < self.generation {
+            checkpoint.step();
+            if checkpoint.is_stable(max_period) {
+                break;
+            }
+        }
+        self.field = checkpoint.field;
+        self.generation = checkpoint.generation;
+        if let Some(stats) = &mut self.stats {
+            stats.truncate(self.generation);
+        }
+        Some(self.generation)
+    }
+
     /// Returns a max size AABB. Used to drain all tree contents
     pub fn full_tree() -> AABB<(i64, i64)> {
         AABB::from_corners((i64::MIN, i64::MIN), (i64::MAX, i64::MAX))
     }
 
     /// Prepares svg document for groups. Sets max/min coords and background color
-    pub fn prep_svg(&self, mut doc: Document) -> Document {
-        let bl = match self.bottom_left() {
-            None => Coord { x: -50, y: -50 },
-            Some(i) => i,
-        };
-        let rt = match self.top_right() {
-            None => Coord { x: 50, y: 50 },
-            Some(i) => i,
-        };
-        let rec = Rectangle::new()
-            .set("x", bl.x * 10)
-            .set("y", bl.y * 10)
-            .set("width", "100%")
-            .set("height", "100%")
-            .set("fill", "grey");
-        doc = doc.add(rec);
-        doc.set(
-            "viewBox",
-            (
-                bl.x * 10,
-                bl.y * 10,
-                (rt.x - bl.x + 1) * 10,
-                (rt.y - bl.y + 1) * 10,
-            ),
-        )
+    pub fn prep_svg(&self, doc: Document) -> Document {
+        svg_render::prepare(doc, self, &SvgConfig::default())
     }
 
     /// Returns global coords of bottom left corner of the active field
@@ -144,281 +345,612 @@ impl Field {
     }
 
     /// Inserts data of every group in Field into svg document
-    pub fn svg_draw(&self, mut doc: Document) -> Document {
+    pub fn svg_draw(&self, doc: Document) -> Document {
+        svg_render::draw(doc, self, &SvgConfig::default())
+    }
+
+    /// Exports all groups merged in their global coordinates to RLE string. Position of the
+    /// pattern is written to a `#CXRLE Pos=` line. Cells of a finite universe are moved inside of
+    /// it
+    pub fn rle_export(&self) -> String {
+        self.export(PatternFormat::Rle)
+    }
+
+    /// Exports all groups merged in their global coordinates to given pattern format. Cells of a
+    /// finite universe are moved inside of it
+    pub fn export(&self, format: PatternFormat) -> String {
+        let mut cells = Vec::new();
         for group in &self.field {
-            doc = group.svg_add(doc);
+            cells.extend(
+                group
+                    .alive_cells()
+                    .into_iter()
+                    .filter_map(|cell| self.topology.canonical(cell)),
+            );
+        }
+        match format {
+            PatternFormat::Macrocell => {
+                macrocell::encode_global(&cells, &self.rule, self.generation)
+            }
+            _ => format::encode_global(&cells, &self.rule, format),
+        }
+    }
+
+    /// Builds a field from pattern text with the rule and bounded grid of its header. *format* is
+    /// detected from the text when [None]. Pattern position is read with y axis going down, as
+    /// written by [Field::export()]
+    pub fn import(text: &str, format: Option<PatternFormat>) -> Result<Field, Error> {
+        let format = format.unwrap_or_else(|| PatternFormat::detect(text));
+        if format == PatternFormat::Macrocell {
+            return Ok(Field::macrocell_import(text)?);
+        }
+        let pattern = format::parse(text, format)?;
+        let (x, y) = pattern.position.unwrap_or((0, 0));
+        let mut group = Group::new(Coord { x, y }, pattern.block);
+        group.reverse_y();
+        let mut field = Field::new(RTree::new());
+        //an empty pattern may have an empty block, which cannot be stepped
+        if group.block.population() > 0 {
+            field.field.insert(group);
         }
-        doc
+        field.set_rule(pattern.rule.unwrap_or_default());
+        field.set_topology(pattern.topology.unwrap_or(Topology::Plane));
+        Ok(field)
+    }
+
+    /// Builds a field from Golly's Macrocell text. The pattern is unpacked through [HashLife],
+    /// so it never has to fit into a single [Block](crate::groups::block::Block)
+    pub fn macrocell_import(text: &str) -> Result<Field, MacrocellError> {
+        Ok(HashLife::from_macrocell(text)?.to_field())
+    }
+
+    /// Exports all groups merged in their global coordinates together with the generation to
+    /// Golly's Macrocell
+    pub fn macrocell_export(&self) -> String {
+        self.export(PatternFormat::Macrocell)
     }
 
     /// Advances [Field] to next game generation
+    /// # Panics
+    /// When a merged group is too large, see [Field::try_step()]
     pub fn step(&mut self) {
-        let mut step_field = Vec::new();
+        if let Err(err) = self.try_step() {
+            panic!("{}", err);
+        }
+    }
+
+    /// Advances [Field] to next game generation
+    ///
+    /// Returns [Error::BlockTooLarge] when groups of the next generation merge into a group that
+    /// does not fit into a block, see [Field::try_merge()]
+    pub fn try_step(&mut self) -> Result<(), Error> {
+        let started = Instant::now();
+        let mut step_field = self.step_folded();
         for group in self.field.drain_in_envelope(Field::full_tree()) {
-            match group.step() {
+            match group.step(&self.rule) {
                 None => (),
                 Some(mut vec) => step_field.append(&mut vec),
             };
         }
 
-        self.field = RTree::bulk_load(step_field);
-        self.merge();
+        self.field = RTree::bulk_load(self.place(step_field));
+        self.try_merge()?;
+        self.finish_step(started);
+        Ok(())
     }
 
-    /// Merges all intersecting [groups](Group) in [Field]
+    /// Merges all intersecting [groups](Group) in [Field], including groups touching across joined
+    /// edges of the [Topology]
+    /// # Panics
+    /// When a merged group is too large, see [Field::try_merge()]
     pub fn merge(&mut self) {
+        if let Err(err) = self.try_merge() {
+            panic!("{}", err);
+        }
+    }
+
+    /// Merges all intersecting [groups](Group) in [Field], see [Field::merge()]
+    ///
+    /// Returns [Error::BlockTooLarge] when a merged group does not fit into a block. Groups merged
+    /// before that stay merged, the ones being merged are lost
+    pub fn try_merge(&mut self) -> Result<(), Error> {
         loop {
-            let mut merge_envelope = Option::<AABB<(i64, i64)>>::None;
-            for piece in self.field.iter() {
-                if self
+            self.merge_intersecting()?;
+            if !self.merge_seam()? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Merges one pair of groups intersecting across a joined edge. The pair is found by
+    /// [images](Topology::images()) of groups.
+    ///
+    /// Returns false if there was no such pair
+    fn merge_seam(&mut self) -> Result<bool, Error> {
+        if !(self.topology.wraps_x() || self.topology.wraps_y()) {
+            return Ok(false);
+        }
+
+        let mut pair = None;
+        'search: for group in self.field.iter() {
+            for image in self.topology.images(group) {
+                let other = self
                     .field
-                    .locate_with_selection_function(SmartSelection { data: &piece })
-                    .count()
-                    > 1
-                {
-                    merge_envelope = Some(piece.envelope());
-                    break;
+                    .locate_with_selection_function(SmartSelection {
+                        data: &image,
+                        rule: &self.rule,
+                    })
+                    .find(|other| other.envelope() != group.envelope());
+                if let Some(other) = other {
+                    pair = Some((group.envelope(), other.envelope(), image));
+                    break 'search;
                 }
             }
-            if merge_envelope == None {
-                break;
+        }
+
+        let (group, other, image) = match pair {
+            None => return Ok(false),
+            Some(val) => val,
+        };
+        self.field
+            .drain_with_selection_function(EnvelopeSelection { data: &group })
+            .for_each(drop);
+        let pieces: Vec<Group> = self
+            .field
+            .drain_with_selection_function(EnvelopeSelection { data: &other })
+            .collect();
+        //not empty, it holds the image
+        let merged = Group::try_merge_many(std::iter::once(image).chain(pieces))?.unwrap();
+        for piece in self.place(vec![merged]) {
+            self.field.insert(piece);
+        }
+        Ok(true)
+    }
+
+    /// Merges all groups intersecting on the plane
+    ///
+    /// Clusters of intersecting groups are found in one pass over the tree and each is merged at
+    /// once. A merged group is larger than its pieces and may reach more groups, so only merged
+    /// groups are checked again until no more merges happen
+    fn merge_intersecting(&mut self) -> Result<(), Error> {
+        let mut clusters = Clusters::default();
+        for group in self.field.iter() {
+            self.join_intersecting(group, &mut clusters);
+        }
+        loop {
+            let mut merged = Vec::new();
+            for cluster in clusters.into_clusters() {
+                let pieces: Vec<Group> = cluster
+                    .iter()
+                    .flat_map(|envelope| {
+                        self.field
+                            .drain_with_selection_function(EnvelopeSelection { data: envelope })
+                            .collect::<Vec<Group>>()
+                    })
+                    .collect();
+                if let Some(group) = Group::try_merge_many(pieces)? {
+                    merged.push(group.envelope());
+                    self.field.insert(group);
+                }
             }
-            for cur in self
-                .field
-                .drain_with_selection_function(EnvelopeSelection {
-                    data: &merge_envelope.unwrap(),
-                })
-                .collect::<Vec<Group>>()
-            {
-                let mut merge_group = None;
-                for piece in self
+            if merged.is_empty() {
+                return Ok(());
+            }
+
+            clusters = Clusters::default();
+            for envelope in &merged {
+                if let Some(group) = self
                     .field
-                    .drain_with_selection_function(SmartSelection { data: &cur })
+                    .locate_with_selection_function(EnvelopeSelection { data: envelope })
+                    .next()
                 {
-                    merge_group = match merge_group {
-                        None => Some(piece),
-                        Some(val) => Some(val.merge(piece)),
-                    };
+                    self.join_intersecting(group, &mut clusters);
                 }
-                merge_group = match merge_group {
-                    None => Some(cur),
-                    Some(val) => Some(val.merge(cur)),
-                };
-                self.field.insert(merge_group.unwrap());
             }
         }
     }
 
-    pub fn step_parallel_slow(&mut self) {
-        let max_thread_count = match thread::available_parallelism() {
-            Ok(val) => val,
-            Err(_) => NonZeroUsize::new(1).unwrap(),
-        };
-
-        let (tx, rx) = channel::unbounded();
-        let mut handles = Vec::new();
+    /// Joins the cluster of *group* with clusters of all groups intersecting it
+    fn join_intersecting(&self, group: &Group, clusters: &mut Clusters) {
+        let envelope = group.envelope();
+        for other in self.field.locate_with_selection_function(SmartSelection {
+            data: group,
+            rule: &self.rule,
+        }) {
+            clusters.join(envelope, other.envelope());
+        }
+    }
 
-        let groups_per_thread = match self.field.size() / max_thread_count {
-            0 => 1,
-            val => val,
-        };
+    /// Advances [Field] to next game generation, parallelized
+    /// # Panics
+    /// When a worker thread dies or a merged group is too large, see [Field::try_step_parallel()]
+    pub fn step_parallel(&mut self) {
+        if let Err(err) = self.try_step_parallel() {
+            panic!("{}", err);
+        }
+    }
 
-        let mut groups: Vec<Group> = Vec::new();
-        for elem in self.field.drain_in_envelope(Field::full_tree()) {
-            groups.push(elem);
+    /// Advances [Field] to next game generation, parallelized
+    ///
+    /// Returns [Error::WorkerPanicked] or [Error::WorkerDied] when a worker thread fails to step
+    /// its groups. The field is left as it was then, at the same generation. The thread pool keeps
+    /// working and can be used for other fields. Returns [Error::BlockTooLarge] when stepped
+    /// groups cannot be merged, see [Field::try_merge()]
+    pub fn try_step_parallel(&mut self) -> Result<(), Error> {
+        let pool = self.thread_pool();
+        if pool.size() == 0 {
+            return self.try_step(); //call unparallelized step if no threads allowed
         }
 
-        for _ in 0..max_thread_count.into() {
-            let tx_thread = tx.clone();
-            let mut groups_thread: Vec<Group> = Vec::new();
-            for _ in 0..=groups_per_thread {
-                match groups.pop() {
-                    Some(val) => groups_thread.push(val),
-                    None => break,
-                };
+        let started = Instant::now();
+        let mut new_field = self.step_folded();
+        //workers get copies, so the tree survives a failed step. Groups with cached phases are
+        //cheap to step and are left out, they are stepped here once the workers are done
+        let groups = self
+            .field
+            .iter()
+            .filter(|group| !group.is_cached(&self.rule))
+            .cloned()
+            .collect();
+        new_field.append(&mut pool.step(self.rule, groups)?);
+        for group in self.field.drain_in_envelope(Field::full_tree()) {
+            if group.is_cached(&self.rule) {
+                new_field.extend(group.step(&self.rule).into_iter().flatten());
             }
-            let handle = thread::spawn(move || {
-                let mut thread_field = Vec::new();
-                for elem in groups_thread {
-                    match elem.step() {
-                        Some(mut val) => thread_field.append(&mut val),
-                        None => continue,
-                    };
-                }
-                tx_thread.send(thread_field).unwrap();
-            });
-
-            handles.push(handle);
         }
 
-        assert_eq!(groups.len(), 0);
+        self.field = RTree::bulk_load(self.place(new_field));
+        self.try_merge()?;
+        self.finish_step(started);
+        Ok(())
+    }
+
+    /// Requests *count* parallel threads for [Field::step_parallel()]. If system allowed thread count is
+    /// less ther requested, this value is ignored. A running thread pool is resized, including
+    /// when it is shared with other fields
+    pub fn request_parallelizm(&mut self, count: u8) {
+        self.req_child_count = Some(count);
+        if let Some(pool) = &self.pool {
+            pool.resize(self.thread_count());
+        }
+    }
 
-        for handle in handles {
-            handle.join().unwrap();
+    /// Returns the thread pool of [Field::step_parallel()], starting it on first call. Pass it to
+    /// [Field::set_thread_pool()] of other fields to share the threads
+    pub fn thread_pool(&mut self) -> Arc<ThreadPool> {
+        //asking for available parallelism is slow, so it is done only once
+        if self.pool.is_none() {
+            self.pool = Some(Arc::new(ThreadPool::new(self.thread_count())));
         }
+        self.pool.clone().unwrap()
+    }
 
-        drop(tx);
+    /// Makes [Field::step_parallel()] use given thread pool. Threads of the previous one stop when
+    /// no other field uses it
+    pub fn set_thread_pool(&mut self, pool: Arc<ThreadPool>) {
+        self.pool = Some(pool);
+    }
 
-        let mut step_field = Vec::new();
-        loop {
-            match rx.recv() {
-                Ok(mut val) => step_field.append(&mut val),
-                Err(_err) => break,
-            };
+    /// Number of threads for a new thread pool: the requested count limited by the system
+    fn thread_count(&self) -> usize {
+        let available = thread::available_parallelism().map_or(1, |val| val.get());
+        match self.req_child_count {
+            Some(val) => std::cmp::min(available, val.into()),
+            None => available,
         }
-
-        self.field = RTree::bulk_load(step_field);
-        self.merge();
     }
 
-    /// Advances [Field] to next game generation, parallelized
-    pub fn step_parallel(&mut self) {
-        match &self.children {
-            None => {
-                //creates threads and sets up channels on first call
-                let mut thread_count = match thread::available_parallelism() {
-                    Ok(val) => val.into(),
-                    Err(_) => 1,
-                };
-                thread_count = match self.req_child_count {
-                    Some(val) => std::cmp::min(thread_count, val.into()),
-                    None => thread_count,
-                };
-                let mut channels = Vec::new();
-                for _ in 0..thread_count {
-                    let (to_thread, from_main) = channel::unbounded();
-                    let (to_main, from_thread) = channel::unbounded();
-                    channels.push(ThreadChannel {
-                        to_thread,
-                        from_thread,
-                        active: false,
-                    });
-                    thread::spawn(move || {
-                        loop {
-                            let task = match from_main.recv() {
-                                Ok(val) => val,
-                                Err(_) => return,
-                            };
-                            let mut result = Vec::new();
-                            for elem in task {
-                                match elem.step() {
-                                    Some(mut val) => result.append(&mut val),
-                                    None => continue,
-                                }
-                            }
-                            to_main.send(result).unwrap();
-                        }
-                    });
-                }
-                self.children = Some(channels);
-                self.step_parallel();
-            }
+    /// Drains **self** into [Vec]
+    pub fn tree_to_vec(mut tree: RTree<Group>) -> Vec<Group> {
+        let mut field = Vec::new();
+        for group in tree.drain_in_envelope(Field::full_tree()) {
+            field.push(group);
+        }
+        field
+    }
 
-            Some(val) if val.len() == 0 => self.step(), //call unparallelized step if no threads allowed
+    /// Cuts SVG document in size so it can be displayed: view box is set to the field and all
+    /// cells are drawn as a single path without group outlines. Keeps files of large patterns small
+    pub fn normalize_svg(&self, doc: Document) -> Document {
+        let config = SvgConfig {
+            cell_stroke: None,
+            show_envelopes: false,
+            mode: SvgMode::Path,
+            ..SvgConfig::default()
+        };
+        svg_render::draw(svg_render::prepare(doc, self, &config), self, &config)
+    }
+}
 
-            Some(_) => {
-                let channels = self.children.as_mut().unwrap();
-                let size = self.field.size();
-                let channel_count = channels.len();
-                let groups_per_thread = match size / channel_count {
-                    0 => 1,
-                    val => val,
-                };
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::groups::block::Block;
+    use crate::library;
+    fn r_pentomino(x: i64) -> Group {
+        library::find("r-pentomino")
+            .unwrap()
+            .group(Coord { x: x + 1, y: 1 })
+    }
 
-                let mut remainder = size % channel_count;
+    #[test]
+    fn field_rle_export() {
+        let mut tree = RTree::new();
+        tree.insert(r_pentomino(0));
+        tree.insert(r_pentomino(10));
+        let field = Field::new(tree);
+        assert_eq!(
+            field.rle_export(),
+            "#CXRLE Pos=1,-3\nx = 13, y = 3, rule = B3/S23\nb2o8b2o$2o8b2o$bo9bo!\n"
+        );
+
+        //a field that died can be exported and read back
+        let empty = Field::new(RTree::new()).rle_export();
+        let mut field = Field::import(&empty, None).unwrap();
+        field.step();
+        assert_eq!(field.population(), 0);
+        assert_eq!(field.rle_export(), empty);
+    }
 
-                let mut groups: Vec<Group> = Vec::new();
-                for elem in self.field.drain_in_envelope(Field::full_tree()) {
-                    groups.push(elem);
-                }
+    #[test]
+    fn field_import() {
+        let text = "#CXRLE Pos=1,-3\nx = 13, y = 3, rule = B36/S23\nb2o8b2o$2o8b2o$bo9bo!\n";
+        let mut field = Field::import(text, None).unwrap();
+        assert_eq!(field.rule(), "B36/S23".parse().unwrap());
+        assert_eq!(field.rle_export(), text);
+        assert!(matches!(
+            Field::import("x = 2, y = 1\n2q!", None),
+            Err(Error::Format(_))
+        ));
+        //header larger than a block is an error, not a panic
+        assert!(matches!(
+            Field::import("x = 70000, y = 70000\no!", None),
+            Err(Error::Format(err)) if err.reason.contains("too large")
+        ));
+
+        field.request_parallelizm(2);
+        assert_eq!(field.try_step_parallel(), Ok(()));
+        assert_eq!(field.generation(), 1);
+    }
 
-                for ch in channels.iter_mut() {
-                    let group_count = match remainder {
-                        0 => groups_per_thread,
-                        _ => {
-                            remainder -= 1;
-                            groups_per_thread + 1
-                        }
-                    };
-
-                    let mut message_vec = Vec::new();
-                    for _ in 0..group_count {
-                        match groups.pop() {
-                            Some(val) => message_vec.push(val),
-                            None => break,
-                        }
-                    }
+    #[test]
+    fn field_import_empty() {
+        for text in [
+            "x = 0, y = 0\n!",
+            "x = 3, y = 2\n!",
+            "!Name: empty\n",
+            "#Life 1.05\n",
+            "#Life 1.06\n",
+        ] {
+            let mut field = Field::import(text, None).unwrap();
+            assert_eq!(field.group_count(), 0, "{text:?}");
+            field.step();
+            assert_eq!(field.population(), 0);
+        }
+    }
 
-                    ch.to_thread.send(message_vec).unwrap();
-                    ch.active = true;
-                }
+    #[test]
+    fn field_worker_panic() {
+        let mut tree = RTree::new();
+        tree.insert(r_pentomino(0));
+        //block larger than its cells panics on indexing
+        let mut block = Block::new(3, 3);
+        block.x_size = 300;
+        tree.insert(Group::new(Coord { x: 1000, y: 0 }, block));
+        let mut field = Field::new(tree);
+        field.set_thread_pool(Arc::new(ThreadPool::new(2)));
+        let before: Vec<Group> = field.field.iter().cloned().collect();
+
+        assert!(matches!(
+            field.try_step_parallel(),
+            Err(Error::WorkerPanicked(_))
+        ));
+        assert_eq!(field.generation(), 0);
+        assert_eq!(field.field.iter().cloned().collect::<Vec<Group>>(), before);
+    }
 
-                let mut new_field = Vec::new();
-                for ch in channels {
-                    if ch.active == false {
-                        continue;
-                    };
-                    new_field.append(&mut ch.from_thread.recv().expect("This is a problem, some of the threads died too soon\n"));
-                    ch.active = false;
-                }
+    #[test]
+    fn field_thread_pool() {
+        let mut tree = RTree::new();
+        tree.insert(r_pentomino(0));
+        let mut field = Field::new(tree);
+        field.request_parallelizm(2);
+        let pool = field.thread_pool();
+        let mut other = field.clone();
+        assert!(Arc::ptr_eq(&pool, &other.thread_pool()));
+
+        let mut expected = field.clone();
+        for _ in 0..50 {
+            field.step_parallel();
+            other.try_step_parallel().unwrap();
+            expected.step();
+        }
+        assert_eq!(field.rle_export(), expected.rle_export());
+        assert_eq!(other.rle_export(), expected.rle_export());
+
+        other.request_parallelizm(1);
+        assert_eq!(pool.size(), 1);
+        drop((field, other, expected));
+        //the last owner joins the threads
+        assert_eq!(Arc::strong_count(&pool), 1);
+    }
 
-                self.field = RTree::bulk_load(new_field);
-                self.merge();
+    #[test]
+    fn field_shared_pool_resize() {
+        let mut tree = RTree::new();
+        tree.insert(r_pentomino(0));
+        tree.insert(r_pentomino(40));
+        let mut field = Field::new(tree);
+        field.set_thread_pool(Arc::new(ThreadPool::new(2)));
+        let mut other = field.clone();
+        let mut expected = field.clone();
+
+        //workers stopped by the other field must not strand jobs of a step in progress
+        let stepping = thread::spawn(move || {
+            for _ in 0..300 {
+                field.try_step_parallel().unwrap();
+            }
+            field
+        });
+        while !stepping.is_finished() {
+            for count in [0, 2, 1] {
+                other.request_parallelizm(count);
+                thread::yield_now();
             }
         }
+        let field = stepping.join().unwrap();
+        for _ in 0..300 {
+            expected.step();
+        }
+        assert_eq!(field.rle_export(), expected.rle_export());
     }
 
-    /// Requests *count* parallel threads for [Field::step_parallel()]. If system allowed thread count is
-    /// less ther requested, this value is ignored
-    pub fn request_parallelizm(&mut self, count: u8) {
-       self.req_child_count = Some(count); 
+    #[test]
+    fn field_merge() {
+        //vertical dominoes with empty borders, neighbouring ones interact
+        let domino = |x: i64| {
+            let mut block = Block::new(3, 4);
+            block[(1, 1)] = 1;
+            block[(1, 2)] = 1;
+            Group::new(Coord { x: x - 1, y: -1 }, block)
+        };
+        let tree = RTree::bulk_load([0, 20, 1, 10, 2, 11].into_iter().map(domino).collect());
+        let mut field = Field::new(tree);
+        field.merge();
+        let mut sizes: Vec<u64> = field
+            .field
+            .iter()
+            .map(|group| group.block.population())
+            .collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 4, 6]);
+
+        //crossing lines merge into a block larger than allowed
+        let line = |x_size: u32, y_size: u32| {
+            let cells = vec![1; (x_size * y_size) as usize];
+            Block::from_cells(x_size, y_size, &cells)
+        };
+        let mut tree = RTree::new();
+        tree.insert(Group::new(Coord { x: 0, y: 35000 }, line(70000, 1)));
+        tree.insert(Group::new(Coord { x: 35000, y: 0 }, line(1, 70000)));
+        let mut field = Field::new(tree);
+        assert!(matches!(
+            field.clone().try_merge(),
+            Err(Error::BlockTooLarge { .. })
+        ));
+        field.request_parallelizm(2);
+        assert!(matches!(
+            field.try_step_parallel(),
+            Err(Error::BlockTooLarge { .. })
+        ));
     }
 
-    /// Drains **self** into [Vec]
-    pub fn tree_to_vec(mut tree: RTree<Group>) -> Vec<Group> {
-        let mut field = Vec::new();
-        for group in tree.drain_in_envelope(Field::full_tree()) {
-            field.push(group);
+    #[test]
+    fn field_run_until_stable() {
+        let mut tree = RTree::new();
+        tree.insert(r_pentomino(0));
+        let mut field = Field::new(tree);
+        assert_eq!(field.run_until_stable(1000, 100, false), None);
+        assert_eq!(field.generation(), 1000);
+        assert_eq!(field.run_until_stable(2000, 100, false), Some(1103));
+        assert_eq!(field.generation(), 1103);
+        assert!(field.is_stable(100));
+    }
+
+    /// Steps cells of a finite universe one by one, looking neighbours up through
+    /// [Topology::canonical()]
+    fn step_dense(cells: &[Coord], topology: &Topology) -> Vec<Coord> {
+        let (lower, (width, height)) = (topology.lower().unwrap(), topology.size().unwrap());
+        let alive = |cell: Coord| match topology.canonical(cell) {
+            Some(val) => cells.contains(&val),
+            None => false,
+        };
+        //unbounded axes cover the cells and their neighbours
+        let range = |lower: i64, size: i64, axis: fn(&Coord) -> i64| match size {
+            0 => {
+                let min = cells.iter().map(axis).min().unwrap_or(0);
+                min - 1..cells.iter().map(axis).max().unwrap_or(0) + 2
+            }
+            _ => lower..lower + size,
+        };
+        let mut next = Vec::new();
+        for y in range(lower.y, height, |c| c.y) {
+            for x in range(lower.x, width, |c| c.x) {
+                let cell = Coord { x, y };
+                let mut count = 0;
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        count += alive(cell + Coord { x: dx, y: dy }) as u8;
+                    }
+                }
+                count -= alive(cell) as u8;
+                if Rule::conway().next(alive(cell), count) {
+                    next.push(Coord { x, y });
+                }
+            }
         }
-        field
+        next.sort();
+        next
     }
 
-    /// Cuts SVG document in size so it can be displayed
-    pub fn normalize_svg(mut doc: Document) {
-        todo!();
+    fn canonical_cells(field: &Field) -> Vec<Coord> {
+        let mut cells: Vec<Coord> = field
+            .field
+            .iter()
+            .flat_map(|group| group.alive_cells())
+            .map(|cell| field.topology().canonical(cell).unwrap())
+            .collect();
+        cells.sort();
+        cells
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::groups::block::Block;
-    fn lidka() -> Block {
-        //29126 generations evolution
-        let mut block = Block::new(11, 8);
-        block[(1, 1)] = 1;
-        block[(2, 1)] = 1;
-        block[(3, 1)] = 1;
-        block[(4, 2)] = 1;
-        block[(4, 3)] = 1;
-        block[(5, 3)] = 1;
-        block[(7, 5)] = 1;
-        block[(7, 6)] = 1;
-        block[(8, 5)] = 1;
-        block[(9, 1)] = 1;
-        block[(9, 2)] = 1;
-        block[(9, 3)] = 1;
-        block[(9, 5)] = 1;
-        block
-    }
-
-    fn r_pentomino() -> Block {
-        //1103 generations evolution
-        let mut block = Block::new(5, 5);
-        block[(1, 2)] = 1;
-        block[(2, 1)] = 1;
-        block[(2, 2)] = 1;
-        block[(2, 3)] = 1;
-        block[(3, 3)] = 1;
-        block
+    #[test]
+    fn field_topology() {
+        for topology in [
+            "P12,11", "T12,11", "K12*,11", "K12,11*", "T12,0", "T0,11", "P0,11",
+        ] {
+            let topology: Topology = topology.parse().unwrap();
+            let mut state: u32 = 4321;
+            let cells: Vec<u8> = (0..12 * 11)
+                .map(|_| {
+                    state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                    (state >> 16 & 1) as u8
+                })
+                .collect();
+            let block = Block::from_cells(12, 11, &cells);
+            let mut tree = RTree::new();
+            tree.insert(Group::new(topology.lower().unwrap(), block));
+            let mut field = Field::new(tree);
+            field.set_topology(topology);
+
+            let mut expected = canonical_cells(&field);
+            for generation in 0..60 {
+                expected = step_dense(&expected, &topology);
+                field.step();
+                let cells = canonical_cells(&field);
+                assert_eq!(cells, expected, "{} at {}", topology, generation);
+            }
+        }
+    }
+
+    #[test]
+    fn field_topology_glider() {
+        //glider comes back after crossing both seams of a torus
+        let mut glider = Block::new(3, 3);
+        glider[(1, 0)] = 1;
+        glider[(2, 1)] = 1;
+        glider[(0, 2)] = 1;
+        glider[(1, 2)] = 1;
+        glider[(2, 2)] = 1;
+        let mut tree = RTree::new();
+        tree.insert(Group::new(Coord { x: 0, y: 0 }, glider));
+        let mut field = Field::new(tree);
+        field.set_topology("T20,20".parse().unwrap());
+        let start = canonical_cells(&field);
+        for _ in 0..80 {
+            field.step();
+            assert_eq!(canonical_cells(&field).len(), 5);
+        }
+        assert_eq!(canonical_cells(&field), start);
     }
 }
@@ -0,0 +1,278 @@
+//! Contains detection of pattern file formats and dispatch to their parsers and encoders
+use super::rle::{self, RleError, RlePattern};
+use super::rule::Rule;
+use super::{cells, life, Coord};
+use crate::hashlife::macrocell::{self, MacrocellError};
+use std::str::FromStr;
+
+/// Text formats of patterns
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PatternFormat {
+    /// Run length encoded, see [rle]
+    Rle,
+    /// Plaintext rows of `.` and `O`, see [cells]
+    Cells,
+    /// Blocks of `.` and `*` rows placed by `#P` lines, see [life]
+    Life105,
+    /// Coordinates of alive cells, see [life]
+    Life106,
+    /// Golly's quadtree, see [macrocell]
+    Macrocell,
+}
+
+impl PatternFormat {
+    /// Guesses the format by the file extension: `.rle`, `.cells`, `.lif`/`.life` (Life 1.06) and
+    /// `.mc`
+    pub fn from_path(path: &str) -> Option<PatternFormat> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "rle" => Some(PatternFormat::Rle),
+            "cells" => Some(PatternFormat::Cells),
+            "lif" | "life" => Some(PatternFormat::Life106),
+            "mc" => Some(PatternFormat::Macrocell),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format by the contents of the pattern. Life files are recognised by their
+    /// header, plaintext by `!` comments or rows of `.` and `O`. Anything else is taken as RLE
+    pub fn detect(text: &str) -> PatternFormat {
+        let Some(first) = text.lines().map(str::trim).find(|line| !line.is_empty()) else {
+            return PatternFormat::Rle;
+        };
+        if first.starts_with(life::HEADER_105) {
+            PatternFormat::Life105
+        } else if first.starts_with(life::HEADER_106) {
+            PatternFormat::Life106
+        } else if first.starts_with(macrocell::HEADER) {
+            PatternFormat::Macrocell
+        } else if first.starts_with('!') || first.chars().all(|c| matches!(c, '.' | 'O' | '*')) {
+            PatternFormat::Cells
+        } else {
+            PatternFormat::Rle
+        }
+    }
+}
+
+impl FromStr for PatternFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "rle" => Ok(PatternFormat::Rle),
+            "cells" | "plaintext" => Ok(PatternFormat::Cells),
+            "life105" | "life1.05" => Ok(PatternFormat::Life105),
+            "life106" | "life1.06" => Ok(PatternFormat::Life106),
+            "mc" | "macrocell" => Ok(PatternFormat::Macrocell),
+            _ => Err(format!(
+                "unknown pattern format '{}', expected rle, cells, life105, life106 or mc",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for PatternFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PatternFormat::Rle => "rle",
+            PatternFormat::Cells => "cells",
+            PatternFormat::Life105 => "life105",
+            PatternFormat::Life106 => "life106",
+            PatternFormat::Macrocell => "mc",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Error returned when pattern text cannot be parsed. Lines and columns are counted from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatError {
+    pub format: PatternFormat,
+    pub line: usize,
+    pub column: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} parse error at line {}, column {}: {}",
+            self.format, self.line, self.column, self.reason
+        )
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl FormatError {
+    /// Error of a pattern too large to be held in one [Block](super::block::Block)
+    pub(crate) fn too_large(format: PatternFormat, err: crate::Error) -> FormatError {
+        FormatError {
+            format,
+            line: 1,
+            column: 1,
+            reason: err.to_string(),
+        }
+    }
+}
+
+impl From<MacrocellError> for FormatError {
+    fn from(err: MacrocellError) -> Self {
+        FormatError {
+            format: PatternFormat::Macrocell,
+            line: err.line,
+            column: 1,
+            reason: err.reason,
+        }
+    }
+}
+
+impl From<RleError> for FormatError {
+    fn from(err: RleError) -> Self {
+        FormatError {
+            format: PatternFormat::Rle,
+            line: err.line,
+            column: err.column,
+            reason: err.reason,
+        }
+    }
+}
+
+/// Parses pattern text of given format. Position of the pattern is its top left corner with y
+/// axis going down, as in RLE
+///
+/// Macrocell patterns are unpacked into a single block, use
+/// [Field::macrocell_import()](super::field::Field::macrocell_import()) for big ones
+pub fn parse(text: &str, format: PatternFormat) -> Result<RlePattern, FormatError> {
+    match format {
+        PatternFormat::Rle => Ok(rle::parse(text)?),
+        PatternFormat::Cells => cells::parse(text),
+        PatternFormat::Life105 => life::parse_105(text),
+        PatternFormat::Life106 => life::parse_106(text),
+        PatternFormat::Macrocell => {
+            let life = macrocell::parse(text)?;
+            let cells: Vec<(i64, i64)> = life
+                .alive_cells()
+                .into_iter()
+                .map(|cell| (cell.x, -cell.y))
+                .collect();
+            let mut pattern = RlePattern::from_cells(&cells, format)?;
+            pattern.rule = Some(life.rule());
+            Ok(pattern)
+        }
+    }
+}
+
+/// Encodes alive cells into given format
+///
+/// Arguments are the same as for [rle::encode()]. Plaintext has no rule and position, Life 1.05
+/// has no bounded grids and writes *rule* as survival/birth counts. Macrocell has no position
+/// either, its root is centered at *pos*
+pub fn encode(
+    cells: &[(u64, u64)],
+    x_size: u64,
+    y_size: u64,
+    rule: &Rule,
+    pos: Option<(i64, i64)>,
+    format: PatternFormat,
+) -> String {
+    match format {
+        PatternFormat::Rle => rle::encode(cells, x_size, y_size, rule, pos),
+        PatternFormat::Cells => cells::encode(cells, y_size),
+        PatternFormat::Life105 => life::encode_105(cells, y_size, rule, pos),
+        PatternFormat::Life106 => life::encode_106(cells, pos),
+        PatternFormat::Macrocell => {
+            let (x, y) = pos.unwrap_or((0, 0));
+            let cells: Vec<Coord> = cells
+                .iter()
+                .map(|(col, row)| Coord {
+                    x: x + *col as i64,
+                    y: -(y + *row as i64),
+                })
+                .collect();
+            macrocell::encode_global(&cells, rule, 0)
+        }
+    }
+}
+
+/// Encodes alive cells given in global [Field](super::field::Field) coordinates into given format
+/// with a tight bounding box
+///
+/// Field y axis goes up, so the topmost row is written first and the position is given with y
+/// negated
+pub fn encode_global(cells: &[Coord], rule: &Rule, format: PatternFormat) -> String {
+    if cells.is_empty() {
+        return encode(&[], 0, 0, rule, None, format);
+    }
+    let x_min = cells.iter().map(|c| c.x).min().unwrap();
+    let x_max = cells.iter().map(|c| c.x).max().unwrap();
+    let y_min = cells.iter().map(|c| c.y).min().unwrap();
+    let y_max = cells.iter().map(|c| c.y).max().unwrap();
+
+    let mut offsets: Vec<(u64, u64)> = cells
+        .iter()
+        .map(|c| ((c.x - x_min) as u64, (y_max - c.y) as u64))
+        .collect();
+    offsets.sort_by_key(|(col, row)| (*row, *col));
+    offsets.dedup();
+
+    encode(
+        &offsets,
+        (x_max - x_min + 1) as u64,
+        (y_max - y_min + 1) as u64,
+        rule,
+        Some((x_min, -y_max)),
+        format,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_detect() {
+        let glider = [
+            "#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n",
+            "!Name: Glider\n.O\n..O\nOOO\n",
+            "#Life 1.05\n#P -1 -1\n.*\n..*\n***\n",
+            "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n",
+        ];
+        let formats = [
+            PatternFormat::Rle,
+            PatternFormat::Cells,
+            PatternFormat::Life105,
+            PatternFormat::Life106,
+        ];
+        let expected = rle::parse(glider[0]).unwrap().block;
+        for (text, format) in glider.iter().zip(formats) {
+            assert_eq!(PatternFormat::detect(text), format);
+            assert_eq!(format.to_string().parse(), Ok(format));
+            assert_eq!(parse(text, format).unwrap().block, expected);
+        }
+        assert_eq!(PatternFormat::detect("\n.O.\n"), PatternFormat::Cells);
+        assert_eq!(
+            PatternFormat::from_path("gun.cells"),
+            Some(PatternFormat::Cells)
+        );
+        assert_eq!(PatternFormat::from_path("gun"), None);
+
+        let err = parse("x = 2, y = 1\n2q!", PatternFormat::Rle).unwrap_err();
+        assert_eq!(err.format, PatternFormat::Rle);
+        assert!(err.to_string().starts_with("rle parse error at line 2"));
+
+        let cells = [
+            Coord { x: 0, y: 1 },
+            Coord { x: 1, y: 0 },
+            Coord { x: -1, y: -1 },
+            Coord { x: 0, y: -1 },
+            Coord { x: 1, y: -1 },
+        ];
+        assert_eq!(
+            encode_global(&cells, &Rule::conway(), PatternFormat::Life106),
+            "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n"
+        );
+    }
+}
@@ -1,15 +1,49 @@
 //! Contains [Group] struct and its methods
-use super::block::Block;
+use super::block::{Block, SplitMode};
+use super::format::{self, PatternFormat};
+use super::rle;
+use super::rule::Rule;
 use super::Coord;
 use super::UCoord;
+use crate::render::svg::{self as svg_render, SvgConfig};
+use crate::Error;
 use rstar::{RTreeObject, AABB};
-use svg::node::element::Rectangle;
+use std::sync::Arc;
+
+/// Longest period of oscillators whose phases are cached
+const MAX_CACHED_PERIOD: u32 = 15;
+/// Generations a group has to be stepped unchanged by merging or splitting before its period is
+/// looked for. Later checks are done at powers of two of its age
+const MIN_CHECK_AGE: u32 = 16;
+
+/// Known behaviour of a [Group] found while stepping it. Lets [Group::step()] reuse generations
+/// it has already computed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stability {
+    /// Not found to repeat yet. *age* is the number of generations the group has been stepped
+    /// without merging or splitting
+    Unknown { age: u32 },
+    /// Repeats in place every `phases.len()` generations under *rule*. Phases hold the block and
+    /// its offset from the previous phase, *phase* is the index of the current one
+    Periodic {
+        rule: Rule,
+        phases: Arc<Vec<(Coord, Block)>>,
+        phase: usize,
+    },
+}
+
+impl Default for Stability {
+    fn default() -> Self {
+        Stability::Unknown { age: 0 }
+    }
+}
 
 /// Contains cell data in [Block], global coords and other analysis data
-#[derive(Debug, Eq)]
+#[derive(Debug, Clone, Eq)]
 pub struct Group {
     pub global_coord: Coord,
     pub block: Block,
+    stability: Stability,
 }
 
 impl std::cmp::PartialEq for Group {
@@ -20,7 +54,7 @@ impl std::cmp::PartialEq for Group {
 
 impl std::cmp::PartialOrd for Group {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.global_coord.partial_cmp(&other.global_coord)
+        Some(self.cmp(other))
     }
 }
 
@@ -41,119 +75,274 @@ impl RTreeObject for Group {
 impl Group {
     /// Returns global coordinates of top right corner of group
     pub fn top_right(&self) -> Coord {
-        return Coord {
+        Coord {
             x: self.global_coord.x + self.block.x_size as i64 - 1,
             y: self.global_coord.y + self.block.y_size as i64 - 1,
-        };
+        }
     }
 
     /// Checks if other group is intersecting **self**
     pub fn intersects(&self, other: &Group) -> bool {
         let self_tr = self.top_right();
         let other_tr = other.top_right();
-        if (self.global_coord.x > other_tr.x || self.global_coord.y > other_tr.y)
-            || (other.global_coord.x > self_tr.x || other.global_coord.y > self_tr.y)
-        {
-            return false;
-        } else {
-            return true;
-        }
+        !((self.global_coord.x > other_tr.x || self.global_coord.y > other_tr.y)
+            || (other.global_coord.x > self_tr.x || other.global_coord.y > self_tr.y))
     }
 
     /// Consumes two groups and returns new group containing both
     pub fn merge(self, other: Group) -> Group {
-        let left_bottom = Coord {
-            x: std::cmp::min(self.global_coord.x, other.global_coord.x),
-            y: std::cmp::min(self.global_coord.y, other.global_coord.y),
-        };
+        Group::merge_many([self, other]).unwrap()
+    }
 
-        let self_offset = UCoord {
-            x: (self.global_coord.x - left_bottom.x) as u32,
-            y: (self.global_coord.y - left_bottom.y) as u32,
-        };
+    /// Consumes groups and returns new group containing all of them. The block of the result is
+    /// allocated once and blocks of the groups are copied into it row by row
+    ///
+    /// Returns [None] if there are no groups, a single group is returned as it is
+    /// # Panics
+    /// When the merged block is too large, see [Group::try_merge_many()]
+    pub fn merge_many(groups: impl IntoIterator<Item = Group>) -> Option<Group> {
+        Group::try_merge_many(groups).unwrap_or_else(|err| panic!("{}", err))
+    }
 
-        let other_offset = UCoord {
-            x: (other.global_coord.x - left_bottom.x) as u32,
-            y: (other.global_coord.y - left_bottom.y) as u32,
-        };
+    /// Consumes groups and returns new group containing all of them, see [Group::merge_many()]
+    ///
+    /// Returns [Error::BlockTooLarge] when the merged block does not fit into a [Block]
+    pub fn try_merge_many(groups: impl IntoIterator<Item = Group>) -> Result<Option<Group>, Error> {
+        let groups: Vec<Group> = groups.into_iter().collect();
+        if groups.len() <= 1 {
+            return Ok(groups.into_iter().next());
+        }
 
-        let mut new_block = Block::new(1, 1);
-        new_block.insert(self_offset, &self.block);
-        new_block.insert(other_offset, &other.block);
+        //there are at least two groups
+        let left_bottom = groups
+            .iter()
+            .map(|group| group.global_coord)
+            .reduce(|a, b| Coord {
+                x: std::cmp::min(a.x, b.x),
+                y: std::cmp::min(a.y, b.y),
+            })
+            .unwrap();
+        let top_right = groups
+            .iter()
+            .map(Group::top_right)
+            .reduce(|a, b| Coord {
+                x: std::cmp::max(a.x, b.x),
+                y: std::cmp::max(a.y, b.y),
+            })
+            .unwrap();
+
+        let mut new_block = Block::try_new(
+            (top_right.x - left_bottom.x + 1) as u64,
+            (top_right.y - left_bottom.y + 1) as u64,
+        )?;
+        for group in &groups {
+            let offset = UCoord {
+                x: (group.global_coord.x - left_bottom.x) as u32,
+                y: (group.global_coord.y - left_bottom.y) as u32,
+            };
+            new_block.insert(offset, &group.block);
+        }
 
-        let new = Group {
+        Ok(Some(Group {
             global_coord: left_bottom,
             block: new_block,
-        };
-        new
+            stability: Stability::default(),
+        }))
     }
 
-    /// Splits **self** (consumes it) into not intersecting pieces.
+    /// Splits **self** (consumes it) into not intersecting pieces, see
+    /// [Block::split_components()]
     ///
     /// Returns [None] if group is empty. Otherwise returns vector of new groups
     pub fn split(self) -> Option<Vec<Group>> {
-        let blocks = match self.block.split() {
-            None => return None,
-            Some(i) => i,
-        };
+        self.split_with(SplitMode::default())
+    }
+
+    /// Splits **self** (consumes it) into not intersecting pieces the given way
+    ///
+    /// Returns [None] if group is empty. Otherwise returns vector of new groups
+    pub fn split_with(self, mode: SplitMode) -> Option<Vec<Group>> {
+        let blocks = self.block.split_with(mode)?;
 
         let mut groups = Vec::new();
         for i in blocks {
             let piece = Group {
                 global_coord: i.1 + self.global_coord,
                 block: i.0,
+                stability: Stability::default(),
             };
             groups.push(piece);
         }
         Some(groups)
     }
 
-    /// Advances **self** to next game generation.
+    /// Advances **self** to next game generation under given [Rule]. Adds empty borders first if
+    /// the block has alive cells on its sides (e.g. right after import)
+    ///
+    /// Still lifes and oscillators are found on the fly (see [Stability]), after that their
+    /// phases are reused instead of being computed again
+    ///
+    /// Stepped groups are split into [stripes](SplitMode::Stripes). Groups not found to be
+    /// periodic are also split into [components](SplitMode::Components) when checked, so
+    /// objects lying diagonally to each other end up in separate groups without searching for
+    /// components every generation
+    ///
+    /// Returns [None] if no alive cells remain. Otherwise returns vector of new independent groups
+    pub fn step(mut self, rule: &Rule) -> Option<Vec<Group>> {
+        match &self.stability {
+            Stability::Periodic {
+                rule: cached,
+                phases,
+                phase,
+            } if cached == rule => {
+                if phases.len() > 1 {
+                    let phase = (phase + 1) % phases.len();
+                    let (offset, block) = &phases[phase];
+                    self.global_coord = self.global_coord + *offset;
+                    self.block = block.clone();
+                    self.stability = Stability::Periodic {
+                        rule: *rule,
+                        phases: phases.clone(),
+                        phase,
+                    };
+                }
+                return Some(vec![self]);
+            }
+            _ => (),
+        }
+
+        if self.block.need_expand() {
+            self.global_coord = self.global_coord + self.block.resize()?;
+        }
+        self.block.step(rule);
+        self.split_stepped(rule)
+    }
+
+    /// Splits **self** (consumes it) after its block was stepped under *rule*, carrying its age
+    /// forward and checking its period as [Group::step()] does. Used for blocks stepped outside of
+    /// [Group::step()], e.g. in tiles by [ThreadPool](super::pool::ThreadPool)
     ///
     /// Returns [None] if no alive cells remain. Otherwise returns vector of new independent groups
-    pub fn step(mut self) -> Option<Vec<Group>> {
-        self.block.step();
-        self.split()
+    pub(crate) fn split_stepped(self, rule: &Rule) -> Option<Vec<Group>> {
+        let age = match &self.stability {
+            Stability::Periodic { .. } => 0,
+            Stability::Unknown { age } => *age + 1,
+        };
+
+        let mut groups = self.split_with(SplitMode::Stripes)?;
+        if let [group] = groups.as_mut_slice() {
+            group.stability = Stability::Unknown { age };
+            if age >= MIN_CHECK_AGE && age.is_power_of_two() {
+                group.find_period(rule);
+                if group.period().is_none() {
+                    //a copy, the group keeps its age when it is one component
+                    let pieces = group.clone().split_with(SplitMode::Components)?;
+                    if pieces.len() > 1 {
+                        return Some(pieces);
+                    }
+                }
+            }
+        }
+        Some(groups)
+    }
+
+    /// Advances **self** to next game generation without looking at its [Stability]
+    fn step_uncached(mut self, rule: &Rule) -> Option<Vec<Group>> {
+        if self.block.need_expand() {
+            self.global_coord = self.global_coord + self.block.resize()?;
+        }
+        self.block.step(rule);
+        self.split_with(SplitMode::Stripes)
+    }
+
+    /// Steps a copy of **self** looking for a return to the same cells at the same place within
+    /// [MAX_CACHED_PERIOD] generations. Caches the phases when found
+    fn find_period(&mut self, rule: &Rule) {
+        let mut phases = vec![(Coord { x: 0, y: 0 }, self.block.clone())];
+        let mut current = Group::new(self.global_coord, self.block.clone());
+        for _ in 0..MAX_CACHED_PERIOD {
+            let next = match current.clone().step_uncached(rule) {
+                Some(mut val) if val.len() == 1 => val.pop().unwrap(),
+                _ => return,
+            };
+            let offset = Coord {
+                x: next.global_coord.x - current.global_coord.x,
+                y: next.global_coord.y - current.global_coord.y,
+            };
+            if next == *self {
+                phases[0].0 = offset;
+                self.stability = Stability::Periodic {
+                    rule: *rule,
+                    phases: Arc::new(phases),
+                    phase: 0,
+                };
+                return;
+            }
+            phases.push((offset, next.block.clone()));
+            current = next;
+        }
+    }
+
+    /// Returns the period of the group if it is known to repeat in place: 1 for still lifes
+    pub fn period(&self) -> Option<u32> {
+        match &self.stability {
+            Stability::Periodic { phases, .. } => Some(phases.len() as u32),
+            Stability::Unknown { .. } => None,
+        }
+    }
+
+    /// Checks if [Group::step()] takes the next generation from cached phases instead of
+    /// computing it under *rule*
+    pub fn is_cached(&self, rule: &Rule) -> bool {
+        matches!(&self.stability, Stability::Periodic { rule: cached, .. } if cached == rule)
+    }
+
+    /// Returns what is known about behaviour of the group
+    pub fn stability(&self) -> &Stability {
+        &self.stability
+    }
+
+    /// Forgets the behaviour of the group, e.g. after its cells were changed from outside
+    pub fn reset_stability(&mut self) {
+        self.stability = Stability::default();
     }
 
     /// Inserts group cells data into svg document
-    pub fn svg_add(&self, mut doc: svg::Document) -> svg::Document {
-        let size = 10;
-        let group_rect = Rectangle::new()
-            .set("x", self.global_coord.x as i64 * size)
-            .set("y", self.global_coord.y as i64 * size)
-            .set("width", self.block.x_size as i64 * size)
-            .set("height", self.block.y_size as i64 * size)
-            .set("stroke", "red")
-            .set("stroke-width", 0.3)
-            .set("fill", "black")
-            .set("fill-opacity", "0.01");
-        for x in 0..self.block.x_size {
-            for y in 0..self.block.y_size {
+    pub fn svg_add(&self, doc: svg::Document) -> svg::Document {
+        svg_render::draw_group(doc, self, &SvgConfig::default())
+    }
+
+    /// Returns global coordinates of all alive cells of the group
+    pub fn alive_cells(&self) -> Vec<Coord> {
+        let mut cells = Vec::new();
+        for y in 0..self.block.y_size {
+            for x in 0..self.block.x_size {
                 if self.block[(x, y)] == 1 {
-                    let mut cell = Rectangle::new();
-                    cell = cell
-                        .set("x", (self.global_coord.x + x as i64) * size)
-                        .set("y", (self.global_coord.y + y as i64) * size)
-                        .set("width", size)
-                        .set("height", size)
-                        .set("fill", "green")
-                        .set("stroke", "black")
-                        .set("stroke-width", 0.3);
-                    doc = doc.add(cell);
+                    cells.push(self.global_coord + UCoord { x, y }.into());
                 }
             }
         }
-        doc.add(group_rect)
+        cells
+    }
+
+    /// Exports group cells to RLE string with a `#CXRLE Pos=` line holding its global position
+    pub fn rle_export(&self, rule: &Rule) -> String {
+        rle::encode_global(&self.alive_cells(), rule)
+    }
+
+    /// Exports group cells to given pattern format, keeping its global position where the format
+    /// allows it
+    pub fn export(&self, rule: &Rule, format: PatternFormat) -> String {
+        format::encode_global(&self.alive_cells(), rule, format)
     }
 
     /// Creates a new group with given global coords and [Block]
     pub fn new(global_coord: Coord, block: Block) -> Self {
-        let group = Group {
+        Group {
             global_coord,
             block,
-        };
-        group
+            stability: Stability::default(),
+        }
     }
 
     /// Reverses group cells by y coord. Helps with the difference of global coordinates in [Field](super::field::Field) and svg format
@@ -171,11 +360,11 @@ impl Group {
         };
     }
 
-    /// Checks if two groups intersect in a way, that it causes some new cells to be born. This is
-    /// useful to avoid merging pseudo- and quasi- <a href="https://conwaylife.com/wiki/Still_life" target="_blank">still lives</a>
+    /// Checks if two groups intersect in a way, that it causes some new cells to be born under
+    /// given [Rule]. This is useful to avoid merging pseudo- and quasi- <a href="https://conwaylife.com/wiki/Still_life" target="_blank">still lives</a>
     /// or constellations
-    pub fn intersects_smart(&self, other: &Group) -> bool {
-        if self.intersects(other) == false {
+    pub fn intersects_smart(&self, other: &Group, rule: &Rule) -> bool {
+        if !self.intersects(other) {
             return false;
         }
 
@@ -203,30 +392,21 @@ impl Group {
                 let self_count = self.block.neighbour_count(self_offset);
                 let other_count = other.block.neighbour_count(other_offset);
                 let sum_count = self_count + other_count;
-    
-                let self_alive = match self.block[self_offset] {
-                    0 => false,
-                    _ => true,
-                };
 
-                let other_alive = match other.block[other_offset] {
-                    0 => false,
-                    _ => true,
-                };
+                let self_alive = self.block[self_offset] != 0;
+                let other_alive = other.block[other_offset] != 0;
 
                 if (self_count == 0) || (other_count == 0) {
                     continue;
                 }
 
-                //if alive/born in one, but dead in sum - interfering
-                if (self_alive && self_count == 2) || (self_count == 3) || (other_alive && other_count == 2) || (other_count == 3) {
-                    if sum_count > 3 {
-                        return true;
-                    }
-                }
+                let self_next = rule.next(self_alive, self_count);
+                let other_next = rule.next(other_alive, other_count);
+                let sum_next = rule.next(self_alive || other_alive, sum_count);
 
-                //if not alive in both, but alive in sum - interfering
-                if (!self_alive) && (!other_alive) && (sum_count == 3) {
+                //if alive/born in one, but dead in sum or not alive in both, but alive in sum -
+                //interfering
+                if sum_next != (self_next || other_next) {
                     return true;
                 }
 
@@ -263,6 +443,7 @@ mod test {
         let group_first = Group {
             global_coord: Coord { x: 1, y: 1 },
             block: block_first,
+            stability: Stability::default(),
         };
 
         let mut block_second = Block::new(6, 6);
@@ -283,9 +464,10 @@ mod test {
         let group_second = Group {
             global_coord: Coord { x: 5, y: 1 },
             block: block_second,
+            stability: Stability::default(),
         };
 
-        let result = group_first.merge(group_second);
+        let result = group_first.clone().merge(group_second.clone());
 
         let mut block_check = Block::new(10, 6);
         //0 0 0 0 0 0 0 0 0 0
@@ -314,8 +496,28 @@ mod test {
         let check = Group {
             global_coord: Coord { x: 1, y: 1 },
             block: block_check,
+            stability: Stability::default(),
         };
         assert_eq!(result, check);
+
+        //the same cells merged at once, with a group far from both
+        let far = Group::new(
+            Coord { x: 40, y: -20 },
+            rle::parse("x = 3, y = 1\n3o!").unwrap().block,
+        );
+        let expected = check.clone().merge(far.clone());
+        assert_eq!(
+            Group::merge_many([far, group_first, group_second]),
+            Some(expected)
+        );
+        assert_eq!(Group::merge_many([check.clone()]), Some(check));
+        assert_eq!(Group::merge_many(Vec::new()), None);
+
+        let far = Group::new(Coord { x: 70000, y: 70000 }, Block::new(3, 3));
+        assert!(matches!(
+            Group::try_merge_many([far, Group::new(Coord { x: 0, y: 0 }, Block::new(3, 3))]),
+            Err(Error::BlockTooLarge { .. })
+        ));
     }
 
     #[test]
@@ -335,11 +537,38 @@ mod test {
         let group = Group {
             global_coord: Coord { x: 5, y: -10 },
             block,
+            stability: Stability::default(),
         };
 
         assert_eq!(group.top_right(), Coord { x: 9, y: -6 });
     }
 
+    #[test]
+    fn group_rle_export() {
+        let mut block = Block::new(5, 5);
+        //0 0 0 0 0
+        //0 0 1 0 0
+        //0 0 0 1 0
+        //0 1 1 1 0
+        //0 0 0 0 0
+        block[(1, 1)] = 1;
+        block[(2, 1)] = 1;
+        block[(3, 1)] = 1;
+        block[(3, 2)] = 1;
+        block[(2, 3)] = 1;
+
+        let group = Group {
+            global_coord: Coord { x: 5, y: -10 },
+            block,
+            stability: Stability::default(),
+        };
+
+        assert_eq!(
+            group.rle_export(&Rule::conway()),
+            "#CXRLE Pos=6,7\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+        );
+    }
+
     #[test]
     fn group_split() {
         let mut block = Block::new(1, 1);
@@ -374,22 +603,84 @@ mod test {
         let group = Group {
             global_coord: Coord { x: 0, y: 0 },
             block,
+            stability: Stability::default(),
         };
         let g1 = Group {
             global_coord: Coord { x: 0, y: 16 },
             block: b1,
+            stability: Stability::default(),
         };
         let g2 = Group {
             global_coord: Coord { x: 3, y: 0 },
             block: b2,
+            stability: Stability::default(),
         };
-        let new = group.split().unwrap();
+        let new = group.clone().split_with(SplitMode::Stripes).unwrap();
         assert_eq!(new[1], g2);
         assert_eq!(new[0], g1);
         assert_eq!(new.len(), 2);
+
+        //pieces already apart as stripes are not split further
+        assert_eq!(group.split().unwrap(), vec![g1, g2]);
+    }
+
+    #[test]
+    fn group_step_no_border() {
+        let mut block = Block::new(3, 1);
+        block[(0, 0)] = 1;
+        block[(1, 0)] = 1;
+        block[(2, 0)] = 1;
+        let group = Group::new(Coord { x: 0, y: 0 }, block);
+
+        let mut blinker = Block::new(3, 5);
+        blinker[(1, 1)] = 1;
+        blinker[(1, 2)] = 1;
+        blinker[(1, 3)] = 1;
+        let result = Group::new(Coord { x: 0, y: -2 }, blinker);
+
+        assert_eq!(group.step(&Rule::conway()).unwrap(), vec![result]);
+    }
+
+    #[test]
+    fn group_step_cached() {
+        let step = |group: Group| {
+            let mut groups = group.step(&Rule::conway()).unwrap();
+            assert_eq!(groups.len(), 1);
+            groups.pop().unwrap()
+        };
+        let parse = |rle: &str| Group::new(Coord { x: 5, y: -3 }, rle::parse(rle).unwrap().block);
+        let mut blinker = parse("x = 3, y = 1\n3o!");
+        let mut still = parse("x = 2, y = 2\n2o$2o!");
+        let mut glider = parse("x = 3, y = 3\nbo$2bo$3o!");
+        for _ in 0..MIN_CHECK_AGE {
+            assert_eq!(blinker.period(), None);
+            blinker = step(blinker);
+            still = step(still);
+            glider = step(glider);
+        }
+        assert_eq!(blinker.period(), Some(2));
+        assert_eq!(still.period(), Some(1));
+        assert_eq!(glider.period(), None);
+
+        let mut expected = Group::new(blinker.global_coord, blinker.block.clone());
+        for _ in 0..5 {
+            blinker = step(blinker);
+            expected = expected
+                .step_uncached(&Rule::conway())
+                .unwrap()
+                .pop()
+                .unwrap();
+            assert_eq!(blinker, expected);
+            assert_eq!(blinker.period(), Some(2));
+        }
+
+        //phases of another rule are not used
+        let rule: Rule = "B3/S".parse().unwrap();
+        assert_eq!(still.step(&rule), None);
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn group_smart_intersection() {
         let mut block1 = Block::new(5, 3);
         block1[(1, 1)] = 1;
@@ -398,6 +689,7 @@ mod test {
         let group1 = Group {
             global_coord: Coord { x: 0, y: 0 },
             block: block1,
+            stability: Stability::default(),
         };
 
         let mut block2 = Block::new(3, 5);
@@ -407,9 +699,10 @@ mod test {
         let group2 = Group {
             global_coord: Coord { x: 4, y: -4 },
             block: block2,
+            stability: Stability::default(),
         };
 
-        assert_eq!(group1.intersects_smart(&group2), false);
+        assert_eq!(group1.intersects_smart(&group2, &Rule::conway()), false);
 
         block1 = Block::new(4, 4);
         block1[(1, 1)] = 1;
@@ -419,6 +712,7 @@ mod test {
         let group1 = Group {
             global_coord: Coord { x: 0, y: 0 },
             block: block1,
+            stability: Stability::default(),
         };
 
         block2 = Block::new(4, 4);
@@ -429,22 +723,25 @@ mod test {
         let group2 = Group {
             global_coord: Coord { x: 3, y: 0 },
             block: block2,
+            stability: Stability::default(),
         };
 
-        assert_eq!(group1.intersects_smart(&group2), false);
+        assert_eq!(group1.intersects_smart(&group2, &Rule::conway()), false);
 
         let group1 = Group {
             global_coord: Coord { x: 0, y: 0 },
             block: Block::new(10, 10),
+            stability: Stability::default(),
         };
 
-        assert_eq!(group1.intersects_smart(&group2), false);
+        assert_eq!(group1.intersects_smart(&group2, &Rule::conway()), false);
 
         block1 = Block::new(3, 3);
         block1[(1, 1)] = 1;
         let group1 = Group {
             global_coord: Coord { x: 0, y: 0 },
             block: block1,
+            stability: Stability::default(),
         };
 
         block2 = Block::new(3, 5);
@@ -453,9 +750,10 @@ mod test {
         let group2 = Group {
             global_coord: Coord { x: 2, y: -2 },
             block: block2,
+            stability: Stability::default(),
         };
 
-        assert_eq!(group1.intersects_smart(&group2), true);
+        assert_eq!(group1.intersects_smart(&group2, &Rule::conway()), true);
 
     }
 }
@@ -0,0 +1,220 @@
+//! Contains Life 1.05 and Life 1.06 parsers and encoding
+//!
+//! Life 1.06 lists coordinates of alive cells, one `x y` pair per line. Life 1.05 holds blocks of
+//! `.` and `*` rows, each placed by a `#P x y` line, with `#D` comments and `#N` or `#R` rule
+//! lines. Both use y axis going down, as RLE does
+use super::format::{FormatError, PatternFormat};
+use super::rle::RlePattern;
+use super::rule::Rule;
+
+/// Header of Life 1.05 files
+pub const HEADER_105: &str = "#Life 1.05";
+/// Header of Life 1.06 files
+pub const HEADER_106: &str = "#Life 1.06";
+
+/// Parses Life 1.06 text into [RlePattern]
+pub fn parse_106(text: &str) -> Result<RlePattern, FormatError> {
+    let error = |line: usize, column: usize, reason: String| FormatError {
+        format: PatternFormat::Life106,
+        line: line + 1,
+        column,
+        reason,
+    };
+
+    let mut cells = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let values: Vec<&str> = line.split_whitespace().collect();
+        let [x, y] = values[..] else {
+            return Err(error(index, 1, "expected 'x y' coordinates".to_string()));
+        };
+        let x = x
+            .parse::<i64>()
+            .map_err(|_| error(index, 1, format!("invalid coordinate '{}'", x)))?;
+        let y = y
+            .parse::<i64>()
+            .map_err(|_| error(index, 1, format!("invalid coordinate '{}'", y)))?;
+        cells.push((x, y));
+    }
+    RlePattern::from_cells(&cells, PatternFormat::Life106)
+}
+
+/// Parses Life 1.05 text into [RlePattern]. `#D` lines become comments, `#N` sets Conway's rule
+/// and `#R` sets a rule written as survival/birth counts (e.g. `#R 23/36`)
+pub fn parse_105(text: &str) -> Result<RlePattern, FormatError> {
+    let error = |line: usize, column: usize, reason: String| FormatError {
+        format: PatternFormat::Life105,
+        line: line + 1,
+        column,
+        reason,
+    };
+
+    let mut cells = Vec::new();
+    let mut rule = None;
+    let mut comments = Vec::new();
+    //top left corner of the current block and current row in it
+    let mut origin: (i64, i64) = (0, 0);
+    let mut row = 0;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.starts_with("#Life") {
+            continue;
+        } else if let Some(comment) = line.strip_prefix("#D") {
+            comments.push(comment.trim().to_string());
+        } else if line.starts_with("#N") {
+            rule = Some(Rule::conway());
+        } else if let Some(val) = line.strip_prefix("#R") {
+            rule = Some(
+                val.trim()
+                    .parse()
+                    .map_err(|err: super::rule::RuleError| error(index, 3, err.to_string()))?,
+            );
+        } else if let Some(val) = line.strip_prefix("#P") {
+            let values: Vec<Result<i64, _>> =
+                val.split_whitespace().map(|val| val.parse()).collect();
+            origin = match values[..] {
+                [Ok(x), Ok(y)] => (x, y),
+                _ => return Err(error(index, 3, "expected '#P x y'".to_string())),
+            };
+            row = 0;
+        } else if line.starts_with('#') {
+            continue;
+        } else {
+            for (column, char) in line.chars().enumerate() {
+                match char {
+                    '.' => (),
+                    '*' | 'O' | 'o' => cells.push((origin.0 + column as i64, origin.1 + row)),
+                    _ => {
+                        return Err(error(
+                            index,
+                            column + 1,
+                            format!("unexpected character '{}'", char),
+                        ))
+                    }
+                }
+            }
+            row += 1;
+        }
+    }
+
+    let mut pattern = RlePattern::from_cells(&cells, PatternFormat::Life105)?;
+    pattern.rule = rule;
+    pattern.comments = comments;
+    Ok(pattern)
+}
+
+/// Encodes alive cells into Life 1.06
+///
+/// *cells* are `(column, row)` offsets from the top left corner *pos* of the pattern
+pub fn encode_106(cells: &[(u64, u64)], pos: Option<(i64, i64)>) -> String {
+    let (x, y) = pos.unwrap_or((0, 0));
+    let mut out = String::from(HEADER_106);
+    out.push('\n');
+    for (col, row) in cells {
+        out += &format!("{} {}\n", x + *col as i64, y + *row as i64);
+    }
+    out
+}
+
+/// Encodes alive cells into Life 1.05 as one block placed at *pos*
+///
+/// *cells* are `(column, row)` offsets from the top left corner of the pattern sorted by row and
+/// then by column, as for [rle::encode()](super::rle::encode())
+pub fn encode_105(
+    cells: &[(u64, u64)],
+    y_size: u64,
+    rule: &Rule,
+    pos: Option<(i64, i64)>,
+) -> String {
+    let mut out = String::from(HEADER_105);
+    out.push('\n');
+    if *rule == Rule::conway() {
+        out += "#N\n";
+    } else {
+        let counts = |check: &dyn Fn(u8) -> bool| -> String {
+            (0..=8)
+                .filter(|count| check(*count))
+                .map(|count| count.to_string())
+                .collect()
+        };
+        out += &format!(
+            "#R {}/{}\n",
+            counts(&|count| rule.survives(count)),
+            counts(&|count| rule.born(count))
+        );
+    }
+    let (x, y) = pos.unwrap_or((0, 0));
+    out += &format!("#P {} {}\n", x, y);
+
+    let mut cells = cells.iter().peekable();
+    for row in 0..y_size {
+        let mut x = 0;
+        let mut line = String::new();
+        while let Some((col, _)) = cells.next_if(|(_, cell_row)| *cell_row == row) {
+            line.extend(std::iter::repeat_n('.', (col - x) as usize));
+            line.push('*');
+            x = col + 1;
+        }
+        //empty rows would be skipped by some readers
+        if line.is_empty() {
+            line.push('.');
+        }
+        out += &line;
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::block::Block;
+
+    #[test]
+    fn life_106() {
+        let pattern = parse_106("#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n").unwrap();
+        assert_eq!(pattern.position, Some((-1, -1)));
+        assert_eq!(
+            pattern.block,
+            Block::rle_import("x = 3, y = 3\nbo$2bo$3o!").unwrap()
+        );
+
+        let cells = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        assert_eq!(
+            encode_106(&cells, Some((-1, -1))),
+            "#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n"
+        );
+        assert_eq!(parse_106("#Life 1.06\n1 2 3\n").unwrap_err().line, 2);
+
+        //bounding boxes wider than u32 or even i64 are errors
+        for far in ["5000000000 0", "9223372036854775807 0"] {
+            let text = format!("#Life 1.06\n-9223372036854775808 0\n{}\n", far);
+            let err = parse_106(&text).unwrap_err();
+            assert_eq!(err.format, PatternFormat::Life106);
+            assert!(err.reason.contains("too large"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn life_105() {
+        let text = "#Life 1.05\n#D Two gliders\n#R 23/36\n#P -1 -1\n.*\n..*\n***\n#P 10 0\n*\n";
+        let pattern = parse_105(text).unwrap();
+        assert_eq!(pattern.rule, Some("B36/S23".parse().unwrap()));
+        assert_eq!(pattern.comments, ["Two gliders"]);
+        assert_eq!(pattern.position, Some((-1, -1)));
+        assert_eq!((pattern.block.x_size, pattern.block.y_size), (12, 3));
+        assert_eq!(pattern.block[(11, 1)], 1);
+        assert_eq!(pattern.block.population(), 6);
+
+        let cells = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        assert_eq!(
+            encode_105(&cells, 3, &Rule::conway(), Some((-1, -1))),
+            "#Life 1.05\n#N\n#P -1 -1\n.*\n..*\n***\n"
+        );
+        let highlife = "B36/S23".parse().unwrap();
+        assert!(encode_105(&[], 1, &highlife, None).contains("#R 23/36\n"));
+    }
+}
@@ -1,6 +1,14 @@
+pub mod bitblock;
 pub mod block;
+pub mod cells;
 pub mod group;
 pub mod field;
+pub mod format;
+pub mod life;
+pub mod pool;
+pub mod rle;
+pub mod rule;
+pub mod topology;
 
 /// Used as a global coordinates (or offsets) of the playing field
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -0,0 +1,446 @@
+//! Contains [ThreadPool] of worker threads stepping groups in parallel
+use super::{block::Block, group::Group, rule::Rule};
+use crate::Error;
+use crossbeam_channel as channel;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Smallest weight (cells of the block) of a group stepped in tiles
+const MIN_TILED_WEIGHT: u64 = 1 << 16;
+/// Smallest number of rows in a tile
+const MIN_TILE_ROWS: u32 = 32;
+
+/// Channel a worker replies to
+type Reply = channel::Sender<Result<Work, Error>>;
+
+/// Message sent to workers
+enum Job {
+    /// Steps the work with the rule and sends it back
+    Step(Rule, Work, Reply),
+    /// Stops the worker that receives it
+    Stop,
+}
+
+/// Part of a generation stepped by one worker, sent back stepped
+enum Work {
+    Groups(Vec<Group>),
+    Tile(Tile),
+}
+
+impl Work {
+    fn step(self, rule: &Rule) -> Work {
+        match self {
+            Work::Groups(groups) => Work::Groups(step_groups(rule, groups)),
+            Work::Tile(mut tile) => {
+                tile.block.step(rule);
+                Work::Tile(tile)
+            }
+        }
+    }
+}
+
+/// Horizontal strip of a large group stepped separately. Its block has one extra row (halo)
+/// above and below when the group has them, so the inner rows are stepped correctly
+struct Tile {
+    /// Index of the tiled group
+    group: usize,
+    /// First inner row in the group block
+    at: u32,
+    /// First inner row in the tile block
+    from: u32,
+    /// Inner row after the last one in the tile block
+    to: u32,
+    block: Block,
+}
+
+/// Work needed to step the group
+fn weight(group: &Group) -> u64 {
+    group.block.x_size as u64 * group.block.y_size as u64
+}
+
+/// Threads of a [ThreadPool]
+struct Workers {
+    /// Number of workers not asked to stop
+    size: usize,
+    /// Handles of all started threads, including stopping ones
+    handles: Vec<JoinHandle<()>>,
+    /// Number of [ThreadPool::step()] calls in progress
+    stepping: usize,
+}
+
+/// Marks a [ThreadPool::step()] in progress until dropped
+struct Stepping<'a> {
+    pool: &'a ThreadPool,
+}
+
+impl Drop for Stepping<'_> {
+    fn drop(&mut self) {
+        self.pool.workers().stepping -= 1;
+        self.pool.idle.notify_all();
+    }
+}
+
+/// Pool of worker threads stepping groups. All workers take jobs from one queue and reply to the
+/// caller through its own channel, so a pool can be shared by several
+/// [Field](super::field::Field)s stepping at the same time (e.g. behind an [Arc](std::sync::Arc))
+///
+/// Dropping the pool stops and joins all its threads
+pub struct ThreadPool {
+    jobs: channel::Sender<Job>,
+    queue: channel::Receiver<Job>,
+    workers: Mutex<Workers>,
+    /// Signalled when a step finishes
+    idle: Condvar,
+}
+
+impl ThreadPool {
+    /// Creates a pool of *threads* workers. A pool of no workers steps groups in the calling thread
+    pub fn new(threads: usize) -> ThreadPool {
+        let (jobs, queue) = channel::unbounded();
+        let pool = ThreadPool {
+            jobs,
+            queue,
+            workers: Mutex::new(Workers {
+                size: 0,
+                handles: Vec::new(),
+                stepping: 0,
+            }),
+            idle: Condvar::new(),
+        };
+        pool.resize(threads);
+        pool
+    }
+
+    /// Creates a pool of at most *threads* workers, limited by [thread::available_parallelism()]
+    pub fn with_limit(threads: usize) -> ThreadPool {
+        let available = thread::available_parallelism().map_or(1, |val| val.get());
+        ThreadPool::new(std::cmp::min(threads, available))
+    }
+
+    /// Returns number of workers
+    pub fn size(&self) -> usize {
+        self.workers().size
+    }
+
+    /// Starts or stops workers so that there are *threads* of them. Stopped workers finish their
+    /// current job first. Workers are stopped only when no [ThreadPool::step()] is in progress, so
+    /// this waits for steps of other fields sharing the pool
+    pub fn resize(&self, threads: usize) {
+        let mut workers = self.workers();
+        //jobs queued by a step would be left with nobody to take them
+        while threads < workers.size && workers.stepping > 0 {
+            workers = self
+                .idle
+                .wait(workers)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+        //threads stopped by earlier calls
+        workers.handles.retain(|handle| !handle.is_finished());
+        while workers.size < threads {
+            let queue = self.queue.clone();
+            workers.handles.push(thread::spawn(move || worker(queue)));
+            workers.size += 1;
+        }
+        while workers.size > threads {
+            //cannot fail, the pool holds a receiver
+            let _ = self.jobs.send(Job::Stop);
+            workers.size -= 1;
+        }
+    }
+
+    /// Steps *groups* with *rule* using all workers. Returns all groups they produced
+    ///
+    /// Work is split by the number of cells of the blocks: groups are packed into one job per
+    /// worker of about the same weight, and groups heavier than that are cut into tiles stepped
+    /// by different workers and stitched back. Groups with cached phases are never tiled. A pool of
+    /// one worker steps the groups in the calling thread, which would only wait for the worker
+    ///
+    /// Returns [Error::WorkerPanicked] when stepping some group panicked and [Error::WorkerDied]
+    /// when a worker stopped without replying. Groups of the failed job are lost in both cases
+    pub fn step(&self, rule: Rule, groups: Vec<Group>) -> Result<Vec<Group>, Error> {
+        let (_stepping, size) = self.start_step();
+        if size == 0 {
+            return Ok(step_groups(&rule, groups));
+        }
+        if size == 1 {
+            return match run(&rule, Work::Groups(groups))? {
+                Work::Groups(val) => Ok(val),
+                Work::Tile(_) => unreachable!(),
+            };
+        }
+
+        let share = groups.iter().map(weight).sum::<u64>() / size as u64;
+        let (mut tiled, mut rest): (Vec<Group>, Vec<Group>) =
+            groups.into_iter().partition(|group| {
+                size > 1
+                    && weight(group) > share
+                    && weight(group) >= MIN_TILED_WEIGHT
+                    && group.block.y_size >= 2 * MIN_TILE_ROWS
+                    && group.period().is_none()
+            });
+        tiled = tiled.into_iter().filter_map(expanded).collect();
+
+        let (reply, results) = channel::unbounded();
+        let mut sent = 0;
+        //cannot fail, the pool holds a receiver
+        for (index, group) in tiled.iter().enumerate() {
+            for tile in tiles(index, &group.block, size as u32) {
+                let _ = self
+                    .jobs
+                    .send(Job::Step(rule, Work::Tile(tile), reply.clone()));
+                sent += 1;
+            }
+        }
+
+        //heaviest groups first, each to the lightest job
+        rest.sort_by_key(|group| std::cmp::Reverse(weight(group)));
+        let mut jobs: Vec<(u64, Vec<Group>)> = (0..size).map(|_| (0, Vec::new())).collect();
+        for group in rest {
+            let job = jobs.iter_mut().min_by_key(|job| job.0).unwrap();
+            job.0 += weight(&group);
+            job.1.push(group);
+        }
+        for (_, groups) in jobs.into_iter().filter(|job| !job.1.is_empty()) {
+            let _ = self
+                .jobs
+                .send(Job::Step(rule, Work::Groups(groups), reply.clone()));
+            sent += 1;
+        }
+        drop(reply);
+
+        let mut stepped = Vec::new();
+        let mut failure = None;
+        for _ in 0..sent {
+            match results.recv() {
+                Ok(Ok(Work::Groups(mut val))) => stepped.append(&mut val),
+                Ok(Ok(Work::Tile(tile))) => {
+                    tiled[tile.group]
+                        .block
+                        .set_rows(tile.at, &tile.block, tile.from, tile.to)
+                }
+                Ok(Err(err)) => failure = failure.or(Some(err)),
+                Err(_) => return Err(Error::WorkerDied),
+            }
+        }
+        if let Some(err) = failure {
+            return Err(err);
+        }
+        for group in tiled {
+            if let Some(mut val) = group.split_stepped(&rule) {
+                stepped.append(&mut val);
+            }
+        }
+        Ok(stepped)
+    }
+
+    /// Marks a step in progress until the returned guard is dropped. Returns the guard and the
+    /// number of workers
+    fn start_step(&self) -> (Stepping<'_>, usize) {
+        let mut workers = self.workers();
+        workers.stepping += 1;
+        (Stepping { pool: self }, workers.size)
+    }
+
+    /// Locks the workers. A panic while holding the lock leaves them consistent, so poisoning is
+    /// ignored
+    fn workers(&self) -> std::sync::MutexGuard<'_, Workers> {
+        self.workers.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        let mut workers = self.workers();
+        for _ in 0..workers.size {
+            let _ = self.jobs.send(Job::Stop);
+        }
+        workers.size = 0;
+        for handle in workers.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Steps every group, dropping the vanished ones
+fn step_groups(rule: &Rule, groups: Vec<Group>) -> Vec<Group> {
+    let mut result = Vec::new();
+    for elem in groups {
+        if let Some(mut val) = elem.step(rule) {
+            result.append(&mut val);
+        }
+    }
+    result
+}
+
+/// Adds empty borders to the group as [Group::step()] does. [None] if it has no alive cells
+fn expanded(mut group: Group) -> Option<Group> {
+    if group.block.need_expand() {
+        group.global_coord = group.global_coord + group.block.resize()?;
+    }
+    Some(group)
+}
+
+/// Cuts *block* of group *index* into at most *count* tiles
+fn tiles(index: usize, block: &Block, count: u32) -> Vec<Tile> {
+    let count = std::cmp::min(count, block.y_size / MIN_TILE_ROWS);
+    let rows = block.y_size.div_ceil(count);
+    (0..block.y_size)
+        .step_by(rows as usize)
+        .map(|at| {
+            let start = at.saturating_sub(1);
+            let end = std::cmp::min(at + rows + 1, block.y_size);
+            Tile {
+                group: index,
+                at,
+                from: at - start,
+                to: std::cmp::min(at + rows, block.y_size) - start,
+                block: block.rows(start, end),
+            }
+        })
+        .collect()
+}
+
+/// Steps the work, returning a panic while stepping as [Error::WorkerPanicked]
+fn run(rule: &Rule, work: Work) -> Result<Work, Error> {
+    panic::catch_unwind(AssertUnwindSafe(|| work.step(rule))).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|val| val.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Error::WorkerPanicked(message)
+    })
+}
+
+/// Main loop of a worker thread. Panics while stepping are sent back as errors and the worker
+/// goes on
+fn worker(queue: channel::Receiver<Job>) {
+    while let Ok(Job::Step(rule, work, reply)) = queue.recv() {
+        //the caller may have given up on the reply already
+        let _ = reply.send(run(&rule, work));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::{block::Block, rle, Coord};
+
+    #[test]
+    fn pool_step() {
+        let blinker = |x| {
+            Group::new(
+                Coord { x, y: 0 },
+                rle::parse("x = 3, y = 1\n3o!").unwrap().block,
+            )
+        };
+        let pool = ThreadPool::new(3);
+        assert_eq!(pool.size(), 3);
+        let stepped = pool
+            .step(Rule::conway(), (0..10).map(|i| blinker(i * 10)).collect())
+            .unwrap();
+        assert_eq!(stepped.len(), 10);
+        assert!(stepped.iter().all(|group| group.block.population() == 3));
+
+        pool.resize(1);
+        assert_eq!(pool.size(), 1);
+        pool.resize(2);
+        assert_eq!(
+            pool.step(Rule::conway(), vec![blinker(0)]).unwrap().len(),
+            1
+        );
+
+        //block larger than its cells panics on indexing
+        let mut block = Block::new(3, 3);
+        block.x_size = 300;
+        let broken = Group::new(Coord { x: 0, y: 0 }, block);
+        assert!(matches!(
+            pool.step(Rule::conway(), vec![broken.clone()]),
+            Err(Error::WorkerPanicked(_))
+        ));
+        assert_eq!(
+            pool.step(Rule::conway(), vec![blinker(0)]).unwrap().len(),
+            1
+        );
+        assert_eq!(pool.size(), 2);
+
+        pool.resize(1);
+        assert!(matches!(
+            pool.step(Rule::conway(), vec![broken]),
+            Err(Error::WorkerPanicked(_))
+        ));
+
+        //workers are not stopped under a step in progress
+        let pool = std::sync::Arc::new(pool);
+        let stepping = pool.start_step();
+        let resizing = {
+            let pool = pool.clone();
+            thread::spawn(move || pool.resize(0))
+        };
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!resizing.is_finished());
+        assert_eq!(pool.size(), 1);
+        drop(stepping);
+        resizing.join().unwrap();
+        assert_eq!(pool.size(), 0);
+    }
+
+    #[test]
+    fn pool_tiles() {
+        //pseudo random soup big enough to be tiled
+        let mut seed: u32 = 12345;
+        let cells: Vec<u8> = (0..300 * 300)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8 & 1
+            })
+            .collect();
+        let soup = Block::from_cells(300, 300, &cells);
+        let groups = vec![
+            Group::new(Coord { x: -150, y: 20 }, soup),
+            Group::new(
+                Coord { x: 400, y: 0 },
+                rle::parse("x = 3, y = 1\n3o!").unwrap().block,
+            ),
+        ];
+        let cells = |groups: Vec<Group>| {
+            let mut cells: Vec<Coord> = groups.iter().flat_map(Group::alive_cells).collect();
+            cells.sort();
+            cells
+        };
+
+        let block = &groups[0].block;
+        let tiles = tiles(0, block, 4);
+        assert_eq!(tiles.len(), 4);
+        assert_eq!(
+            tiles.iter().map(|tile| tile.to - tile.from).sum::<u32>(),
+            300
+        );
+
+        let expected = step_groups(&Rule::conway(), groups.clone());
+        let stepped = ThreadPool::new(4).step(Rule::conway(), groups).unwrap();
+        assert_eq!(cells(stepped.clone()), cells(expected.clone()));
+        //tiled groups are split the same way as stepped ones
+        let sorted = |mut groups: Vec<Group>| {
+            groups.sort();
+            groups
+        };
+        assert_eq!(sorted(stepped), sorted(expected));
+
+        //lattice of blocks is a still life big enough to be tiled, it is found once old enough
+        let cells: Vec<u8> = (0..264 * 264)
+            .map(|i| (i % 264 % 3 != 2 && i / 264 % 3 != 2) as u8)
+            .collect();
+        let mut groups = vec![Group::new(
+            Coord { x: 0, y: 0 },
+            Block::from_cells(264, 264, &cells),
+        )];
+        let pool = ThreadPool::new(2);
+        for _ in 0..32 {
+            groups = pool.step(Rule::conway(), groups).unwrap();
+        }
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].period(), Some(1));
+    }
+}
@@ -0,0 +1,530 @@
+//! Contains RLE parser and encoding shared by [Block], [Group](super::group::Group) and
+//! [Field](super::field::Field) exporters
+use super::block::Block;
+use super::format::{self, FormatError, PatternFormat};
+use super::rule::Rule;
+use super::topology::Topology;
+use super::Coord;
+
+/// Maximal length of a line in RLE output
+const LINE_WIDTH: usize = 70;
+
+/// Encodes alive cells into RLE pattern text
+///
+/// *cells* are `(column, row)` offsets from the top left corner of the pattern, with rows going
+/// down as in RLE. They must be sorted by row and then by column. *pos* is the global position of
+/// the top left corner written to a `#CXRLE Pos=` line
+pub fn encode(
+    cells: &[(u64, u64)],
+    x_size: u64,
+    y_size: u64,
+    rule: &Rule,
+    pos: Option<(i64, i64)>,
+) -> String {
+    let mut writer = RleWriter {
+        out: String::new(),
+        line_len: 0,
+    };
+    if let Some((x, y)) = pos {
+        writer.out += &format!("#CXRLE Pos={},{}\n", x, y);
+    }
+    writer.out += &format!("x = {}, y = {}, rule = {}\n", x_size, y_size, rule);
+
+    let mut cur_row = 0;
+    let mut cur_col = 0;
+    //start and length of the pending run of alive cells
+    let mut run: Option<(u64, u64)> = None;
+
+    for (col, row) in cells {
+        if *row != cur_row {
+            writer.flush_alive(&mut run, &mut cur_col);
+            writer.push_run(row - cur_row, '$');
+            cur_row = *row;
+            cur_col = 0;
+        }
+        match run {
+            Some((start, len)) if start + len == *col => run = Some((start, len + 1)),
+            _ => {
+                writer.flush_alive(&mut run, &mut cur_col);
+                writer.push_run(col - cur_col, 'b');
+                run = Some((*col, 1));
+            }
+        }
+    }
+    writer.flush_alive(&mut run, &mut cur_col);
+    writer.push_run(1, '!');
+    writer.out.push('\n');
+    writer.out
+}
+
+/// Encodes alive cells given in global [Field](super::field::Field) coordinates into RLE with a
+/// tight bounding box
+///
+/// Field y axis goes up, so the topmost row is written first and the `#CXRLE Pos=` offset is
+/// given with y negated
+pub fn encode_global(cells: &[Coord], rule: &Rule) -> String {
+    format::encode_global(cells, rule, PatternFormat::Rle)
+}
+
+/// Error returned when RLE text cannot be parsed. Lines and columns are counted from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RleError {
+    pub line: usize,
+    pub column: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for RleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RLE parse error at line {}, column {}: {}",
+            self.line, self.column, self.reason
+        )
+    }
+}
+
+impl std::error::Error for RleError {}
+
+/// Pattern read from RLE text together with its metadata
+#[derive(Debug, PartialEq, Eq)]
+pub struct RlePattern {
+    /// Pattern cells. Row 0 is the first row of RLE body
+    pub block: Block,
+    /// Rule from the header, [None] if it was not specified
+    pub rule: Option<Rule>,
+    /// Bounded grid from the rule suffix (e.g. `B3/S23:T100,100`), [None] if it was not specified
+    pub topology: Option<Topology>,
+    /// Name from `#N` line
+    pub name: Option<String>,
+    /// Author from `#O` line
+    pub author: Option<String>,
+    /// Contents of `#C` and `#c` lines
+    pub comments: Vec<String>,
+    /// Position of the top left corner from `#P`, `#R` or `#CXRLE Pos=` lines
+    pub position: Option<(i64, i64)>,
+}
+
+impl Default for RlePattern {
+    /// Empty pattern without metadata
+    fn default() -> Self {
+        RlePattern {
+            block: Block::new(0, 0),
+            rule: None,
+            topology: None,
+            name: None,
+            author: None,
+            comments: Vec::new(),
+            position: None,
+        }
+    }
+}
+
+impl RlePattern {
+    /// Builds a pattern without metadata holding alive *cells* given as `(x, y)` with y axis going
+    /// down. Position of the pattern is the top left corner of their bounding box
+    ///
+    /// Returns [FormatError] of *format* if the bounding box is too large for one [Block]
+    pub fn from_cells(
+        cells: &[(i64, i64)],
+        format: PatternFormat,
+    ) -> Result<RlePattern, FormatError> {
+        let mut pattern = RlePattern::default();
+        if cells.is_empty() {
+            return Ok(pattern);
+        }
+        let x_min = cells.iter().map(|cell| cell.0).min().unwrap();
+        let x_max = cells.iter().map(|cell| cell.0).max().unwrap();
+        let y_min = cells.iter().map(|cell| cell.1).min().unwrap();
+        let y_max = cells.iter().map(|cell| cell.1).max().unwrap();
+
+        //extent of i64 coordinates may not fit into i64
+        let extent = |min: i64, max: i64| max.abs_diff(min).saturating_add(1);
+        let mut block = Block::try_new(extent(x_min, x_max), extent(y_min, y_max))
+            .map_err(|err| FormatError::too_large(format, err))?;
+        for (x, y) in cells {
+            block[(x.abs_diff(x_min) as u32, y.abs_diff(y_min) as u32)] = 1;
+        }
+        pattern.block = block;
+        pattern.position = Some((x_min, y_min));
+        Ok(pattern)
+    }
+}
+
+/// Parses RLE text into [RlePattern]
+///
+/// Body may span any number of lines. Digits before `b`, `o` and `$` are run counts, everything
+/// after `!` is ignored
+pub fn parse(text: &str) -> Result<RlePattern, RleError> {
+    let mut pattern = RlePattern::default();
+    let mut header = false;
+    let mut x: u32 = 0;
+    let mut y: u32 = 0;
+    let mut run: Option<u32> = None;
+    let mut run_column = 0;
+
+    for (line_index, line) in text.lines().enumerate() {
+        let line_number = line_index + 1;
+        let error = |column: usize, reason: &str| RleError {
+            line: line_number,
+            column,
+            reason: reason.to_string(),
+        };
+
+        if let Some(comment) = line.strip_prefix('#') {
+            parse_comment(comment, &mut pattern).map_err(|reason| error(1, reason))?;
+            continue;
+        }
+
+        if !header {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (x_size, y_size, rule, topology) =
+                parse_header(line).map_err(|(column, reason)| error(column, &reason))?;
+            pattern.block = Block::try_new(x_size as u64, y_size as u64)
+                .map_err(|err| error(1, &err.to_string()))?;
+            pattern.rule = rule;
+            pattern.topology = topology;
+            header = true;
+            continue;
+        }
+
+        for (column_index, c) in line.chars().enumerate() {
+            let column = column_index + 1;
+            match c {
+                '0'..='9' => {
+                    if run.is_none() {
+                        run_column = column;
+                    }
+                    let digit = c.to_digit(10).unwrap();
+                    run = Some(
+                        run.unwrap_or(0)
+                            .checked_mul(10)
+                            .and_then(|val| val.checked_add(digit))
+                            .ok_or_else(|| error(run_column, "run count is too big"))?,
+                    );
+                }
+                'b' | '.' => {
+                    x = x
+                        .checked_add(run.take().unwrap_or(1))
+                        .filter(|val| *val <= pattern.block.x_size)
+                        .ok_or_else(|| error(column, "pattern exceeds size given in header"))?;
+                }
+                'o' | 'A' => {
+                    let count = run.take().unwrap_or(1);
+                    let end = x
+                        .checked_add(count)
+                        .filter(|val| *val <= pattern.block.x_size);
+                    if end.is_none() || y >= pattern.block.y_size {
+                        return Err(error(column, "pattern exceeds size given in header"));
+                    }
+                    for _ in 0..count {
+                        pattern.block[(x, y)] = 1;
+                        x += 1;
+                    }
+                }
+                '$' => {
+                    y = y
+                        .checked_add(run.take().unwrap_or(1))
+                        .filter(|val| *val <= pattern.block.y_size)
+                        .ok_or_else(|| error(column, "pattern exceeds size given in header"))?;
+                    x = 0;
+                }
+                '!' => {
+                    if run.is_some() {
+                        return Err(error(column, "run count before '!'"));
+                    }
+                    return Ok(pattern);
+                }
+                c if c.is_whitespace() => {
+                    if run.is_some() {
+                        return Err(error(column, "whitespace inside a run"));
+                    }
+                }
+                _ => return Err(error(column, &format!("unexpected character '{}'", c))),
+            }
+        }
+    }
+
+    let line = text.lines().count();
+    match header {
+        false => Err(RleError {
+            line,
+            column: 1,
+            reason: "missing header line".to_string(),
+        }),
+        true => Err(RleError {
+            line,
+            column: text.lines().last().map_or(0, |l| l.chars().count()) + 1,
+            reason: "missing '!' at the end of pattern".to_string(),
+        }),
+    }
+}
+
+/// Parses a comment line (without leading `#`) into pattern metadata
+fn parse_comment(comment: &str, pattern: &mut RlePattern) -> Result<(), &'static str> {
+    let mut chars = comment.chars();
+    let kind = chars.next();
+    let text = chars.as_str().trim();
+    match kind {
+        Some('N') => pattern.name = Some(text.to_string()),
+        Some('O') => pattern.author = Some(text.to_string()),
+        Some('C' | 'c') => {
+            if let Some(pos) = text.strip_prefix("XRLE") {
+                for field in pos.split_whitespace() {
+                    if let Some(val) = field.strip_prefix("Pos=") {
+                        pattern.position = Some(parse_position(val.split(','))?);
+                    }
+                }
+            } else {
+                pattern.comments.push(text.to_string());
+            }
+        }
+        Some('P' | 'R') => pattern.position = Some(parse_position(text.split_whitespace())?),
+        _ => (),
+    }
+    Ok(())
+}
+
+/// Parses two integers into a position
+fn parse_position<'a>(
+    mut parts: impl Iterator<Item = &'a str>,
+) -> Result<(i64, i64), &'static str> {
+    let mut next = || {
+        parts
+            .next()
+            .and_then(|val| val.trim().parse().ok())
+            .ok_or("invalid pattern position")
+    };
+    Ok((next()?, next()?))
+}
+
+/// Parses `x = m, y = n, rule = abc` header. Returns column of the error on failure
+#[allow(clippy::type_complexity)]
+fn parse_header(line: &str) -> Result<(u32, u32, Option<Rule>, Option<Topology>), (usize, String)> {
+    let mut x_size = None;
+    let mut y_size = None;
+    let mut rule = None;
+    let mut topology = None;
+    let mut column = 1;
+
+    //bounded grid in the rule has its own comma, so pieces without '=' continue previous item
+    let mut items: Vec<(usize, &str)> = Vec::new();
+    for piece in line.split(',') {
+        match items.last_mut() {
+            Some((start, item)) if !piece.contains('=') => {
+                *item = &line[*start - 1..*start - 1 + item.len() + 1 + piece.len()]
+            }
+            _ => items.push((column, piece)),
+        }
+        column += piece.len() + 1;
+    }
+
+    for (start, item) in items {
+        let item_column = start + item.len() - item.trim_start().len();
+        let (key, val) = item
+            .split_once('=')
+            .ok_or_else(|| (item_column, "expected 'key = value'".to_string()))?;
+        let val = val.trim();
+        match key.trim() {
+            "x" => x_size = Some(parse_size(val, item_column)?),
+            "y" => y_size = Some(parse_size(val, item_column)?),
+            "rule" => {
+                //Golly appends bounded grid specification after ':'
+                let (name, grid) = match val.split_once(':') {
+                    Some((name, grid)) => (name, Some(grid)),
+                    None => (val, None),
+                };
+                rule = Some(
+                    name.parse()
+                        .map_err(|err: super::rule::RuleError| (item_column, err.to_string()))?,
+                );
+                if let Some(grid) = grid {
+                    topology = Some(grid.parse().map_err(
+                        |err: super::topology::TopologyError| (item_column, err.to_string()),
+                    )?);
+                }
+            }
+            _ => return Err((item_column, format!("unknown header key '{}'", key.trim()))),
+        }
+    }
+
+    match (x_size, y_size) {
+        (Some(x), Some(y)) => Ok((x, y, rule, topology)),
+        _ => Err((1, "header must specify both x and y".to_string())),
+    }
+}
+
+/// Parses pattern size from the header
+fn parse_size(val: &str, column: usize) -> Result<u32, (usize, String)> {
+    val.parse()
+        .map_err(|_| (column, format!("invalid pattern size '{}'", val)))
+}
+
+/// Accumulates RLE body wrapping it into lines of [LINE_WIDTH]
+struct RleWriter {
+    out: String,
+    line_len: usize,
+}
+
+impl RleWriter {
+    /// Appends *count* cells with *tag*. Runs are never split between lines
+    fn push_run(&mut self, count: u64, tag: char) {
+        let item = match count {
+            0 => return,
+            1 => tag.to_string(),
+            _ => format!("{}{}", count, tag),
+        };
+        if self.line_len + item.len() > LINE_WIDTH {
+            self.out.push('\n');
+            self.line_len = 0;
+        }
+        self.line_len += item.len();
+        self.out += &item;
+    }
+
+    /// Writes pending run of alive cells and moves current column past it
+    fn flush_alive(&mut self, run: &mut Option<(u64, u64)>, cur_col: &mut u64) {
+        if let Some((start, len)) = run.take() {
+            self.push_run(len, 'o');
+            *cur_col = start + len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rle_encode() {
+        //glider
+        let cells = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        assert_eq!(
+            encode(&cells, 3, 3, &Rule::conway(), None),
+            "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+        );
+
+        let cells = [(0, 0), (5, 3)];
+        assert_eq!(
+            encode(&cells, 6, 4, &Rule::conway(), Some((-1, 2))),
+            "#CXRLE Pos=-1,2\nx = 6, y = 4, rule = B3/S23\no3$5bo!\n"
+        );
+    }
+
+    #[test]
+    fn rle_encode_global() {
+        let cells = [Coord { x: 4, y: -1 }, Coord { x: 2, y: 1 }];
+        assert_eq!(
+            encode_global(&cells, &Rule::conway()),
+            "#CXRLE Pos=2,-1\nx = 3, y = 3, rule = B3/S23\no2$2bo!\n"
+        );
+        assert_eq!(
+            encode_global(&[], &Rule::conway()),
+            "x = 0, y = 0, rule = B3/S23\n!\n"
+        );
+    }
+
+    #[test]
+    fn rle_parse() {
+        let text = "#N Glider pair\n#O Someone\n#C First comment\n#CXRLE Pos=-3,4\n\
+                    x=5,y = 6,  rule = b36/s23\n\
+                    bo$2bo$3o\n\
+                    3$3b2o!trailing text";
+        let pattern = parse(text).unwrap();
+        let mut block = Block::new(5, 6);
+        block[(1, 0)] = 1;
+        block[(2, 1)] = 1;
+        block[(0, 2)] = 1;
+        block[(1, 2)] = 1;
+        block[(2, 2)] = 1;
+        block[(3, 5)] = 1;
+        block[(4, 5)] = 1;
+
+        assert_eq!(pattern.block, block);
+        assert_eq!(pattern.rule, Some("B36/S23".parse().unwrap()));
+        assert_eq!(pattern.name.as_deref(), Some("Glider pair"));
+        assert_eq!(pattern.author.as_deref(), Some("Someone"));
+        assert_eq!(pattern.comments, vec!["First comment".to_string()]);
+        assert_eq!(pattern.position, Some((-3, 4)));
+
+        let pattern = parse("#P 1 -2\nx = 1, y = 1\no!").unwrap();
+        assert_eq!(pattern.position, Some((1, -2)));
+        assert_eq!(pattern.rule, None);
+        assert_eq!(pattern.topology, None);
+
+        let pattern = parse("x = 1, y = 1, rule = B3/S23:T20,10\no!").unwrap();
+        assert_eq!(pattern.rule, Some(Rule::conway()));
+        assert_eq!(
+            pattern.topology,
+            Some(Topology::Torus {
+                width: 20,
+                height: 10
+            })
+        );
+    }
+
+    #[test]
+    fn rle_parse_errors() {
+        let error = parse("x = 3, y = 3\nbo$2bo$3o!").map(|_| ());
+        assert_eq!(error, Ok(()));
+
+        let error = parse("x = 3, y = 3\nbo$2bo$\n3oz!").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 3));
+
+        let error = parse("x = 3, y = 3\nbo$2bo$4o!").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 9));
+
+        let error = parse("x = 3, y = q").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 8));
+
+        let error = parse("x = 3, y = 3, rule = B3").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 15));
+
+        let error = parse("x = 3, y = 3, rule = B3/S23:S10").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 15));
+
+        let error = parse("#C just a comment\n").unwrap_err();
+        assert_eq!(error.reason, "missing header line");
+
+        let error = parse("x = 3, y = 3\nbo$\n2bo").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 4));
+
+        let error = parse("x = 70000, y = 70000\no!").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 1));
+
+        //runs of dead cells and rows that would overflow or leave the block
+        let error = parse("x = 3, y = 3\n4294967295b4294967295b!").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 11));
+        let error = parse("x = 3, y = 3\n3bo!").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+        let error = parse("x = 3, y = 1\no2$o!").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 3));
+        assert!(parse("x = 3, y = 1\n3b$!").is_ok());
+    }
+
+    #[test]
+    fn rle_roundtrip() {
+        let cells = [
+            Coord { x: -7, y: 3 },
+            Coord { x: 100, y: 3 },
+            Coord { x: 0, y: -50 },
+        ];
+        let pattern = parse(&encode_global(&cells, &Rule::conway())).unwrap();
+        assert_eq!(pattern.position, Some((-7, -3)));
+        assert_eq!((pattern.block.x_size, pattern.block.y_size), (108, 54));
+        assert_eq!(pattern.block[(0, 0)], 1);
+        assert_eq!(pattern.block[(107, 0)], 1);
+        assert_eq!(pattern.block[(7, 53)], 1);
+    }
+
+    #[test]
+    fn rle_encode_wrap() {
+        let cells: Vec<(u64, u64)> = (0..100).map(|x| (x * 2, 0)).collect();
+        let rle = encode(&cells, 199, 1, &Rule::conway(), None);
+        let body: Vec<&str> = rle.lines().skip(1).collect();
+        assert!(body.iter().all(|line| line.len() <= LINE_WIDTH));
+        assert_eq!(body.concat(), "ob".repeat(99) + "o!");
+    }
+}
@@ -0,0 +1,197 @@
+//! Contains [Rule] struct describing outer-totalistic Life-like rules
+use std::str::FromStr;
+
+/// An outer-totalistic rule in B/S notation (e.g. `B3/S23` for Conway's Life)
+///
+/// Both sets are stored as bitmasks indexed by the Moore neighbourhood count (0..=8)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rule {
+    birth: u16,
+    survival: u16,
+}
+
+/// Error returned when a rulestring cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    pub rule: String,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid rule \"{}\": {}", self.rule, self.reason)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl Default for Rule {
+    /// Conway's "Game of Life" (B3/S23)
+    fn default() -> Self {
+        Rule::conway()
+    }
+}
+
+impl Rule {
+    /// Creates a rule from lists of neighbour counts that cause birth and survival
+    ///
+    /// # Panics
+    /// When any count is greater than 8
+    pub fn new(birth: &[u8], survival: &[u8]) -> Rule {
+        let mut rule = Rule {
+            birth: 0,
+            survival: 0,
+        };
+        for count in birth {
+            assert!(*count <= 8, "Neighbour count is out of range");
+            rule.birth |= 1 << count;
+        }
+        for count in survival {
+            assert!(*count <= 8, "Neighbour count is out of range");
+            rule.survival |= 1 << count;
+        }
+        rule
+    }
+
+    /// Conway's "Game of Life" (B3/S23)
+    pub fn conway() -> Rule {
+        Rule::new(&[3], &[2, 3])
+    }
+
+    /// Checks if a dead cell with *count* alive neighbours is born
+    pub fn born(&self, count: u8) -> bool {
+        count <= 8 && self.birth & (1 << count) != 0
+    }
+
+    /// Checks if an alive cell with *count* alive neighbours survives
+    pub fn survives(&self, count: u8) -> bool {
+        count <= 8 && self.survival & (1 << count) != 0
+    }
+
+    /// Returns the next state of a cell given its current state and alive neighbour count
+    pub fn next(&self, alive: bool, count: u8) -> bool {
+        if alive {
+            self.survives(count)
+        } else {
+            self.born(count)
+        }
+    }
+
+    /// Parses a list of digits into a bitmask of neighbour counts
+    fn parse_counts(digits: &str, rule: &str) -> Result<u16, RuleError> {
+        let mut mask = 0;
+        for c in digits.chars() {
+            match c.to_digit(10) {
+                Some(val) if val <= 8 => mask |= 1 << val,
+                _ => {
+                    return Err(RuleError {
+                        rule: rule.to_string(),
+                        reason: "neighbour counts must be digits from 0 to 8",
+                    })
+                }
+            }
+        }
+        Ok(mask)
+    }
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    /// Parses `B3/S23`, `b3s23` and the older `23/3` (survival/birth) notations
+    ///
+    /// Rules with birth on 0 neighbours are rejected, as they turn the infinite empty background
+    /// alive and cannot be represented with finite groups
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason| RuleError {
+            rule: s.to_string(),
+            reason,
+        };
+
+        let trimmed = s.trim();
+        let (first, second) = match trimmed.split_once('/') {
+            Some(val) => val,
+            //"B3S23" form used by Catagolue
+            None if trimmed.starts_with(['B', 'b']) => trimmed.split_at(
+                trimmed
+                    .find(['S', 's'])
+                    .ok_or_else(|| error("missing 'S'"))?,
+            ),
+            None => return Err(error("missing '/'")),
+        };
+
+        let (birth, survival) = match (first.chars().next(), second.chars().next()) {
+            (Some('B' | 'b'), Some('S' | 's')) => (&first[1..], &second[1..]),
+            (Some('S' | 's'), Some('B' | 'b')) => (&second[1..], &first[1..]),
+            _ => (second, first),
+        };
+
+        let rule = Rule {
+            birth: Rule::parse_counts(birth, s)?,
+            survival: Rule::parse_counts(survival, s)?,
+        };
+
+        if rule.born(0) {
+            return Err(error("B0 rules are not supported"));
+        }
+        Ok(rule)
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "B")?;
+        for count in 0..=8 {
+            if self.born(count) {
+                write!(f, "{}", count)?;
+            }
+        }
+        write!(f, "/S")?;
+        for count in 0..=8 {
+            if self.survives(count) {
+                write!(f, "{}", count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rule_parse() {
+        assert_eq!("B3/S23".parse::<Rule>().unwrap(), Rule::conway());
+        assert_eq!("b3/s23".parse::<Rule>().unwrap(), Rule::conway());
+        assert_eq!("23/3".parse::<Rule>().unwrap(), Rule::conway());
+        assert_eq!("S23/B3".parse::<Rule>().unwrap(), Rule::conway());
+        assert_eq!(
+            "B36/S23".parse::<Rule>().unwrap(),
+            Rule::new(&[3, 6], &[2, 3])
+        );
+        assert_eq!("B2/S".parse::<Rule>().unwrap(), Rule::new(&[2], &[]));
+
+        assert_eq!("b3s23".parse::<Rule>().unwrap(), Rule::conway());
+
+        assert!("323".parse::<Rule>().is_err());
+        assert!("B39/S23".parse::<Rule>().is_err());
+        assert!("B03/S23".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn rule_display() {
+        let rule: Rule = "b3678/s34678".parse().unwrap();
+        assert_eq!(rule.to_string(), "B3678/S34678");
+        assert_eq!(Rule::conway().to_string(), "B3/S23");
+    }
+
+    #[test]
+    fn rule_next() {
+        let rule = Rule::conway();
+        assert!(rule.next(false, 3));
+        assert!(!rule.next(false, 2));
+        assert!(rule.next(true, 2));
+        assert!(!rule.next(true, 4));
+    }
+}
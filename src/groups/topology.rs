@@ -0,0 +1,592 @@
+//! Contains [Topology] of the playing field - unbounded plane or a finite universe with dead or
+//! joined edges
+use super::block::Block;
+use super::group::Group;
+use super::rule::Rule;
+use super::Coord;
+
+/// Shape of the universe. Finite universes are centered at the origin the way Golly places them:
+/// x runs from `-width / 2` and y (pointing up) ends at `height / 2`.
+///
+/// As in Golly, a zero width or height makes the universe unbounded along that axis, so
+/// `T100,0` is a cylinder with joined left and right edges and `P0,50` is an infinite strip.
+///
+/// Groups of a finite universe keep their bottom left corner inside of it, but may stick out
+/// through the right and top edges of wrapped axes
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Topology {
+    /// Unbounded plane
+    #[default]
+    Plane,
+    /// Finite plane. Cells outside of it are always dead
+    Bounded { width: u32, height: u32 },
+    /// Left and right edges are joined, so are top and bottom ones. Zero side is not joined
+    Torus { width: u32, height: u32 },
+    /// Both pairs of edges are joined, one of them with a [Twist]
+    KleinBottle {
+        width: u32,
+        height: u32,
+        twist: Twist,
+    },
+}
+
+/// Pair of edges of a [Topology::KleinBottle] joined with a twist. Golly marks the side with `*`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Twist {
+    /// Top and bottom edges (`K100*,100`): crossing them mirrors x
+    Width,
+    /// Left and right edges (`K100,100*`): crossing them mirrors y
+    Height,
+}
+
+/// Error of parsing [Topology] from Golly notation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopologyError {
+    pub topology: String,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid topology \"{}\": {}", self.topology, self.reason)
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+/// Parses Golly bounded grid notation: `P40,20` (bounded plane), `T100,100` (torus), `T100,0`
+/// (cylinder), `K100*,100` or `K100,100*` (Klein bottle). Cross-surfaces (`C`) and spheres (`S`)
+/// are not supported
+impl std::str::FromStr for Topology {
+    type Err = TopologyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason| TopologyError {
+            topology: s.to_string(),
+            reason,
+        };
+
+        let trimmed = s.trim();
+        let mut chars = trimmed.chars();
+        let kind = chars.next().ok_or_else(|| error("empty topology"))?;
+        let (width, height) = chars
+            .as_str()
+            .split_once(',')
+            .ok_or_else(|| error("expected 'width,height'"))?;
+
+        let twist = match (width.ends_with('*'), height.ends_with('*')) {
+            (false, false) => None,
+            (true, false) => Some(Twist::Width),
+            (false, true) => Some(Twist::Height),
+            (true, true) => return Err(error("only one pair of edges may have a twist")),
+        };
+        let size = |val: &str| {
+            val.trim_end_matches('*')
+                .parse::<u32>()
+                .map_err(|_| error("size must be a number"))
+        };
+        let (width, height) = (size(width)?, size(height)?);
+        if width == 0 && height == 0 {
+            return Err(error("width or height must be positive"));
+        }
+
+        match (kind.to_ascii_uppercase(), twist) {
+            ('P', None) => Ok(Topology::Bounded { width, height }),
+            ('T', None) => Ok(Topology::Torus { width, height }),
+            ('K', Some(_)) if width == 0 || height == 0 => {
+                Err(error("Klein bottle needs both sizes positive"))
+            }
+            ('K', Some(twist)) => Ok(Topology::KleinBottle {
+                width,
+                height,
+                twist,
+            }),
+            ('K', None) => Err(error("Klein bottle needs a twisted side, e.g. 'K100*,100'")),
+            ('C', _) => Err(error("cross-surface is not supported")),
+            ('S', _) => Err(error("sphere is not supported")),
+            (_, Some(_)) => Err(error("only Klein bottle may have a twist")),
+            _ => Err(error("unknown topology, expected 'P', 'T' or 'K'")),
+        }
+    }
+}
+
+/// Prints Golly notation. Unbounded plane is an empty string
+impl std::fmt::Display for Topology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Topology::Plane => Ok(()),
+            Topology::Bounded { width, height } => write!(f, "P{},{}", width, height),
+            Topology::Torus { width, height } => write!(f, "T{},{}", width, height),
+            Topology::KleinBottle {
+                width,
+                height,
+                twist: Twist::Width,
+            } => write!(f, "K{}*,{}", width, height),
+            Topology::KleinBottle {
+                width,
+                height,
+                twist: Twist::Height,
+            } => write!(f, "K{},{}*", width, height),
+        }
+    }
+}
+
+impl Topology {
+    /// Returns width and height of a finite universe. Zero side is unbounded
+    pub fn size(&self) -> Option<(i64, i64)> {
+        match *self {
+            Topology::Plane => None,
+            Topology::Bounded { width, height }
+            | Topology::Torus { width, height }
+            | Topology::KleinBottle { width, height, .. } => Some((width as i64, height as i64)),
+        }
+    }
+
+    /// Returns bottom left cell of a finite universe
+    pub fn lower(&self) -> Option<Coord> {
+        let (width, height) = self.size()?;
+        Some(Coord {
+            x: -(width / 2),
+            y: height / 2 - height + 1,
+        })
+    }
+
+    /// Checks if left and right edges are joined
+    pub fn wraps_x(&self) -> bool {
+        match *self {
+            Topology::Torus { width, .. } => width != 0,
+            Topology::KleinBottle { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Checks if top and bottom edges are joined
+    pub fn wraps_y(&self) -> bool {
+        match *self {
+            Topology::Torus { height, .. } => height != 0,
+            Topology::KleinBottle { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the pair of edges joined with a twist
+    fn twist(&self) -> Option<Twist> {
+        match *self {
+            Topology::KleinBottle { twist, .. } => Some(twist),
+            _ => None,
+        }
+    }
+
+    /// Mirrors x coordinate inside of the universe
+    fn mirror_x(&self, x: i64) -> i64 {
+        match (self.lower(), self.size()) {
+            (Some(lower), Some((width, _))) => 2 * lower.x + width - 1 - x,
+            _ => x,
+        }
+    }
+
+    /// Mirrors y coordinate inside of the universe
+    fn mirror_y(&self, y: i64) -> i64 {
+        match (self.lower(), self.size()) {
+            (Some(lower), Some((_, height))) => 2 * lower.y + height - 1 - y,
+            _ => y,
+        }
+    }
+
+    /// Maps a cell to its place inside of the universe. Returns [None] for cells behind dead
+    /// edges
+    pub fn canonical(&self, cell: Coord) -> Option<Coord> {
+        let (lower, (width, height)) = match (self.lower(), self.size()) {
+            (Some(lower), Some(size)) => (lower, size),
+            _ => return Some(cell),
+        };
+
+        let wraps_y = match height {
+            0 => 0,
+            _ => (cell.y - lower.y).div_euclid(height),
+        };
+        let mut y = match self.wraps_y() {
+            true => cell.y - wraps_y * height,
+            false if wraps_y != 0 => return None,
+            false => cell.y,
+        };
+
+        let wraps_x = match width {
+            0 => 0,
+            _ => (cell.x - lower.x).div_euclid(width),
+        };
+        let mut x = match self.wraps_x() {
+            true => cell.x - wraps_x * width,
+            false if wraps_x != 0 => return None,
+            false => cell.x,
+        };
+        match self.twist() {
+            Some(Twist::Width) if wraps_y % 2 != 0 => x = self.mirror_x(x),
+            Some(Twist::Height) if wraps_x % 2 != 0 => y = self.mirror_y(y),
+            _ => (),
+        }
+        Some(Coord { x, y })
+    }
+
+    /// Returns the copy of **group** moved *wraps_x* widths right and *wraps_y* heights up. It
+    /// represents the same cells of the universe
+    pub fn image(&self, group: &Group, wraps_x: i64, wraps_y: i64) -> Group {
+        let (width, height) = self.size().unwrap_or((0, 0));
+        let mut image = group.clone();
+        let (x_size, y_size) = (group.block.x_size, group.block.y_size);
+        match self.twist() {
+            Some(Twist::Width) if wraps_y % 2 != 0 => {
+                for y in 0..y_size {
+                    for x in 0..x_size {
+                        image.block[(x_size - 1 - x, y)] = group.block[(x, y)];
+                    }
+                }
+                image.global_coord.x = self.mirror_x(group.top_right().x);
+            }
+            Some(Twist::Height) if wraps_x % 2 != 0 => {
+                for y in 0..y_size {
+                    for x in 0..x_size {
+                        image.block[(x, y_size - 1 - y)] = group.block[(x, y)];
+                    }
+                }
+                image.global_coord.y = self.mirror_y(group.top_right().y);
+            }
+            _ => (),
+        }
+        image.global_coord = image.global_coord
+            + Coord {
+                x: wraps_x * width,
+                y: wraps_y * height,
+            };
+        image
+    }
+
+    /// Returns images of **group** across every joined edge
+    pub fn images(&self, group: &Group) -> Vec<Group> {
+        let x_range = match self.wraps_x() {
+            true => -1..=1,
+            false => 0..=0,
+        };
+        let y_range = match self.wraps_y() {
+            true => -1..=1,
+            false => 0..=0,
+        };
+
+        let mut images = Vec::new();
+        for wraps_y in y_range {
+            for wraps_x in x_range.clone() {
+                if wraps_x != 0 || wraps_y != 0 {
+                    images.push(self.image(group, wraps_x, wraps_y));
+                }
+            }
+        }
+        images
+    }
+
+    /// Checks if **group** is larger than the universe along a joined axis, so it can interact
+    /// with itself and has to be stepped by [Topology::step_folded()]
+    pub fn needs_fold(&self, group: &Group) -> bool {
+        match self.size() {
+            None => false,
+            Some((width, height)) => {
+                (self.wraps_x() && group.block.x_size as i64 > width)
+                    || (self.wraps_y() && group.block.y_size as i64 > height)
+            }
+        }
+    }
+
+    /// Removes cells behind dead edges and moves **group** so its bottom left corner is inside of
+    /// the universe. Cached phases do not know about the edges, so stability of clipped and moved
+    /// groups is reset, other groups keep it.
+    ///
+    /// Returns [None] if no alive cells remain. Otherwise returns vector of new groups
+    pub fn place(&self, mut group: Group) -> Option<Vec<Group>> {
+        let (lower, (width, height)) = match (self.lower(), self.size()) {
+            (Some(lower), Some(size)) => (lower, size),
+            _ => return Some(vec![group]),
+        };
+        if group.block.need_expand() {
+            group.global_coord = group.global_coord + group.block.resize()?;
+        }
+
+        let mut clipped = false;
+        for y in 0..group.block.y_size {
+            for x in 0..group.block.x_size {
+                let cell = group.global_coord
+                    + Coord {
+                        x: x as i64,
+                        y: y as i64,
+                    };
+                if group.block[(x, y)] == 1 && self.canonical(cell).is_none() {
+                    group.block[(x, y)] = 0;
+                    clipped = true;
+                }
+            }
+        }
+        let pieces = match clipped {
+            true => group.split()?,
+            false => vec![group],
+        };
+
+        let mut placed = Vec::new();
+        for mut piece in pieces {
+            //alive cells start right after the border
+            let mut wrapped = false;
+            if self.wraps_y() {
+                let wraps_y = (piece.global_coord.y + 1 - lower.y).div_euclid(height);
+                if wraps_y != 0 {
+                    piece = self.image(&piece, 0, -wraps_y);
+                    wrapped = true;
+                }
+            }
+            if self.wraps_x() {
+                let wraps_x = (piece.global_coord.x + 1 - lower.x).div_euclid(width);
+                if wraps_x != 0 {
+                    piece = self.image(&piece, -wraps_x, 0);
+                    wrapped = true;
+                }
+            }
+            //split pieces are reset already
+            if wrapped {
+                piece.reset_stability();
+            }
+            placed.push(piece);
+        }
+        Some(placed)
+    }
+
+    /// Advances groups that are too large for the universe to the next generation. Groups are
+    /// folded into one block spanning the whole universe along joined axes, which is stepped with
+    /// wrapped neighbours.
+    ///
+    /// Returns pieces of the next generation, not yet [placed](Topology::place())
+    pub fn step_folded(&self, groups: Vec<Group>, rule: &Rule) -> Vec<Group> {
+        let (lower, (width, height)) = match (self.lower(), self.size()) {
+            (Some(lower), Some(size)) => (lower, size),
+            _ => {
+                return groups
+                    .into_iter()
+                    .filter_map(|g| g.step(rule))
+                    .flatten()
+                    .collect()
+            }
+        };
+
+        let mut cells = Vec::new();
+        for group in &groups {
+            cells.extend(
+                group
+                    .alive_cells()
+                    .into_iter()
+                    .filter_map(|c| self.canonical(c)),
+            );
+        }
+        if cells.is_empty() {
+            return Vec::new();
+        }
+
+        //dead axes keep the extent of the cells plus one cell for births
+        let (x_min, x_size) = match self.wraps_x() {
+            true => (lower.x, width),
+            false => {
+                let min = cells.iter().map(|c| c.x).min().unwrap_or(0) - 1;
+                (min, cells.iter().map(|c| c.x).max().unwrap_or(0) + 2 - min)
+            }
+        };
+        let (y_min, y_size) = match self.wraps_y() {
+            true => (lower.y, height),
+            false => {
+                let min = cells.iter().map(|c| c.y).min().unwrap_or(0) - 1;
+                (min, cells.iter().map(|c| c.y).max().unwrap_or(0) + 2 - min)
+            }
+        };
+
+        let mut folded = Block::new(x_size as u32, y_size as u32);
+        for cell in &cells {
+            folded[((cell.x - x_min) as u32, (cell.y - y_min) as u32)] = 1;
+        }
+
+        //one more cell around holds wrapped neighbours
+        let mut padded = Block::new(x_size as u32 + 2, y_size as u32 + 2);
+        for y in 0..padded.y_size {
+            for x in 0..padded.x_size {
+                let cell = Coord {
+                    x: x_min + x as i64 - 1,
+                    y: y_min + y as i64 - 1,
+                };
+                if let Some(inner) = self.canonical(cell) {
+                    let (inner_x, inner_y) = (inner.x - x_min, inner.y - y_min);
+                    if (0..x_size).contains(&inner_x) && (0..y_size).contains(&inner_y) {
+                        padded[(x, y)] = folded[(inner_x as u32, inner_y as u32)];
+                    }
+                }
+            }
+        }
+        padded.step(rule);
+
+        for y in 0..folded.y_size {
+            for x in 0..folded.x_size {
+                folded[(x, y)] = padded[(x + 1, y + 1)];
+            }
+        }
+        Group::new(Coord { x: x_min, y: y_min }, folded)
+            .split()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::rle;
+
+    #[test]
+    fn topology_parse() {
+        assert_eq!(
+            "T100,80".parse(),
+            Ok(Topology::Torus {
+                width: 100,
+                height: 80
+            })
+        );
+        assert_eq!(
+            "K10*,20".parse(),
+            Ok(Topology::KleinBottle {
+                width: 10,
+                height: 20,
+                twist: Twist::Width
+            })
+        );
+        assert_eq!(
+            "K10,20*".parse(),
+            Ok(Topology::KleinBottle {
+                width: 10,
+                height: 20,
+                twist: Twist::Height
+            })
+        );
+        assert_eq!(
+            "T100,0".parse(),
+            Ok(Topology::Torus {
+                width: 100,
+                height: 0
+            })
+        );
+        assert_eq!(
+            "p4,5".parse(),
+            Ok(Topology::Bounded {
+                width: 4,
+                height: 5
+            })
+        );
+        for topology in [
+            "T100", "T0,0", "K10,20", "K10*,20*", "K0*,5", "X3,3", "C4,4", "T4*,4", "",
+        ] {
+            assert!(topology.parse::<Topology>().is_err(), "{}", topology);
+        }
+        for topology in ["T7,0", "P0,3", "K7*,3", "K7,3*"] {
+            assert_eq!(topology.parse::<Topology>().unwrap().to_string(), topology);
+        }
+    }
+
+    #[test]
+    fn topology_canonical() {
+        let torus = Topology::Torus {
+            width: 10,
+            height: 10,
+        };
+        assert_eq!(torus.lower(), Some(Coord { x: -5, y: -4 }));
+        assert_eq!(
+            torus.canonical(Coord { x: 5, y: 6 }),
+            Some(Coord { x: -5, y: -4 })
+        );
+
+        let klein = Topology::KleinBottle {
+            width: 10,
+            height: 10,
+            twist: Twist::Width,
+        };
+        assert_eq!(
+            klein.canonical(Coord { x: -5, y: 6 }),
+            Some(Coord { x: 4, y: -4 })
+        );
+        let klein = Topology::KleinBottle {
+            width: 10,
+            height: 10,
+            twist: Twist::Height,
+        };
+        assert_eq!(
+            klein.canonical(Coord { x: 5, y: -4 }),
+            Some(Coord { x: -5, y: 5 })
+        );
+
+        let cylinder = Topology::Torus {
+            width: 10,
+            height: 0,
+        };
+        assert_eq!(
+            cylinder.canonical(Coord { x: 5, y: 100 }),
+            Some(Coord { x: -5, y: 100 })
+        );
+
+        let bounded = Topology::Bounded {
+            width: 10,
+            height: 10,
+        };
+        assert_eq!(bounded.canonical(Coord { x: 5, y: 0 }), None);
+        assert_eq!(
+            Topology::Plane.canonical(Coord { x: 5, y: 0 }),
+            Some(Coord { x: 5, y: 0 })
+        );
+    }
+
+    #[test]
+    fn topology_image() {
+        let group = Group::new(
+            Coord { x: -5, y: 0 },
+            rle::parse("x = 2, y = 1\n2o!").unwrap().block,
+        );
+        for (twist, wraps, coord) in [
+            (Twist::Width, (0, 1), Coord { x: 3, y: 10 }),
+            (Twist::Height, (1, 0), Coord { x: 5, y: 1 }),
+        ] {
+            let klein = Topology::KleinBottle {
+                width: 10,
+                height: 10,
+                twist,
+            };
+            let image = klein.image(&group, wraps.0, wraps.1);
+            assert_eq!(image.global_coord, coord);
+            let mut cells: Vec<Coord> = image
+                .alive_cells()
+                .into_iter()
+                .filter_map(|c| klein.canonical(c))
+                .collect();
+            cells.sort();
+            let mut expected = group.alive_cells();
+            expected.sort();
+            assert_eq!(cells, expected);
+        }
+    }
+
+    #[test]
+    fn topology_place() {
+        let torus: Topology = "T20,20".parse().unwrap();
+        let mut block = Group::new(
+            Coord { x: 0, y: 0 },
+            rle::parse("x = 2, y = 2\n2o$2o!").unwrap().block,
+        );
+        for _ in 0..32 {
+            block = block.step(&Rule::conway()).unwrap().pop().unwrap();
+        }
+        assert_eq!(block.period(), Some(1));
+
+        //untouched groups keep the cached phases, wrapped ones lose them
+        let placed = torus.place(block.clone()).unwrap();
+        assert_eq!(placed[0].period(), Some(1));
+        block.global_coord.x += 20;
+        let placed = torus.place(block).unwrap();
+        assert_eq!(placed[0].global_coord.x, -1);
+        assert_eq!(placed[0].period(), None);
+    }
+}
@@ -0,0 +1,276 @@
+//! Contains Golly's Macrocell (`.mc`) parser and encoding
+//!
+//! Macrocell stores the pattern as a quadtree with shared subtrees, one node per line. Lines
+//! made of `.`, `*` and `$` are 8x8 leaves, `k nw ne sw se` lines are 2^k nodes referring to
+//! earlier lines by their number (starting from 1, 0 is an empty node). The last node is the
+//! root, centered at the origin with y axis going down
+use super::{HashLife, NodeId, ALIVE, DEAD};
+use crate::groups::{rule::Rule, Coord};
+use std::collections::HashMap;
+
+/// First line of Macrocell files
+pub const HEADER: &str = "[M2]";
+/// Level of leaf nodes
+const LEAF_LEVEL: u8 = 3;
+/// Largest level of a root whose corners fit into global coords
+const MAX_LEVEL: u8 = 62;
+
+/// Error returned when Macrocell text cannot be parsed. Lines are counted from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MacrocellError {
+    pub line: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for MacrocellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Macrocell parse error at line {}: {}",
+            self.line, self.reason
+        )
+    }
+}
+
+impl std::error::Error for MacrocellError {}
+
+/// Bottom left corner of a root of given level in global coords. Macrocell roots are centered
+/// with y axis going down, so the y range is shifted by one after flipping it
+fn origin(level: u8) -> Coord {
+    let half = 1i64 << (level - 1);
+    Coord {
+        x: -half,
+        y: -half + 1,
+    }
+}
+
+/// Parses Macrocell text into [HashLife] universe. `#R` line sets the rule, `#G` line sets the
+/// generation
+pub fn parse(text: &str) -> Result<HashLife, MacrocellError> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, line)) if line.starts_with(HEADER) => (),
+        _ => {
+            return Err(MacrocellError {
+                line: 1,
+                reason: format!("expected '{}' header", HEADER),
+            })
+        }
+    }
+
+    let mut life = HashLife::new(Rule::conway());
+    //node ids of the lines, index 0 is the empty node
+    let mut ids: Vec<NodeId> = vec![DEAD];
+    for (index, line) in lines {
+        let error = |reason: String| MacrocellError {
+            line: index + 1,
+            reason,
+        };
+        let line = line.trim();
+        if let Some(rule) = line.strip_prefix("#R") {
+            life.rule = rule
+                .trim()
+                .parse()
+                .map_err(|err: crate::groups::rule::RuleError| error(err.to_string()))?;
+        } else if let Some(generation) = line.strip_prefix("#G") {
+            life.generation = generation
+                .trim()
+                .parse()
+                .map_err(|_| error(format!("invalid generation '{}'", generation.trim())))?;
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else if line.starts_with(['.', '*', '$']) {
+            let mut grid = [[false; 8]; 8];
+            let (mut x, mut y) = (0, 0);
+            for char in line.chars() {
+                match char {
+                    '.' => x += 1,
+                    '*' if x >= 8 || y >= 8 => return Err(error("leaf is over 8x8".to_string())),
+                    '*' => {
+                        //rows of the grid go up
+                        grid[7 - y][x] = true;
+                        x += 1;
+                    }
+                    '$' => {
+                        x = 0;
+                        y += 1;
+                    }
+                    _ => return Err(error(format!("unexpected character '{}' in leaf", char))),
+                }
+            }
+            ids.push(life.grid_node(&grid, LEAF_LEVEL, 0, 0));
+        } else {
+            let values: Result<Vec<usize>, _> =
+                line.split_whitespace().map(|val| val.parse()).collect();
+            let values = values.map_err(|_| error(format!("invalid node '{}'", line)))?;
+            let [level, nw, ne, sw, se] = values[..] else {
+                return Err(error(format!("invalid node '{}'", line)));
+            };
+            if level <= LEAF_LEVEL as usize || level > MAX_LEVEL as usize {
+                return Err(error(format!("unsupported node level {}", level)));
+            }
+            let level = level as u8;
+            let mut children = [DEAD; 4];
+            //hashlife quadrants go from the bottom left
+            for (quadrant, child) in [(2, nw), (3, ne), (0, sw), (1, se)] {
+                children[quadrant] = match child {
+                    0 => life.empty_node(level - 1),
+                    _ if child < ids.len() => ids[child],
+                    _ => return Err(error(format!("reference to unknown node {}", child))),
+                };
+                if life.nodes[children[quadrant] as usize].level != level - 1 {
+                    return Err(error(format!("node {} has wrong level", child)));
+                }
+            }
+            ids.push(life.node(children));
+        }
+    }
+
+    if ids.len() > 1 {
+        life.root = *ids.last().unwrap();
+        life.origin = origin(life.level());
+    }
+    Ok(life)
+}
+
+/// Encodes alive cells given in global [Field](crate::groups::field::Field) coordinates into
+/// Macrocell
+pub fn encode_global(cells: &[Coord], rule: &Rule, generation: u64) -> String {
+    let mut life = HashLife::new(*rule);
+    life.origin = origin(life.level());
+    for cell in cells {
+        life.set_cell(*cell);
+    }
+
+    let mut out = format!("{} (rust-cell)\n#R {}\n", HEADER, rule);
+    if generation != 0 {
+        out += &format!("#G {}\n", generation);
+    }
+    let mut ids = HashMap::new();
+    let mut lines = Vec::new();
+    if life.write_node(life.root, &mut ids, &mut lines) == 0 {
+        lines.push(String::from("$"));
+    }
+    for line in lines {
+        out += &line;
+        out.push('\n');
+    }
+    out
+}
+
+impl HashLife {
+    /// Builds a universe from Macrocell text. See [parse()]
+    pub fn from_macrocell(text: &str) -> Result<Self, MacrocellError> {
+        parse(text)
+    }
+
+    /// Exports the universe to Macrocell
+    pub fn to_macrocell(&self) -> String {
+        encode_global(&self.alive_cells(), &self.rule, self.generation)
+    }
+
+    /// Returns a node of given level holding cells of *grid* starting from (x, y). Rows of the
+    /// grid go up
+    fn grid_node(&mut self, grid: &[[bool; 8]; 8], level: u8, x: usize, y: usize) -> NodeId {
+        if level == 0 {
+            return match grid[y][x] {
+                true => ALIVE,
+                false => DEAD,
+            };
+        }
+        let half = 1 << (level - 1);
+        let children = [
+            self.grid_node(grid, level - 1, x, y),
+            self.grid_node(grid, level - 1, x + half, y),
+            self.grid_node(grid, level - 1, x, y + half),
+            self.grid_node(grid, level - 1, x + half, y + half),
+        ];
+        self.node(children)
+    }
+
+    /// Appends lines of **node** and its subnodes not written yet. Returns the line number of the
+    /// node, 0 for empty ones
+    fn write_node(
+        &self,
+        node: NodeId,
+        ids: &mut HashMap<NodeId, usize>,
+        lines: &mut Vec<String>,
+    ) -> usize {
+        let data = &self.nodes[node as usize];
+        if data.population == 0 {
+            return 0;
+        }
+        if let Some(id) = ids.get(&node) {
+            return *id;
+        }
+
+        let line = if data.level == LEAF_LEVEL {
+            let mut cells = Vec::new();
+            self.collect_cells(node, Coord { x: 0, y: 0 }, &mut cells);
+            let mut rows = [[false; 8]; 8];
+            for cell in cells {
+                rows[7 - cell.y as usize][cell.x as usize] = true;
+            }
+            let mut line = String::new();
+            let last = rows.iter().rposition(|row| row.contains(&true)).unwrap();
+            for row in &rows[..=last] {
+                let len = row.iter().rposition(|cell| *cell).map_or(0, |x| x + 1);
+                line.extend(row[..len].iter().map(|cell| if *cell { '*' } else { '.' }));
+                line.push('$');
+            }
+            line
+        } else {
+            let [sw, se, nw, ne] = data.children;
+            let children = [nw, ne, sw, se].map(|child| self.write_node(child, ids, lines));
+            format!(
+                "{} {} {} {} {}",
+                data.level, children[0], children[1], children[2], children[3]
+            )
+        };
+        lines.push(line);
+        ids.insert(node, lines.len());
+        lines.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macrocell_roundtrip() {
+        //glider from Golly: top left at (0, 0), y going down
+        let text = "[M2] (golly 4.2)\n#R B3/S23\n#G 12\n.*$..*$***$\n4 0 0 0 1\n";
+        let life = parse(text).unwrap();
+        assert_eq!(life.generation(), 12);
+        assert_eq!(life.population(), 5);
+        for (x, y) in [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)] {
+            assert!(life.get_cell(Coord { x, y: -y }));
+        }
+
+        //smallest centered root holding the glider is a single leaf
+        assert_eq!(
+            life.to_macrocell(),
+            "[M2] (rust-cell)\n#R B3/S23\n#G 12\n$$$$.....*$......*$....***$\n"
+        );
+        assert_eq!(
+            encode_global(&[], &Rule::conway(), 0),
+            "[M2] (rust-cell)\n#R B3/S23\n$\n"
+        );
+
+        let error = parse("[M2]\n.*$\n4 0 0 0 2\n").err().unwrap();
+        assert_eq!(error.line, 3);
+        assert!(parse("x = 1, y = 1\no!").is_err());
+
+        let mut life = HashLife::new(Rule::conway());
+        for (x, y) in [(0, 0), (-100, 37), (1000, -5000)] {
+            life.set_cell(Coord { x, y });
+        }
+        let cells = |life: &HashLife| {
+            let mut cells = life.alive_cells();
+            cells.sort();
+            cells
+        };
+        assert_eq!(cells(&parse(&life.to_macrocell()).unwrap()), cells(&life));
+    }
+}
@@ -0,0 +1,602 @@
+//! Contains [HashLife] struct - a memoized quadtree stepping backend
+//!
+//! The pattern is stored as a hash-consed quadtree where identical subtrees share one node and the
+//! evolution of every node is cached. This allows to jump 2^k generations at once on patterns
+//! with a lot of repetition (ash, oscillators, spaceship streams).
+//!
+//! Results are converted back into a [Field] of [groups](Group) so the rest of the crate keeps
+//! working on them
+
+use crate::groups::{block::Block, field::Field, group::Group, rule::Rule, Coord, UCoord};
+use rstar::RTree;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+pub mod macrocell;
+
+/// Index of a node in [HashLife] node storage
+type NodeId = u32;
+
+/// Dead leaf (single cell)
+const DEAD: NodeId = 0;
+/// Alive leaf (single cell)
+const ALIVE: NodeId = 1;
+
+/// Level of quadtree nodes converted into one [Block] by [HashLife::to_field()]
+const TILE_LEVEL: u8 = 6;
+
+/// Multiplicative hasher for node keys. Keys are small integers, so the default SipHash is
+/// needlessly slow here
+#[derive(Default)]
+struct NodeHasher(u64);
+
+impl Hasher for NodeHasher {
+    fn finish(&self) -> u64 {
+        let hash = self.0 ^ (self.0 >> 32);
+        hash.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ (hash >> 29)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.write_u64(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+type NodeMap<K> = HashMap<K, NodeId, BuildHasherDefault<NodeHasher>>;
+
+/// Quadtree node. Children are indexed as `(y_half << 1) | x_half`, so 0 is the bottom left
+/// quadrant and 3 is the top right one
+struct Node {
+    children: [NodeId; 4],
+    level: u8,
+    population: u64,
+    ///Cached result of advancing the node 2^(level - 2) generations
+    result: Option<NodeId>,
+}
+
+/// Memoized quadtree representation of the playing field
+pub struct HashLife {
+    nodes: Vec<Node>,
+    index: NodeMap<[NodeId; 4]>,
+    empty: Vec<NodeId>,
+    ///Cached results of advancing nodes less than 2^(level - 2) generations
+    cache: NodeMap<(NodeId, u8)>,
+    root: NodeId,
+    ///Global coords of bottom left corner of the root node
+    origin: Coord,
+    rule: Rule,
+    generation: u64,
+}
+
+impl HashLife {
+    /// Creates an empty universe running given [Rule]
+    pub fn new(rule: Rule) -> Self {
+        let leaf = |population| Node {
+            children: [DEAD; 4],
+            level: 0,
+            population,
+            result: None,
+        };
+        let mut life = HashLife {
+            nodes: vec![leaf(0), leaf(1)],
+            index: NodeMap::default(),
+            empty: vec![DEAD],
+            cache: NodeMap::default(),
+            root: DEAD,
+            origin: Coord { x: 0, y: 0 },
+            rule,
+            generation: 0,
+        };
+        life.root = life.empty_node(3);
+        life.origin = Coord { x: -4, y: -4 };
+        life
+    }
+
+    /// Builds a quadtree containing all cells of the [Field] and its [Rule]
+    pub fn from_field(field: &Field) -> Self {
+        let mut life = HashLife::new(field.rule());
+        for group in &field.field {
+            for x in 0..group.block.x_size {
+                for y in 0..group.block.y_size {
+                    if group.block[(x, y)] == 1 {
+                        life.set_cell(group.global_coord + UCoord { x, y }.into());
+                    }
+                }
+            }
+        }
+        life
+    }
+
+    /// Converts the quadtree into a [Field] of independent [groups](Group)
+    pub fn to_field(&self) -> Field {
+        let mut tiles = Vec::new();
+        let tile_level = std::cmp::min(TILE_LEVEL, self.level());
+        self.collect_tiles(self.root, self.origin, tile_level, &mut tiles);
+
+        let mut groups = Vec::new();
+        for (coord, node) in tiles {
+            let size = 1 << tile_level;
+            let mut block = Block::new(size, size);
+            self.fill_block(node, tile_level, (0, 0), &mut block);
+            if let Some(mut pieces) = Group::new(coord, block).split() {
+                groups.append(&mut pieces);
+            }
+        }
+
+        let mut field = Field::new(RTree::bulk_load(groups));
+        field.set_rule(self.rule);
+        field.set_generation(self.generation);
+        field.merge();
+        field
+    }
+
+    /// Returns the [Rule] the universe is running
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// Returns number of generations advanced since creation
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns number of alive cells
+    pub fn population(&self) -> u64 {
+        self.nodes[self.root as usize].population
+    }
+
+    /// Returns global coords of all alive cells
+    pub fn alive_cells(&self) -> Vec<Coord> {
+        let mut cells = Vec::new();
+        self.collect_cells(self.root, self.origin, &mut cells);
+        cells
+    }
+
+    /// Returns number of distinct quadtree nodes currently stored
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Drops all nodes not reachable from the current root together with the step cache
+    pub fn collect_garbage(&mut self) {
+        let mut cells = Vec::new();
+        self.collect_cells(self.root, self.origin, &mut cells);
+        let mut fresh = HashLife::new(self.rule);
+        fresh.generation = self.generation;
+        for coord in cells {
+            fresh.set_cell(coord);
+        }
+        *self = fresh;
+    }
+
+    /// Checks if a cell with given global coords is alive
+    pub fn get_cell(&self, coord: Coord) -> bool {
+        let size = 1i64 << self.level();
+        let (x, y) = (coord.x - self.origin.x, coord.y - self.origin.y);
+        if x < 0 || y < 0 || x >= size || y >= size {
+            return false;
+        }
+        let mut node = self.root;
+        for level in (0..self.level()).rev() {
+            let x_half = (x >> level) & 1;
+            let y_half = (y >> level) & 1;
+            node = self.nodes[node as usize].children[(y_half << 1 | x_half) as usize];
+        }
+        node == ALIVE
+    }
+
+    /// Makes a cell with given global coords alive. Expands the universe if needed
+    pub fn set_cell(&mut self, coord: Coord) {
+        loop {
+            let size = 1i64 << self.level();
+            let (x, y) = (coord.x - self.origin.x, coord.y - self.origin.y);
+            if x >= 0 && y >= 0 && x < size && y < size {
+                self.root = self.set_node_cell(self.root, x as u64, y as u64);
+                return;
+            }
+            self.expand();
+        }
+    }
+
+    /// Advances the universe 2^k generations at once
+    pub fn step_pow2(&mut self, k: u8) {
+        while self.level() < k + 2 || !self.is_padded() {
+            self.expand();
+        }
+        self.expand();
+
+        let level = self.level();
+        self.root = self.advance(self.root, k);
+        self.origin = self.origin
+            + Coord {
+                x: 1 << (level - 2),
+                y: 1 << (level - 2),
+            };
+        self.generation += 1 << k;
+        self.shrink();
+    }
+
+    /// Advances the universe given number of generations
+    pub fn step(&mut self, generations: u64) {
+        for k in 0..u64::BITS as u8 {
+            if generations & (1 << k) != 0 {
+                self.step_pow2(k);
+            }
+        }
+    }
+
+    /// Level of the root node. The universe is a 2^level square
+    fn level(&self) -> u8 {
+        self.nodes[self.root as usize].level
+    }
+
+    /// Returns a canonical node with given children
+    fn node(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(id) = self.index.get(&children) {
+            return *id;
+        }
+        let level = self.nodes[children[0] as usize].level + 1;
+        let population = children
+            .iter()
+            .map(|child| self.nodes[*child as usize].population)
+            .sum();
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            children,
+            level,
+            population,
+            result: None,
+        });
+        self.index.insert(children, id);
+        id
+    }
+
+    /// Returns an empty node of given level
+    fn empty_node(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let child = *self.empty.last().unwrap();
+            let id = self.node([child; 4]);
+            self.empty.push(id);
+        }
+        self.empty[level as usize]
+    }
+
+    /// Returns child of **node** with given quadrant index
+    fn child(&self, node: NodeId, index: usize) -> NodeId {
+        self.nodes[node as usize].children[index]
+    }
+
+    /// Returns a copy of **node** with a cell at offset (x, y) made alive
+    fn set_node_cell(&mut self, node: NodeId, x: u64, y: u64) -> NodeId {
+        let level = self.nodes[node as usize].level;
+        if level == 0 {
+            return ALIVE;
+        }
+        let half = 1 << (level - 1);
+        let index = ((y >= half) as usize) << 1 | (x >= half) as usize;
+        let mut children = self.nodes[node as usize].children;
+        children[index] = self.set_node_cell(children[index], x % half, y % half);
+        self.node(children)
+    }
+
+    /// Surrounds the root with empty space, keeping it in the center of a twice bigger root
+    fn expand(&mut self) {
+        let level = self.level();
+        let e = self.empty_node(level - 1);
+        let [sw, se, nw, ne] = self.nodes[self.root as usize].children;
+        let children = [
+            self.node([e, e, e, sw]),
+            self.node([e, e, se, e]),
+            self.node([e, nw, e, e]),
+            self.node([ne, e, e, e]),
+        ];
+        self.root = self.node(children);
+        self.origin = self.origin
+            + Coord {
+                x: -(1 << (level - 1)),
+                y: -(1 << (level - 1)),
+            };
+    }
+
+    /// Checks if all alive cells are in the central half of the root
+    fn is_padded(&self) -> bool {
+        let children = self.nodes[self.root as usize].children;
+        (0..4).all(|quadrant| {
+            (0..4)
+                .filter(|grandchild| *grandchild != 3 - quadrant)
+                .all(|grandchild| {
+                    self.nodes[self.child(children[quadrant], grandchild) as usize].population == 0
+                })
+        })
+    }
+
+    /// Replaces the root with its center while all alive cells fit in it
+    fn shrink(&mut self) {
+        while self.level() > 3 && self.is_padded() {
+            let level = self.level();
+            self.root = self.center(self.root);
+            self.origin = self.origin
+                + Coord {
+                    x: 1 << (level - 2),
+                    y: 1 << (level - 2),
+                };
+        }
+    }
+
+    /// Returns the central subnode of **node** (one level lower)
+    fn center(&mut self, node: NodeId) -> NodeId {
+        let [sw, se, nw, ne] = self.nodes[node as usize].children;
+        let children = [
+            self.child(sw, 3),
+            self.child(se, 2),
+            self.child(nw, 1),
+            self.child(ne, 0),
+        ];
+        self.node(children)
+    }
+
+    /// Returns the central subnode of **node** (one level lower) advanced 2^k generations.
+    /// *k* must not exceed `level - 2`
+    fn advance(&mut self, node: NodeId, k: u8) -> NodeId {
+        let level = self.nodes[node as usize].level;
+        if self.nodes[node as usize].population == 0 {
+            return self.empty_node(level - 1);
+        }
+        let full_speed = k == level - 2;
+        let cached = match full_speed {
+            true => self.nodes[node as usize].result,
+            false => self.cache.get(&(node, k)).copied(),
+        };
+        if let Some(result) = cached {
+            return result;
+        }
+
+        let result = if level == 2 {
+            self.advance_base(node)
+        } else {
+            //3x3 overlapping subnodes of **node** one level lower
+            let mut grandchildren = [[DEAD; 4]; 4];
+            for (gy, row) in grandchildren.iter_mut().enumerate() {
+                for (gx, cell) in row.iter_mut().enumerate() {
+                    let child = self.child(node, (gy / 2) * 2 + gx / 2);
+                    *cell = self.child(child, (gy % 2) * 2 + gx % 2);
+                }
+            }
+            let mut sub = [[DEAD; 3]; 3];
+            for j in 0..3 {
+                for i in 0..3 {
+                    let g = &grandchildren;
+                    let subnode = self.node([g[j][i], g[j][i + 1], g[j + 1][i], g[j + 1][i + 1]]);
+                    sub[j][i] = if full_speed {
+                        self.advance(subnode, level - 3)
+                    } else {
+                        self.center(subnode)
+                    };
+                }
+            }
+
+            let next_k = if full_speed { level - 3 } else { k };
+            let mut quadrants = [DEAD; 4];
+            for (index, quadrant) in quadrants.iter_mut().enumerate() {
+                let (j, i) = (index / 2, index % 2);
+                let combined =
+                    self.node([sub[j][i], sub[j][i + 1], sub[j + 1][i], sub[j + 1][i + 1]]);
+                *quadrant = self.advance(combined, next_k);
+            }
+            self.node(quadrants)
+        };
+
+        match full_speed {
+            true => self.nodes[node as usize].result = Some(result),
+            false => {
+                self.cache.insert((node, k), result);
+            }
+        }
+        result
+    }
+
+    /// Advances a 4x4 node one generation directly, returning its central 2x2 node
+    fn advance_base(&mut self, node: NodeId) -> NodeId {
+        let mut cells = [[false; 4]; 4];
+        for (y, row) in cells.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                let child = self.child(node, (y / 2) * 2 + x / 2);
+                *cell = self.child(child, (y % 2) * 2 + x % 2) == ALIVE;
+            }
+        }
+
+        let mut result = [DEAD; 4];
+        for (index, cell) in result.iter_mut().enumerate() {
+            let (x, y) = (index % 2 + 1, index / 2 + 1);
+            let mut count = 0;
+            for row in &cells[y - 1..=y + 1] {
+                count += row[x - 1..=x + 1].iter().filter(|cell| **cell).count() as u8;
+            }
+            count -= cells[y][x] as u8;
+            if self.rule.next(cells[y][x], count) {
+                *cell = ALIVE;
+            }
+        }
+        self.node(result)
+    }
+
+    /// Collects global coords of all alive cells of **node** with bottom left corner at *coord*
+    fn collect_cells(&self, node: NodeId, coord: Coord, cells: &mut Vec<Coord>) {
+        let data = &self.nodes[node as usize];
+        if data.population == 0 {
+            return;
+        }
+        if data.level == 0 {
+            cells.push(coord);
+            return;
+        }
+        let half = 1 << (data.level - 1);
+        for (index, child) in data.children.iter().enumerate() {
+            let offset = Coord {
+                x: (index as i64 & 1) * half,
+                y: (index as i64 >> 1) * half,
+            };
+            self.collect_cells(*child, coord + offset, cells);
+        }
+    }
+
+    /// Collects non empty subnodes of *tile_level* together with their global coords
+    fn collect_tiles(
+        &self,
+        node: NodeId,
+        coord: Coord,
+        tile_level: u8,
+        tiles: &mut Vec<(Coord, NodeId)>,
+    ) {
+        let data = &self.nodes[node as usize];
+        if data.population == 0 {
+            return;
+        }
+        if data.level == tile_level {
+            tiles.push((coord, node));
+            return;
+        }
+        let half = 1 << (data.level - 1);
+        for (index, child) in data.children.iter().enumerate() {
+            let offset = Coord {
+                x: (index as i64 & 1) * half,
+                y: (index as i64 >> 1) * half,
+            };
+            self.collect_tiles(*child, coord + offset, tile_level, tiles);
+        }
+    }
+
+    /// Copies alive cells of **node** into *block* at given offset
+    fn fill_block(&self, node: NodeId, level: u8, offset: (u32, u32), block: &mut Block) {
+        if self.nodes[node as usize].population == 0 {
+            return;
+        }
+        if level == 0 {
+            block[offset] = 1;
+            return;
+        }
+        let half = 1 << (level - 1);
+        for (index, child) in self.nodes[node as usize].children.iter().enumerate() {
+            let child_offset = (
+                offset.0 + (index as u32 & 1) * half,
+                offset.1 + (index as u32 >> 1) * half,
+            );
+            self.fill_block(*child, level - 1, child_offset, block);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library;
+
+    fn field_cells(field: &Field) -> Vec<Coord> {
+        let mut cells = Vec::new();
+        for group in &field.field {
+            for x in 0..group.block.x_size {
+                for y in 0..group.block.y_size {
+                    if group.block[(x, y)] == 1 {
+                        cells.push(group.global_coord + UCoord { x, y }.into());
+                    }
+                }
+            }
+        }
+        cells.sort();
+        cells
+    }
+
+    fn r_pentomino() -> Field {
+        let mut tree = RTree::new();
+        tree.insert(
+            library::find("r-pentomino")
+                .unwrap()
+                .group(Coord { x: 1, y: 1 }),
+        );
+        Field::new(tree)
+    }
+
+    #[test]
+    fn hashlife_cells() {
+        let mut life = HashLife::new(Rule::conway());
+        let coords = [
+            Coord { x: 0, y: 0 },
+            Coord { x: -100, y: 37 },
+            Coord { x: 1000, y: -5000 },
+        ];
+        for coord in coords {
+            life.set_cell(coord);
+        }
+        for coord in coords {
+            assert!(life.get_cell(coord));
+        }
+        assert!(!life.get_cell(Coord { x: 1, y: 0 }));
+        assert_eq!(life.population(), 3);
+    }
+
+    #[test]
+    fn hashlife_glider() {
+        let mut life = HashLife::new(Rule::conway());
+        //glider moving to the bottom right
+        for (x, y) in [(1, 2), (2, 1), (0, 0), (1, 0), (2, 0)] {
+            life.set_cell(Coord { x, y });
+        }
+        life.step(4);
+        for (x, y) in [(1, 2), (2, 1), (0, 0), (1, 0), (2, 0)] {
+            assert!(life.get_cell(Coord { x: x + 1, y: y - 1 }));
+        }
+        assert_eq!(life.population(), 5);
+        assert_eq!(life.generation(), 4);
+    }
+
+    #[test]
+    fn hashlife_matches_field() {
+        let mut field = r_pentomino();
+        let mut life = HashLife::from_field(&field);
+        for _ in 0..200 {
+            field.step();
+        }
+        life.step(137);
+        life.step(63);
+        assert_eq!(field_cells(&life.to_field()), field_cells(&field));
+
+        life.collect_garbage();
+        life.step_pow2(10);
+        assert_eq!(life.generation(), 1224);
+        //r-pentomino stabilizes at generation 1103 with population 116
+        assert_eq!(life.population(), 116);
+    }
+
+    #[test]
+    fn hashlife_rule() {
+        let mut field = r_pentomino();
+        field.set_rule("B36/S23".parse().unwrap());
+        let mut life = HashLife::from_field(&field);
+        for _ in 0..100 {
+            field.step();
+        }
+        life.step(100);
+        let result = life.to_field();
+        assert_eq!(result.rule(), field.rule());
+        assert_eq!(field_cells(&result), field_cells(&field));
+    }
+}
@@ -6,7 +6,15 @@
 
 #![allow(dead_code)] //remove after major writing and debugging is finished
 
+pub mod analysis;
+pub mod error;
 pub mod groups;
+pub mod hashlife;
+pub mod library;
+pub mod render;
+pub mod view;
+
+pub use error::Error;
 
 #[cfg(test)]
 mod test;
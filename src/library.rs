@@ -0,0 +1,332 @@
+//! Contains a library of well known named patterns together with their known long-term behaviour
+use crate::groups::{group::Group, rle, rle::RlePattern, Coord};
+use crate::Error;
+
+/// Known long-term behaviour of a [LibraryPattern] in B3/S23
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Behaviour {
+    StillLife,
+    /// Returns to the same state every *period* generations
+    Oscillator {
+        period: u32,
+    },
+    /// Returns to the same state moved by *(dx, dy)* cells every *period* generations. The y axis
+    /// goes up, as in [Field](crate::groups::field::Field)
+    Spaceship {
+        period: u32,
+        dx: i64,
+        dy: i64,
+    },
+    /// Settles into still lifes, oscillators and escaping spaceships (or dies out) at generation
+    /// *lifespan*
+    Methuselah {
+        lifespan: u32,
+    },
+    /// Emits a spaceship every *period* generations
+    Gun {
+        period: u32,
+    },
+    /// Grows without bound
+    Growth,
+}
+
+/// Named pattern of the library
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LibraryPattern {
+    /// Lowercase name with words separated by `-`
+    pub name: &'static str,
+    pub rle: &'static str,
+    pub behaviour: Behaviour,
+}
+
+impl LibraryPattern {
+    /// Parses the pattern. Row 0 of the block is the top row
+    pub fn parse(&self) -> RlePattern {
+        rle::parse(self.rle).expect("Library patterns are valid RLE")
+    }
+
+    /// Returns the pattern as a [Group] with bottom left corner at *coord*
+    pub fn group(&self, coord: Coord) -> Group {
+        let mut group = Group::new(coord, self.parse().block);
+        group.reverse_y();
+        group.global_coord = coord;
+        group
+    }
+
+    /// Generation at which the pattern stabilizes: 0 for still lifes, oscillators and
+    /// spaceships, [None] for guns and growing patterns
+    pub fn lifespan(&self) -> Option<u32> {
+        match self.behaviour {
+            Behaviour::StillLife | Behaviour::Oscillator { .. } | Behaviour::Spaceship { .. } => {
+                Some(0)
+            }
+            Behaviour::Methuselah { lifespan } => Some(lifespan),
+            Behaviour::Gun { .. } | Behaviour::Growth => None,
+        }
+    }
+
+    /// Period of still lifes (1), oscillators, spaceships and guns
+    pub fn period(&self) -> Option<u32> {
+        match self.behaviour {
+            Behaviour::StillLife => Some(1),
+            Behaviour::Oscillator { period }
+            | Behaviour::Spaceship { period, .. }
+            | Behaviour::Gun { period } => Some(period),
+            Behaviour::Methuselah { .. } | Behaviour::Growth => None,
+        }
+    }
+}
+
+/// Shortens entries of [PATTERNS]
+const fn pattern(name: &'static str, rle: &'static str, behaviour: Behaviour) -> LibraryPattern {
+    LibraryPattern {
+        name,
+        rle,
+        behaviour,
+    }
+}
+
+use Behaviour::*;
+
+/// All patterns of the library
+pub const PATTERNS: &[LibraryPattern] = &[
+    pattern("block", "x = 2, y = 2\n2o$2o!", StillLife),
+    pattern("beehive", "x = 4, y = 3\nb2o$o2bo$b2o!", StillLife),
+    pattern("loaf", "x = 4, y = 4\nb2o$o2bo$bobo$2bo!", StillLife),
+    pattern("boat", "x = 3, y = 3\n2o$obo$bo!", StillLife),
+    pattern("ship", "x = 3, y = 3\n2o$obo$b2o!", StillLife),
+    pattern("tub", "x = 3, y = 3\nbo$obo$bo!", StillLife),
+    pattern("pond", "x = 4, y = 4\nb2o$o2bo$o2bo$b2o!", StillLife),
+    pattern("eater", "x = 4, y = 4\n2o$obo$2bo$2b2o!", StillLife),
+    pattern("blinker", "x = 3, y = 1\n3o!", Oscillator { period: 2 }),
+    pattern("toad", "x = 4, y = 2\nb3o$3o!", Oscillator { period: 2 }),
+    pattern(
+        "beacon",
+        "x = 4, y = 4\n2o$2o$2b2o$2b2o!",
+        Oscillator { period: 2 },
+    ),
+    pattern(
+        "clock",
+        "x = 4, y = 4\nbo$2b2o$2o$2bo!",
+        Oscillator { period: 2 },
+    ),
+    pattern(
+        "pulsar",
+        "x = 13, y = 13\n2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$\
+         o4bobo4bo$o4bobo4bo$o4bobo4bo2$2b3o3b3o!",
+        Oscillator { period: 3 },
+    ),
+    pattern(
+        "figure-eight",
+        "x = 6, y = 6\n3o$3o$3o$3b3o$3b3o$3b3o!",
+        Oscillator { period: 8 },
+    ),
+    pattern(
+        "galaxy",
+        "x = 9, y = 9\n2ob6o$2ob6o$2o$2o5b2o$2o5b2o$2o5b2o$7b2o$6ob2o$6ob2o!",
+        Oscillator { period: 8 },
+    ),
+    pattern(
+        "tumbler",
+        "x = 9, y = 5\nbo5bo$obo3bobo$o2bobo2bo$2bo3bo$2b2ob2o!",
+        Oscillator { period: 14 },
+    ),
+    pattern(
+        "pentadecathlon",
+        "x = 10, y = 3\n2bo4bo$2ob4ob2o$2bo4bo!",
+        Oscillator { period: 15 },
+    ),
+    pattern(
+        "queen-bee-shuttle",
+        "x = 24, y = 7\n9bo$7bobo$6bobo$2o3bo2bo11b2o$2o4bobo11b2o$7bobo$9bo!",
+        Oscillator { period: 30 },
+    ),
+    pattern(
+        "glider",
+        "x = 3, y = 3\nbo$2bo$3o!",
+        Spaceship {
+            period: 4,
+            dx: 1,
+            dy: -1,
+        },
+    ),
+    pattern(
+        "lwss",
+        "x = 5, y = 4\nbo2bo$o$o3bo$4o!",
+        Spaceship {
+            period: 4,
+            dx: -2,
+            dy: 0,
+        },
+    ),
+    pattern(
+        "mwss",
+        "x = 6, y = 5\n3bo$bo3bo$o$o4bo$5o!",
+        Spaceship {
+            period: 4,
+            dx: -2,
+            dy: 0,
+        },
+    ),
+    pattern(
+        "hwss",
+        "x = 7, y = 5\n3b2o$bo4bo$o$o5bo$6o!",
+        Spaceship {
+            period: 4,
+            dx: -2,
+            dy: 0,
+        },
+    ),
+    pattern(
+        "r-pentomino",
+        "x = 3, y = 3\nb2o$2o$bo!",
+        Methuselah { lifespan: 1103 },
+    ),
+    pattern(
+        "diehard",
+        "x = 8, y = 3\n6bo$2o$bo3b3o!",
+        Methuselah { lifespan: 130 },
+    ),
+    pattern(
+        "acorn",
+        "x = 7, y = 3\nbo$3bo$2o2b3o!",
+        Methuselah { lifespan: 5206 },
+    ),
+    pattern(
+        "b-heptomino",
+        "x = 4, y = 3\nob2o$3o$bo!",
+        Methuselah { lifespan: 148 },
+    ),
+    pattern(
+        "pi-heptomino",
+        "x = 3, y = 3\n3o$obo$obo!",
+        Methuselah { lifespan: 173 },
+    ),
+    pattern(
+        "herschel",
+        "x = 3, y = 4\no$3o$obo$2bo!",
+        Methuselah { lifespan: 128 },
+    ),
+    pattern(
+        "thunderbird",
+        "x = 3, y = 5\n3o2$bo$bo$bo!",
+        Methuselah { lifespan: 243 },
+    ),
+    pattern(
+        "century",
+        "x = 4, y = 3\n2b2o$3o$bo!",
+        Methuselah { lifespan: 103 },
+    ),
+    pattern(
+        "rabbits",
+        "x = 7, y = 3\no3b3o$3o2bo$bo!",
+        Methuselah { lifespan: 17331 },
+    ),
+    pattern(
+        "lidka",
+        "x = 9, y = 6\n3o5bo$3bo4bo$3b2o3bo2$6b3o$6bo!",
+        Methuselah { lifespan: 29126 },
+    ),
+    pattern(
+        "gosper-glider-gun",
+        "x = 36, y = 9\n24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$\
+         2o8bo3bob2o4bobo$10bo5bo7bo$11bo3bo$12b2o!",
+        Gun { period: 30 },
+    ),
+    pattern(
+        "simkin-glider-gun",
+        "x = 33, y = 21\n2o5b2o$2o5b2o2$4b2o$4b2o5$22b2ob2o$21bo5bo$21bo6bo2b2o$\
+         21b3o3bo3b2o$26bo4$20b2o$20bo$21b3o$23bo!",
+        Gun { period: 120 },
+    ),
+    pattern(
+        "switch-engine",
+        "x = 8, y = 6\n6bo$4bob2o$4bobo$4bo$2bo$obo!",
+        Growth,
+    ),
+    pattern(
+        "infinite-growth-5x5",
+        "x = 5, y = 5\n3obo$o$3b2o$b2obo$obobo!",
+        Growth,
+    ),
+];
+
+/// Finds a pattern by name. Case, spaces and underscores are ignored, so "Gosper glider gun"
+/// finds "gosper-glider-gun"
+pub fn find(name: &str) -> Option<&'static LibraryPattern> {
+    let name = name.trim().to_lowercase().replace([' ', '_'], "-");
+    PATTERNS.iter().find(|pattern| pattern.name == name)
+}
+
+/// Finds a pattern by name as [find()] does. Returns [Error::UnknownPattern] when there is none
+pub fn get(name: &str) -> Result<&'static LibraryPattern, Error> {
+    find(name).ok_or_else(|| Error::UnknownPattern(name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::period::{classify, Classification};
+    use crate::groups::{field::Field, rule::Rule};
+    use rstar::RTree;
+
+    fn field(pattern: &LibraryPattern) -> Field {
+        let mut tree = RTree::new();
+        tree.insert(pattern.group(Coord { x: 0, y: 0 }));
+        Field::new(tree)
+    }
+
+    #[test]
+    fn library_behaviour() {
+        assert_eq!(find("Gosper glider_gun").unwrap().name, "gosper-glider-gun");
+        assert!(find("unknown").is_none());
+        assert_eq!(
+            get("unknown"),
+            Err(Error::UnknownPattern(String::from("unknown")))
+        );
+
+        for pattern in PATTERNS {
+            let group = pattern.group(Coord { x: 0, y: 0 });
+            assert_eq!(group.global_coord, Coord { x: 0, y: 0 });
+            let classification = classify(&group, &Rule::conway(), 100);
+            match pattern.behaviour {
+                StillLife => assert_eq!(classification, Classification::StillLife),
+                Oscillator { period } => {
+                    assert_eq!(classification, Classification::Oscillator { period })
+                }
+                Spaceship { period, dx, dy } => {
+                    assert_eq!(classification, Classification::Spaceship { period, dx, dy })
+                }
+                //long lived ones take too much time
+                Methuselah { lifespan } if lifespan < 1000 => {
+                    let mut field = field(pattern);
+                    let limit = lifespan as u64 + 100;
+                    assert_eq!(
+                        field.run_until_stable(limit, 100, false),
+                        Some(lifespan as u64),
+                        "{}",
+                        pattern.name
+                    );
+                }
+                Methuselah { .. } => (),
+                Gun { period } => {
+                    //every period adds a glider
+                    let mut field = field(pattern);
+                    let mut populations = Vec::new();
+                    for _ in 0..3 {
+                        for _ in 0..period {
+                            field.step();
+                        }
+                        populations.push(field.population());
+                    }
+                    assert_eq!(populations[1] - populations[0], 5, "{}", pattern.name);
+                    assert_eq!(populations[2] - populations[1], 5, "{}", pattern.name);
+                }
+                Growth => {
+                    let mut field = field(pattern);
+                    assert_eq!(field.run_until_stable(1000, 100, false), None);
+                }
+            }
+        }
+    }
+}
@@ -1,120 +1,781 @@
+use clap::{Args, CommandFactory, ErrorKind, Parser, Subcommand, ValueEnum};
 use rstar::RTree;
-use rust_cell::groups::{block::Block, field::Field, group::Group, Coord};
-use svg::node::element::Rectangle;
-use clap::Parser;
-
-/// Returns a block with Lidka predecessor (29126 generations lifespan)
-fn lidka() -> Block {
-    let mut block = Block::new(11, 8);
-    block[(1, 1)] = 1;
-    block[(2, 1)] = 1;
-    block[(3, 1)] = 1;
-    block[(4, 2)] = 1;
-    block[(4, 3)] = 1;
-    block[(5, 3)] = 1;
-    block[(7, 5)] = 1;
-    block[(7, 6)] = 1;
-    block[(8, 5)] = 1;
-    block[(9, 1)] = 1;
-    block[(9, 2)] = 1;
-    block[(9, 3)] = 1;
-    block[(9, 5)] = 1;
-    block
-}
-
-/// Returns a block with a r pentomino (1103 generations lifespan)
-fn r_pentomino() -> Block {
-    let mut block = Block::new(5, 5);
-    block[(1, 2)] = 1;
-    block[(2, 1)] = 1;
-    block[(2, 2)] = 1;
-    block[(2, 3)] = 1;
-    block[(3, 3)] = 1;
-    block
+use rust_cell::analysis::{census, period};
+use rust_cell::groups::{
+    block::Block, field::Field, format::PatternFormat, group::Group, rule::Rule,
+    topology::Topology, Coord,
+};
+use rust_cell::hashlife::HashLife;
+use rust_cell::library;
+use rust_cell::render::animation::{Animation, AnimationFormat};
+use rust_cell::render::png;
+use rust_cell::render::raster::RasterConfig;
+use rust_cell::render::svg::{self as svg_render, SvgAnimation, SvgConfig, SvgMode};
+use rust_cell::render::terminal::Glyphs;
+use rust_cell::view::{self, Viewer};
+use rust_cell::Error;
+use std::time::Instant;
+
+/// Engine used to advance the field
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Backend {
+    /// R*-tree of independent groups
+    Rtree,
+    /// Memoized quadtree, jumps many generations at once
+    Hashlife,
+}
+
+/// File format of per generation statistics
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum StatsFormat {
+    /// Comma separated values with a header line
+    Csv,
+    /// One JSON object per line
+    Json,
+}
+
+/// Pattern to start from together with its rule and universe
+#[derive(Args)]
+struct InputArgs {
+    /// Pattern from the built-in library (e.g. "r-pentomino", "acorn", "gosper-glider-gun").
+    /// When not specified RLE, plaintext, Life 1.05/1.06 or Macrocell pattern is read from
+    /// "--file" or stdin
+    #[clap(value_parser)]
+    pattern: Option<String>,
+
+    /// Path to read the pattern from instead of stdin
+    #[clap(short, long, value_parser, conflicts_with = "pattern")]
+    file: Option<String>,
+
+    /// Format of the pattern read from "--file" or stdin: "rle", "cells", "life105", "life106"
+    /// or "mc". Detected from the file extension or contents when not specified
+    #[clap(long, value_parser)]
+    format: Option<PatternFormat>,
+
+    /// Rule to run in B/S notation (e.g. "B36/S23" for HighLife). Defaults to the rule from the
+    /// pattern header or B3/S23
+    #[clap(short, long, value_parser)]
+    rule: Option<Rule>,
+
+    /// Finite universe in Golly notation: "P40,20" (bounded plane), "T100,100" (torus),
+    /// "T100,0" (cylinder), "K100*,100" or "K100,100*" (Klein bottle, twisted top and bottom or
+    /// left and right edges). Zero size is unbounded. Defaults to the one from RLE rule suffix or
+    /// unbounded plane. Not supported by "hashlife"
+    #[clap(short, long, value_parser)]
+    topology: Option<Topology>,
+}
+
+impl InputArgs {
+    /// Loads the pattern into a field with its rule and topology set. Library patterns also
+    /// give the number of generations to run when it is not specified: their lifespan or period
+    fn load(&self) -> (Field, Option<u32>) {
+        let mut age = None;
+
+        let mut field = match &self.pattern {
+            Some(name) => {
+                let pattern = library::get(name).unwrap_or_else(|err| {
+                    let names: Vec<&str> = library::PATTERNS.iter().map(|val| val.name).collect();
+                    eprintln!("{}, available: {}", err, names.join(", "));
+                    std::process::exit(1);
+                });
+                age = Some(
+                    pattern
+                        .lifespan()
+                        .filter(|lifespan| *lifespan > 0)
+                        .or_else(|| pattern.period())
+                        .unwrap_or(0),
+                );
+                field_from_block(Coord { x: 0, y: 0 }, pattern.parse().block)
+            }
+            None => {
+                let mut buf = String::new();
+                match &self.file {
+                    Some(path) => {
+                        buf = std::fs::read_to_string(path).unwrap_or_else(|err| {
+                            eprintln!("Cannot read pattern file {}: {}", path, err);
+                            std::process::exit(1);
+                        })
+                    }
+                    None => {
+                        if let Err(err) =
+                            std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf)
+                        {
+                            eprintln!("Cannot read pattern from stdin: {}", err);
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                let format = self
+                    .format
+                    .or_else(|| self.file.as_deref().and_then(PatternFormat::from_path));
+                Field::import(&buf, format).unwrap_or_else(|err| {
+                    eprintln!("Cannot parse pattern: {}", err);
+                    std::process::exit(1);
+                })
+            }
+        };
+
+        if let Some(rule) = self.rule {
+            field.set_rule(rule);
+        }
+        if let Some(topology) = self.topology {
+            field.set_topology(topology);
+        }
+        (field, age)
+    }
+}
+
+/// How the pattern is advanced
+#[derive(Args)]
+struct SimArgs {
+    /// Number of generations to be run. Required for patterns not from the library
+    #[clap(short, long, value_parser)]
+    generations: Option<u32>,
+
+    /// Runs the pattern until it stabilizes and prints its lifespan. "--generations" becomes the
+    /// limit. Always uses "rtree" backend
+    #[clap(long, action)]
+    until_stable: bool,
+
+    /// Maximal period of oscillators and spaceships expected after stabilization
+    #[clap(long, value_parser, default_value_t = 100)]
+    stable_period: u32,
+
+    /// Stepping engine to use. "--parallel" only applies to "rtree"
+    #[clap(short, long, value_enum, default_value_t = Backend::Rtree)]
+    backend: Backend,
+}
+
+impl SimArgs {
+    /// Number of generations to run, or the limit of "--until-stable". *default* is used when
+    /// "--generations" is not specified
+    fn generations(&self, default: Option<u32>) -> u32 {
+        match (self.generations, self.until_stable, default) {
+            (Some(val), _, _) => val,
+            (None, true, _) => u32::MAX,
+            (None, false, Some(val)) => val,
+            (None, false, None) => Cli::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "\"--generations\" is required for patterns not from the library",
+                )
+                .exit(),
+        }
+    }
+}
+
+/// Look of the SVG output
+#[derive(Args)]
+struct SvgArgs {
+    /// Size of one cell in SVG units
+    #[clap(long, value_parser, default_value_t = 10)]
+    cell_size: u32,
+
+    /// Background color
+    #[clap(long, value_parser, default_value_t = String::from("grey"))]
+    background: String,
+
+    /// Color of alive cells
+    #[clap(long, value_parser, default_value_t = String::from("green"))]
+    cell_color: String,
+
+    /// Outline color of cells, "none" to disable
+    #[clap(long, value_parser, default_value_t = String::from("black"))]
+    cell_stroke: String,
+
+    /// Hides outlines of groups
+    #[clap(long, action)]
+    no_envelopes: bool,
+
+    /// Hides the marker of (0, 0) cell
+    #[clap(long, action)]
+    no_origin: bool,
+
+    /// Draws only the window "X_MIN,Y_MIN,X_MAX,Y_MAX" of the field
+    #[clap(long, value_parser = parse_crop, value_name = "WINDOW")]
+    crop: Option<(Coord, Coord)>,
+
+    /// How cells are written: "cells" (rect per cell), "runs" (rect per horizontal run) or
+    /// "path" (single path, smallest files)
+    #[clap(long, value_parser, default_value_t = SvgMode::Cells)]
+    svg_mode: SvgMode,
+
+    /// Path to write an animated SVG of the run to. Frames are taken as for "--animation"
+    #[clap(long, value_parser, value_name = "FILE")]
+    svg_animation: Option<String>,
+}
+
+impl SvgArgs {
+    fn config(&self) -> SvgConfig {
+        SvgConfig {
+            cell_size: self.cell_size,
+            background: self.background.clone(),
+            cell_color: self.cell_color.clone(),
+            cell_stroke: match self.cell_stroke.as_str() {
+                "none" => None,
+                val => Some(val.to_string()),
+            },
+            show_envelopes: !self.no_envelopes,
+            show_origin: !self.no_origin,
+            crop: self.crop,
+            mode: self.svg_mode,
+            ..SvgConfig::default()
+        }
+    }
+}
+
+/// Raster outputs: PNG of the final generation and animation of the run
+#[derive(Args)]
+struct RasterArgs {
+    /// Path to write the final generation as PNG to
+    #[clap(long, value_parser, value_name = "FILE")]
+    png: Option<String>,
+
+    /// Path to write an animation of the run to. Files ending with ".png" or ".apng" are APNG,
+    /// others are GIF
+    #[clap(long, value_parser, value_name = "FILE")]
+    animation: Option<String>,
+
+    /// Captures an animation frame (raster or SVG) every K generations
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..), value_name = "K", default_value_t = 1)]
+    frame_every: u32,
+
+    /// Time each animation frame is shown in milliseconds
+    #[clap(long, value_parser, value_name = "MS", default_value_t = 100)]
+    frame_delay: u16,
+
+    /// Size of one raster pixel in image pixels
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..), default_value_t = 1)]
+    zoom: u32,
+
+    /// Side of the square of cells shown as one raster pixel, its color is the share of alive
+    /// cells
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..), value_name = "N", default_value_t = 1)]
+    density: u32,
+}
+
+impl RasterArgs {
+    fn config(&self, crop: Option<(Coord, Coord)>) -> RasterConfig {
+        RasterConfig {
+            zoom: self.zoom,
+            density: self.density,
+            crop,
+            ..RasterConfig::default()
+        }
+    }
+}
+
+/// Parses crop window of the SVG output
+fn parse_crop(s: &str) -> Result<(Coord, Coord), String> {
+    let values: Vec<i64> = s
+        .split(',')
+        .map(|val| val.trim().parse::<i64>())
+        .collect::<Result<_, _>>()
+        .map_err(|err| err.to_string())?;
+    match values[..] {
+        [x_min, y_min, x_max, y_max] if x_min <= x_max && y_min <= y_max => {
+            Ok((Coord { x: x_min, y: y_min }, Coord { x: x_max, y: y_max }))
+        }
+        _ => Err("expected X_MIN,Y_MIN,X_MAX,Y_MAX".to_string()),
+    }
+}
+
+#[derive(Args)]
+struct RunArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    sim: SimArgs,
+
+    /// Path to write the final generation as SVG to
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    output_file: Option<String>,
+
+    /// Path to write the final generation as RLE to
+    #[clap(long, value_parser, value_name = "FILE")]
+    rle_output: Option<String>,
+
+    /// Path to write the final generation to in "--output-format"
+    #[clap(long, value_parser, value_name = "FILE")]
+    pattern_output: Option<String>,
+
+    /// Format of "--pattern-output": "rle", "cells", "life105", "life106" or "mc". Defaults to
+    /// the one matching the file extension or RLE
+    #[clap(long, value_parser, value_name = "FORMAT")]
+    output_format: Option<PatternFormat>,
+
+    /// Path to write population, group count, bounding box and step time of every generation to.
+    /// Only "rtree" backend records them
+    #[clap(long, value_parser, value_name = "FILE")]
+    stats: Option<String>,
+
+    /// Format of the statistics file
+    #[clap(long, value_enum, default_value_t = StatsFormat::Csv)]
+    stats_format: StatsFormat,
+
+    #[clap(flatten)]
+    svg: SvgArgs,
+
+    #[clap(flatten)]
+    raster: RasterArgs,
+}
+
+impl RunArgs {
+    /// Creates animations requested by raster and SVG arguments
+    fn frames(&self) -> Frames {
+        Frames {
+            raster: self.raster.animation.as_ref().map(|path| {
+                Animation::new(
+                    AnimationFormat::from_path(path),
+                    self.raster.config(self.svg.crop),
+                    self.raster.frame_delay,
+                )
+            }),
+            svg: self
+                .svg
+                .svg_animation
+                .as_ref()
+                .map(|_| SvgAnimation::new(self.svg.config(), self.raster.frame_delay.into())),
+            every: self.raster.frame_every,
+        }
+    }
+}
+
+#[derive(Args)]
+struct AnalyzeArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    sim: SimArgs,
+
+    /// Maximal period searched for when classifying objects
+    #[clap(long, value_parser, default_value_t = 1000)]
+    max_period: u32,
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+
+    /// Path to write the pattern to. Printed to stdout when not specified
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    output_file: Option<String>,
+
+    /// Format to write: "rle", "cells", "life105", "life106" or "mc". Defaults to the one
+    /// matching the file extension or RLE
+    #[clap(long, value_parser, value_name = "FORMAT")]
+    output_format: Option<PatternFormat>,
+}
+
+#[derive(Args)]
+struct CensusArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    sim: SimArgs,
+
+    /// Maximal period searched for when classifying objects
+    #[clap(long, value_parser, default_value_t = 1000)]
+    max_period: u32,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    sim: SimArgs,
+}
+
+#[derive(Args)]
+struct ViewArgs {
+    #[clap(flatten)]
+    input: InputArgs,
+
+    #[clap(flatten)]
+    sim: SimArgs,
+
+    /// Draws with half-block characters instead of braille
+    #[clap(long, action)]
+    half_block: bool,
+
+    /// Draws outlines of groups from the start
+    #[clap(long, action)]
+    envelopes: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs the pattern and writes the final generation, its statistics and animations
+    Run(Box<RunArgs>),
+    /// Runs the pattern and classifies every object of the final generation
+    Analyze(AnalyzeArgs),
+    /// Converts the pattern to another format
+    Convert(ConvertArgs),
+    /// Runs the pattern and prints counts of objects (by apgcode) in the final generation
+    Census(CensusArgs),
+    /// Runs the pattern and prints how fast it was stepped
+    Bench(BenchArgs),
+    /// Shows the field in the terminal after "--generations" (0 by default) and lets to step it
+    /// interactively
+    View(ViewArgs),
 }
 
 #[derive(Parser)]
 struct Cli {
     /// Enables parallel calculations
-    #[clap(short, long, action)]
+    #[clap(short, long, action, global = true)]
     parallel: bool,
-    
+
     /// Number of threads to use
-    #[clap(short, long, value_parser, default_value_t = 8)]
+    #[clap(short, long, value_parser, default_value_t = 8, global = true)]
     jobs: u8,
 
-    /// Number of generations to be run
-    #[clap(short, long, value_parser, required_unless_present = "pattern")]
-    generations: Option<u32>,
+    #[clap(subcommand)]
+    command: Command,
+}
 
-    /// Path to output SVG file to
-    #[clap(short, long, value_parser, value_name = "FILE", default_value_t = String::from("life.svg"))]
-    output_file: String,
+/// Animations recorded during the run
+struct Frames {
+    raster: Option<Animation>,
+    svg: Option<SvgAnimation>,
+    /// Generations between frames
+    every: u32,
+}
 
-    /// Pattern to run. Available "lidka" and "r-pentomino". When not specified RLE is parsed 
-    /// from stdin
-    #[clap(value_parser)]
-    pattern: Option<String>,
+impl Frames {
+    fn none() -> Self {
+        Frames {
+            raster: None,
+            svg: None,
+            every: 1,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.raster.is_none() && self.svg.is_none()
+    }
+
+    /// Captures the current generation into all requested animations
+    fn capture(&mut self, field: &Field) {
+        if let Some(animation) = &mut self.raster {
+            animation.capture(field);
+        }
+        if let Some(svg) = &mut self.svg {
+            svg.capture(field);
+        }
+    }
 }
 
-fn main() {
-    let cli = Cli::parse();
+/// Builds a field of a single group with *block* placed at *coord*, given with y axis going down
+fn field_from_block(coord: Coord, block: Block) -> Field {
+    let mut group = Group::new(coord, block);
+    group.reverse_y();
+    let mut field = Field::new(RTree::new());
+    field.field.insert(group);
+    field
+}
 
-    let coord = Coord { x: 0, y: 0 };
-    let mut age = 0;
-
-    let mut group = match &cli.pattern {
-        Some(pattern) => {
-            if pattern == "r-pentomino" {
-                age = 1103;
-                Group::new(coord, r_pentomino())
-            } else if pattern == "lidka" {
-                age = 29126;
-                Group::new(coord, lidka())
-            } else {
-                panic!("Unknown pattern specified\n");
+/// Advances the field by *generations* (or until it stabilizes) with the backend chosen in *sim*
+fn simulate(
+    field: &mut Field,
+    sim: &SimArgs,
+    generations: u32,
+    parallel: bool,
+    frames: &mut Frames,
+) {
+    if !frames.is_empty() {
+        if sim.until_stable {
+            eprintln!("Animation is not recorded with --until-stable");
+            std::process::exit(1);
+        }
+        frames.capture(field);
+    }
+
+    if sim.until_stable {
+        match field.run_until_stable(generations.into(), sim.stable_period, parallel) {
+            Some(generation) => println!("Stabilized at generation {}", generation),
+            None => println!("Not stabilized in {} generations", generations),
+        }
+    } else if sim.backend == Backend::Hashlife {
+        if field.topology() != Topology::Plane {
+            eprintln!("Hashlife backend supports only unbounded plane");
+            std::process::exit(1);
+        }
+        if field.stats().is_some() {
+            eprintln!("Hashlife backend does not record statistics");
+            std::process::exit(1);
+        }
+        let mut life = HashLife::from_field(field);
+        if !frames.is_empty() {
+            let mut done = 0;
+            while done < generations {
+                let steps = std::cmp::min(frames.every, generations - done);
+                life.step(steps.into());
+                done += steps;
+                if steps == frames.every {
+                    frames.capture(&life.to_field());
+                }
             }
+        } else {
+            life.step(generations.into());
         }
-        None => {
-			let mut buf = "".to_string();
-				loop {
-				match std::io::stdin().read_line(&mut buf) {
-					Ok(0) => break,
-					_ => (),
-				}
-			}
-            Group::new(coord, Block::rle_import(&buf).expect("Cannot parse pattern\n"))
+        *field = life.to_field();
+    } else {
+        for i in 0..generations {
+            if parallel {
+                field.step_parallel();
+            } else {
+                field.step();
+            }
+            if (i + 1) % frames.every == 0 {
+                frames.capture(field);
+            }
         }
-    };
+    }
+}
 
-    group.reverse_y();
-    let mut test_field = Field::new(RTree::new());
-    test_field.field.insert(group);
+/// Writes the final generation and recorded data to the files requested in *args*
+fn write_outputs(field: &Field, args: &RunArgs, frames: &Frames) -> Result<(), Error> {
+    if let (Some(path), Some(animation)) = (&args.raster.animation, &frames.raster) {
+        animation.save(path)?;
+    }
 
-    age = match &cli.generations {
-        Some(val) => *val,
-        None => age,
-    };
+    if let (Some(path), Some(svg_animation)) = (&args.svg.svg_animation, &frames.svg) {
+        svg_animation
+            .save(path)
+            .map_err(|err| Error::io(path, err))?;
+    }
 
-    test_field.request_parallelizm(cli.jobs);
+    if let Some(path) = &args.raster.png {
+        png::save(path, field, &args.raster.config(args.svg.crop))
+            .map_err(|err| Error::io(path, err))?;
+    }
 
-    for _i in 0..age {
-        if cli.parallel {
-            test_field.step_parallel();
-        } else {
-            test_field.step();
+    if let (Some(path), Some(stats)) = (&args.stats, field.stats()) {
+        let out = match args.stats_format {
+            StatsFormat::Csv => stats.to_csv(),
+            StatsFormat::Json => stats.to_json_lines(),
+        };
+        std::fs::write(path, out).map_err(|err| Error::io(path, err))?;
+    }
+
+    if let Some(path) = &args.rle_output {
+        std::fs::write(path, field.rle_export()).map_err(|err| Error::io(path, err))?;
+    }
+
+    if let Some(path) = &args.pattern_output {
+        let format = args
+            .output_format
+            .or_else(|| PatternFormat::from_path(path))
+            .unwrap_or(PatternFormat::Rle);
+        std::fs::write(path, field.export(format)).map_err(|err| Error::io(path, err))?;
+    }
+
+    if let Some(path) = &args.output_file {
+        let doc = svg_render::render(field, &args.svg.config());
+        svg::save(path, &doc).map_err(|err| Error::io(path, err))?;
+    }
+    Ok(())
+}
+
+/// Prints census table sorted by object count
+fn print_census(field: &Field, max_period: u32) {
+    let mut counts: Vec<(String, u64)> = census::census(field, max_period).into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    for (code, count) in counts {
+        match census::common_name(&code) {
+            Some(name) => println!("{:>8}  {} ({})", count, code, name),
+            None => println!("{:>8}  {}", count, code),
         }
     }
+}
 
-    let mut doc = svg::Document::new();
-    doc = test_field.prep_svg(doc);
-    doc = test_field.svg_draw(doc);
-    let start = Rectangle::new()
-        .set("x", 0)
-        .set("y", 0)
-        .set("height", 10)
-        .set("width", 10)
-        .set("fill", "blue");
-    doc = doc.add(start);
-    svg::save(&cli.output_file, &doc).unwrap();
+/// Prints position, population, behaviour and apgcode of every group, topmost first
+fn print_analysis(field: &Field, max_period: u32) {
+    let rule = field.rule();
+    let mut groups: Vec<&Group> = field.field.iter().collect();
+    groups.sort_by_key(|group| (-group.global_coord.y, group.global_coord.x));
+    for group in groups {
+        let classification = period::classify(group, &rule, max_period);
+        let code = census::apgcode(group, &rule, max_period).unwrap_or_default();
+        match census::common_name(&code) {
+            Some(name) => println!(
+                "{:>8} {:>8} {:>8}  {}  {} ({})",
+                group.global_coord.x,
+                group.global_coord.y,
+                group.block.population(),
+                classification,
+                code,
+                name
+            ),
+            None => println!(
+                "{:>8} {:>8} {:>8}  {}  {}",
+                group.global_coord.x,
+                group.global_coord.y,
+                group.block.population(),
+                classification,
+                code
+            ),
+        }
+    }
+    println!(
+        "Generation {}: population {}, groups {}, stable: {}",
+        field.generation(),
+        field.population(),
+        field.group_count(),
+        field.is_stable(max_period)
+    );
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(err) = run(&cli) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+/// Runs the command. Errors of writing outputs are returned, so they are reported without a
+/// backtrace
+fn run(cli: &Cli) -> Result<(), Error> {
+    match &cli.command {
+        Command::Run(args) => {
+            let (mut field, age) = args.input.load();
+            field.request_parallelizm(cli.jobs);
+            if args.stats.is_some() {
+                field.record_stats();
+            }
+            let mut frames = args.frames();
+            let generations = args.sim.generations(age);
+            simulate(
+                &mut field,
+                &args.sim,
+                generations,
+                cli.parallel,
+                &mut frames,
+            );
+            write_outputs(&field, args, &frames)?;
+            println!(
+                "Generation {}: population {}, groups {}",
+                field.generation(),
+                field.population(),
+                field.group_count()
+            );
+        }
+        Command::Analyze(args) => {
+            let (mut field, age) = args.input.load();
+            field.request_parallelizm(cli.jobs);
+            let generations = args.sim.generations(age);
+            simulate(
+                &mut field,
+                &args.sim,
+                generations,
+                cli.parallel,
+                &mut Frames::none(),
+            );
+            print_analysis(&field, args.max_period);
+        }
+        Command::Convert(args) => {
+            let (field, _) = args.input.load();
+            let format = args
+                .output_format
+                .or_else(|| {
+                    args.output_file
+                        .as_deref()
+                        .and_then(PatternFormat::from_path)
+                })
+                .unwrap_or(PatternFormat::Rle);
+            let out = field.export(format);
+            match &args.output_file {
+                Some(path) => std::fs::write(path, out).map_err(|err| Error::io(path, err))?,
+                None => print!("{}", out),
+            }
+        }
+        Command::Census(args) => {
+            let (mut field, age) = args.input.load();
+            field.request_parallelizm(cli.jobs);
+            let generations = args.sim.generations(age);
+            simulate(
+                &mut field,
+                &args.sim,
+                generations,
+                cli.parallel,
+                &mut Frames::none(),
+            );
+            print_census(&field, args.max_period);
+        }
+        Command::Bench(args) => {
+            let (mut field, age) = args.input.load();
+            field.request_parallelizm(cli.jobs);
+            if args.sim.backend == Backend::Rtree {
+                field.record_stats();
+            }
+            let generations = args.sim.generations(age);
+            //the pattern may start at a later generation (e.g. "#G" lines)
+            let start = field.generation();
+            let started = Instant::now();
+            simulate(
+                &mut field,
+                &args.sim,
+                generations,
+                cli.parallel,
+                &mut Frames::none(),
+            );
+            let elapsed = started.elapsed().as_secs_f64();
+            let stepped = field.generation().saturating_sub(start);
+            println!(
+                "{} generations in {:.3} s, {:.1} generations/s",
+                stepped,
+                elapsed,
+                stepped as f64 / elapsed
+            );
+            //the first sample is the starting generation, it took no step
+            if let Some(stats) = field.stats().filter(|stats| stats.samples().len() > 1) {
+                let times: Vec<f64> = stats
+                    .samples()
+                    .iter()
+                    .skip(1)
+                    .map(|sample| sample.step_time.as_secs_f64() * 1000.0)
+                    .collect();
+                println!(
+                    "Step time: mean {:.3} ms, max {:.3} ms",
+                    times.iter().sum::<f64>() / times.len() as f64,
+                    times.iter().cloned().fold(0.0, f64::max)
+                );
+            }
+            println!(
+                "Final population {}, groups {}",
+                field.population(),
+                field.group_count()
+            );
+        }
+        Command::View(args) => {
+            let (mut field, _) = args.input.load();
+            field.request_parallelizm(cli.jobs);
+            let generations = args.sim.generations(Some(0));
+            simulate(
+                &mut field,
+                &args.sim,
+                generations,
+                cli.parallel,
+                &mut Frames::none(),
+            );
+            let mut viewer = Viewer::new(field, cli.parallel);
+            if args.half_block {
+                viewer.viewport.glyphs = Glyphs::HalfBlock;
+            }
+            viewer.viewport.show_envelopes = args.envelopes;
+            if let Err(err) = view::run(viewer) {
+                eprintln!("Cannot run terminal viewer: {}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+    Ok(())
 }
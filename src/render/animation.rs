@@ -0,0 +1,115 @@
+//! Contains recording of [field](Field) generations into animated GIF or PNG
+use super::raster::{self, Raster, RasterConfig};
+use super::{gif, png};
+use crate::groups::{field::Field, Coord};
+use crate::Error;
+
+/// File format of an [Animation]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    /// Animated PNG
+    Apng,
+}
+
+impl AnimationFormat {
+    /// Picks the format by file extension: `.png` and `.apng` are APNG, anything else is GIF
+    pub fn from_path(path: &str) -> AnimationFormat {
+        let lower = path.to_lowercase();
+        if lower.ends_with(".png") || lower.ends_with(".apng") {
+            AnimationFormat::Apng
+        } else {
+            AnimationFormat::Gif
+        }
+    }
+}
+
+/// Captured generation: alive cells and the envelope of the field
+struct Frame {
+    cells: Vec<Coord>,
+    window: Option<(Coord, Coord)>,
+}
+
+/// Collects frames of a running field. Frames are rasterized on [Animation::encode()] over the
+/// union of all captured windows, so the picture does not jump while the pattern grows
+pub struct Animation {
+    pub config: RasterConfig,
+    pub format: AnimationFormat,
+    /// Time each frame is shown
+    pub delay_ms: u16,
+    frames: Vec<Frame>,
+}
+
+impl Animation {
+    pub fn new(format: AnimationFormat, config: RasterConfig, delay_ms: u16) -> Self {
+        Animation {
+            config,
+            format,
+            delay_ms,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Captures the current generation of the field
+    pub fn capture(&mut self, field: &Field) {
+        self.frames.push(Frame {
+            cells: raster::alive_cells(field),
+            window: field.bottom_left().zip(field.top_right()),
+        });
+    }
+
+    /// Returns count of captured frames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Window shared by all frames: crop or union of captured windows
+    fn window(&self) -> (Coord, Coord) {
+        if let Some(crop) = self.config.crop {
+            return crop;
+        }
+        self.frames
+            .iter()
+            .filter_map(|frame| frame.window)
+            .reduce(|(bl, tr), (other_bl, other_tr)| {
+                (
+                    Coord {
+                        x: std::cmp::min(bl.x, other_bl.x),
+                        y: std::cmp::min(bl.y, other_bl.y),
+                    },
+                    Coord {
+                        x: std::cmp::max(tr.x, other_tr.x),
+                        y: std::cmp::max(tr.y, other_tr.y),
+                    },
+                )
+            })
+            .unwrap_or((Coord { x: 0, y: 0 }, Coord { x: 0, y: 0 }))
+    }
+
+    /// Encodes captured frames into the file contents. Returns [Error::GifTooLarge] if frames
+    /// do not fit into GIF
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let window = self.window();
+        let rasters: Vec<Raster> = self
+            .frames
+            .iter()
+            .map(|frame| Raster::from_cells(frame.cells.clone(), window, &self.config))
+            .collect();
+        match self.format {
+            AnimationFormat::Gif => gif::encode_animation(&rasters, &self.config, self.delay_ms),
+            AnimationFormat::Apng => {
+                Ok(png::encode_animation(&rasters, &self.config, self.delay_ms))
+            }
+        }
+    }
+
+    /// Writes captured frames to a file
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        std::fs::write(path, self.encode()?).map_err(|err| Error::io(path, err))
+    }
+}
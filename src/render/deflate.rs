@@ -0,0 +1,281 @@
+//! Contains a small zlib encoder used by PNG. Compresses with LZ77 and fixed Huffman codes
+//! (RFC 1950, RFC 1951), which is enough for images of cells with long runs of equal pixels
+
+/// Size of the LZ77 window
+const WINDOW: usize = 1 << 15;
+/// Longest match allowed by deflate
+const MAX_MATCH: usize = 258;
+/// Shortest match worth encoding
+const MIN_MATCH: usize = 3;
+/// Number of older positions with the same hash checked for a match
+const CHAIN_DEPTH: usize = 32;
+/// Bits of the hash of 3 bytes
+const HASH_BITS: u32 = 15;
+
+/// Base lengths of length codes 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+/// Extra bits of length codes 257..=285
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances of distance codes 0..=29
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+/// Extra bits of distance codes 0..=29
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Writes bits least significant first
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            out: Vec::new(),
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    /// Writes *count* low bits of *bits*
+    fn write(&mut self, bits: u32, count: u32) {
+        self.buffer |= (bits as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which goes most significant bit first
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+/// Writes literal/length symbol with the fixed Huffman code
+fn write_symbol(writer: &mut BitWriter, symbol: u16) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol as u32, 8),
+        144..=255 => writer.write_code(0x190 + (symbol - 144) as u32, 9),
+        256..=279 => writer.write_code((symbol - 256) as u32, 7),
+        _ => writer.write_code(0xc0 + (symbol - 280) as u32, 8),
+    }
+}
+
+/// Writes a match of *length* bytes *distance* bytes back
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE
+        .iter()
+        .rposition(|base| *base as usize <= length)
+        .unwrap();
+    write_symbol(writer, 257 + code as u16);
+    writer.write(
+        (length - LENGTH_BASE[code] as usize) as u32,
+        LENGTH_EXTRA[code] as u32,
+    );
+
+    let code = DIST_BASE
+        .iter()
+        .rposition(|base| *base as usize <= distance)
+        .unwrap();
+    writer.write_code(code as u32, 5);
+    writer.write(
+        (distance - DIST_BASE[code] as usize) as u32,
+        DIST_EXTRA[code] as u32,
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    let value = (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32;
+    (value.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compresses *data* into a raw deflate stream of one block
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new();
+    //final block with fixed codes
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let insert = |head: &mut Vec<usize>, prev: &mut Vec<usize>, pos: usize| {
+        if pos + MIN_MATCH <= data.len() {
+            let h = hash(&data[pos..]);
+            prev[pos % WINDOW] = head[h];
+            head[h] = pos;
+        }
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best = (0, 0);
+        if pos + MIN_MATCH <= data.len() {
+            let max = std::cmp::min(MAX_MATCH, data.len() - pos);
+            let mut candidate = head[hash(&data[pos..])];
+            for _ in 0..CHAIN_DEPTH {
+                if candidate == usize::MAX || pos - candidate > WINDOW - 1 {
+                    break;
+                }
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[pos..pos + max])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, pos - candidate);
+                    if length == max {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+            }
+        }
+
+        if best.0 >= MIN_MATCH {
+            write_match(&mut writer, best.0, best.1);
+            for i in pos..pos + best.0 {
+                insert(&mut head, &mut prev, i);
+            }
+            pos += best.0;
+        } else {
+            write_symbol(&mut writer, data[pos] as u16);
+            insert(&mut head, &mut prev, pos);
+            pos += 1;
+        }
+    }
+    write_symbol(&mut writer, 256);
+    writer.finish()
+}
+
+/// Adler-32 checksum used by zlib
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// Compresses *data* into a zlib stream
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads bits least significant first
+    struct BitReader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            let mut value = 0;
+            for i in 0..count {
+                let bit = self.data[self.pos / 8] >> (self.pos % 8) & 1;
+                value |= (bit as u32) << i;
+                self.pos += 1;
+            }
+            value
+        }
+
+        /// Reads a fixed Huffman literal/length symbol
+        fn symbol(&mut self) -> u16 {
+            let mut code = 0;
+            for length in 1..=9 {
+                code = code << 1 | self.bits(1);
+                match (length, code) {
+                    (7, 0..=23) => return 256 + code as u16,
+                    (8, 0x30..=0xbf) => return (code - 0x30) as u16,
+                    (8, 0xc0..=0xc7) => return 280 + (code - 0xc0) as u16,
+                    (9, 0x190..=0x1ff) => return 144 + (code - 0x190) as u16,
+                    _ => (),
+                }
+            }
+            panic!("invalid code");
+        }
+    }
+
+    /// Decoder of fixed Huffman blocks written by [deflate()]
+    fn inflate(data: &[u8]) -> Vec<u8> {
+        let mut reader = BitReader { data, pos: 0 };
+        assert_eq!(reader.bits(3), 0b011);
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            let symbol = reader.symbol();
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => return out,
+                _ => {
+                    let code = (symbol - 257) as usize;
+                    let length = LENGTH_BASE[code] as usize
+                        + reader.bits(LENGTH_EXTRA[code] as u32) as usize;
+                    let mut dist_code = 0;
+                    for _ in 0..5 {
+                        dist_code = dist_code << 1 | reader.bits(1);
+                    }
+                    let dist_code = dist_code as usize;
+                    let distance = DIST_BASE[dist_code] as usize
+                        + reader.bits(DIST_EXTRA[dist_code] as u32) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deflate_roundtrip() {
+        let mut noise = Vec::new();
+        let mut state: u32 = 7;
+        for _ in 0..70000 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            noise.push((state >> 16) as u8 & 0x3);
+        }
+        let runs: Vec<u8> = (0..100000).map(|i| (i / 1000 % 2) as u8).collect();
+        for data in [Vec::new(), b"abcabcabcabcabc".to_vec(), noise, runs] {
+            let compressed = deflate(&data);
+            assert_eq!(inflate(&compressed), data);
+        }
+        assert!(deflate(&[0; 100000]).len() < 1000);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+}
@@ -0,0 +1,249 @@
+//! Contains animated GIF encoding of [rasters](Raster)
+use super::raster::{Raster, RasterConfig};
+use crate::Error;
+use std::collections::HashMap;
+
+/// Bits of uncompressed pixels (256 color palette)
+const MIN_CODE_SIZE: u32 = 8;
+/// Largest LZW code size allowed by GIF
+const MAX_CODE_SIZE: u32 = 12;
+
+/// Writes LZW codes least significant bit first
+struct CodeWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl CodeWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+/// Compresses pixels with GIF flavoured LZW
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let clear: u16 = 1 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut writer = CodeWriter {
+        out: Vec::new(),
+        buffer: 0,
+        count: 0,
+    };
+    let mut size = MIN_CODE_SIZE + 1;
+    let mut next = end + 1;
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    writer.write(clear, size);
+
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(val) => *val as u16,
+        None => {
+            writer.write(end, size);
+            return writer.finish();
+        }
+    };
+    for pixel in pixels {
+        if let Some(code) = table.get(&(prefix, *pixel)) {
+            prefix = *code;
+            continue;
+        }
+        writer.write(prefix, size);
+        if next < 1 << MAX_CODE_SIZE {
+            table.insert((prefix, *pixel), next);
+            //decoder learns the code one step later, so it grows the size after this one
+            if next == 1 << size {
+                size += 1;
+            }
+            next += 1;
+        } else {
+            writer.write(clear, size);
+            table.clear();
+            size = MIN_CODE_SIZE + 1;
+            next = end + 1;
+        }
+        prefix = *pixel as u16;
+    }
+    writer.write(prefix, size);
+    if next == 1 << size && size < MAX_CODE_SIZE {
+        size += 1;
+    }
+    writer.write(end, size);
+    writer.finish()
+}
+
+/// Appends data split into sub-blocks of up to 255 bytes
+fn sub_blocks(out: &mut Vec<u8>, data: &[u8]) {
+    for block in data.chunks(255) {
+        out.push(block.len() as u8);
+        out.extend_from_slice(block);
+    }
+    out.push(0);
+}
+
+/// Returns sides of the frame as GIF stores them
+fn gif_size(frame: &Raster) -> Result<(u16, u16), Error> {
+    match (u16::try_from(frame.width), u16::try_from(frame.height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(Error::GifTooLarge {
+            width: frame.width,
+            height: frame.height,
+        }),
+    }
+}
+
+/// Encodes frames of equal size as endlessly looping GIF showing each frame for *delay_ms*
+/// (rounded to hundredths of a second)
+///
+/// Returns [Error::GifTooLarge] if a side of some frame is longer than 65535 pixels
+pub fn encode_animation(
+    frames: &[Raster],
+    config: &RasterConfig,
+    delay_ms: u16,
+) -> Result<Vec<u8>, Error> {
+    let (width, height) = match frames.first() {
+        Some(frame) => gif_size(frame)?,
+        None => (1, 1),
+    };
+    let mut out = Vec::from(*b"GIF89a");
+    out.extend(width.to_le_bytes());
+    out.extend(height.to_le_bytes());
+    //global color table of 256 colors
+    out.extend([0xf7, 0, 0]);
+    out.extend(config.palette().concat());
+
+    //NETSCAPE2.0 extension, loop forever
+    out.extend([0x21, 0xff, 11]);
+    out.extend(b"NETSCAPE2.0");
+    out.extend([3, 1, 0, 0, 0]);
+
+    let delay = delay_ms.div_ceil(10);
+    for frame in frames {
+        let (width, height) = gif_size(frame)?;
+        out.extend([0x21, 0xf9, 4, 0]);
+        out.extend(delay.to_le_bytes());
+        out.extend([0, 0]);
+
+        out.push(0x2c);
+        out.extend([0, 0, 0, 0]);
+        out.extend(width.to_le_bytes());
+        out.extend(height.to_le_bytes());
+        out.push(0);
+        out.push(MIN_CODE_SIZE as u8);
+        sub_blocks(&mut out, &lzw(&frame.pixels));
+    }
+    out.push(0x3b);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decoder of GIF LZW data
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let clear = 1usize << MIN_CODE_SIZE;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut size = MIN_CODE_SIZE + 1;
+        let mut prev: Option<usize> = None;
+        let mut out = Vec::new();
+        let (mut pos, total) = (0, data.len() * 8);
+        while pos + size as usize <= total {
+            let mut code = 0;
+            for i in 0..size as usize {
+                let bit = data[(pos + i) / 8] >> ((pos + i) % 8) & 1;
+                code |= (bit as usize) << i;
+            }
+            pos += size as usize;
+            if code == clear {
+                table = (0..=255).map(|val| vec![val]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                size = MIN_CODE_SIZE + 1;
+                prev = None;
+                continue;
+            }
+            if code == clear + 1 {
+                break;
+            }
+            let entry = match prev {
+                None => table[code].clone(),
+                Some(prev) => {
+                    let entry = if code < table.len() {
+                        table[code].clone()
+                    } else {
+                        let mut val = table[prev].clone();
+                        val.push(val[0]);
+                        val
+                    };
+                    if table.len() < 1 << MAX_CODE_SIZE {
+                        let mut new = table[prev].clone();
+                        new.push(entry[0]);
+                        table.push(new);
+                        if table.len() == 1 << size && size < MAX_CODE_SIZE {
+                            size += 1;
+                        }
+                    }
+                    entry
+                }
+            };
+            out.extend(&entry);
+            prev = Some(code);
+        }
+        out
+    }
+
+    #[test]
+    fn gif_lzw() {
+        let mut noise = Vec::new();
+        let mut state: u32 = 11;
+        for _ in 0..50000 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            noise.push((state >> 16) as u8);
+        }
+        let runs: Vec<u8> = (0..60000).map(|i| (i / 700 % 3) as u8 * 100).collect();
+        for data in [Vec::new(), vec![7], noise, runs] {
+            assert_eq!(unlzw(&lzw(&data)), data);
+        }
+
+        let raster = Raster {
+            width: 2,
+            height: 2,
+            pixels: vec![0, 255, 255, 0],
+        };
+        let gif =
+            encode_animation(&[raster.clone(), raster], &RasterConfig::default(), 100).unwrap();
+        assert!(gif.starts_with(b"GIF89a\x02\x00\x02\x00"));
+        assert_eq!(gif.last(), Some(&0x3b));
+        assert_eq!(
+            gif.windows(4).filter(|w| *w == [0x21, 0xf9, 4, 0]).count(),
+            2
+        );
+
+        let wide = Raster {
+            width: 70000,
+            height: 1,
+            pixels: vec![0; 70000],
+        };
+        assert_eq!(
+            encode_animation(&[wide], &RasterConfig::default(), 100),
+            Err(Error::GifTooLarge {
+                width: 70000,
+                height: 1
+            })
+        );
+    }
+}
@@ -0,0 +1,9 @@
+//! Renderers of [fields](crate::groups::field::Field) into image formats
+
+pub mod animation;
+mod deflate;
+pub mod gif;
+pub mod png;
+pub mod raster;
+pub mod svg;
+pub mod terminal;
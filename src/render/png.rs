@@ -0,0 +1,152 @@
+//! Contains PNG and animated PNG (APNG) encoding of [rasters](Raster)
+use super::deflate;
+use super::raster::{Raster, RasterConfig};
+use crate::groups::field::Field;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// CRC-32 (IEEE) used by PNG chunks
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                crc >> 1 ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Appends a chunk with its length and CRC
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+    let crc = crc32(&out[start..]);
+    out.extend(crc.to_be_bytes());
+}
+
+/// Writes signature, header and palette of an 8 bit indexed image
+fn header(out: &mut Vec<u8>, width: u32, height: u32, config: &RasterConfig) {
+    out.extend(SIGNATURE);
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend(width.to_be_bytes());
+    ihdr.extend(height.to_be_bytes());
+    //bit depth 8, indexed color, default compression, filter and no interlace
+    ihdr.extend([8, 3, 0, 0, 0]);
+    chunk(out, b"IHDR", &ihdr);
+    chunk(out, b"PLTE", &config.palette().concat());
+}
+
+/// Compresses pixel rows, each prefixed with filter type 0
+fn image_data(raster: &Raster) -> Vec<u8> {
+    let mut data = Vec::with_capacity(raster.pixels.len() + raster.height as usize);
+    for row in raster.pixels.chunks(raster.width as usize) {
+        data.push(0);
+        data.extend_from_slice(row);
+    }
+    deflate::zlib(&data)
+}
+
+/// Encodes the raster as PNG with colors from *config*
+pub fn encode(raster: &Raster, config: &RasterConfig) -> Vec<u8> {
+    let mut out = Vec::new();
+    header(&mut out, raster.width, raster.height, config);
+    chunk(&mut out, b"IDAT", &image_data(raster));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Encodes frames of equal size as endlessly looping APNG showing each frame for *delay_ms*
+pub fn encode_animation(frames: &[Raster], config: &RasterConfig, delay_ms: u16) -> Vec<u8> {
+    let (width, height) = frames
+        .first()
+        .map_or((1, 1), |frame| (frame.width, frame.height));
+    let mut out = Vec::new();
+    header(&mut out, width, height, config);
+
+    let mut actl = Vec::with_capacity(8);
+    actl.extend((frames.len() as u32).to_be_bytes());
+    actl.extend(0u32.to_be_bytes());
+    chunk(&mut out, b"acTL", &actl);
+
+    let mut sequence = 0u32;
+    for (index, frame) in frames.iter().enumerate() {
+        let mut fctl = Vec::with_capacity(26);
+        fctl.extend(sequence.to_be_bytes());
+        fctl.extend(frame.width.to_be_bytes());
+        fctl.extend(frame.height.to_be_bytes());
+        fctl.extend(0u32.to_be_bytes());
+        fctl.extend(0u32.to_be_bytes());
+        fctl.extend(delay_ms.to_be_bytes());
+        fctl.extend(1000u16.to_be_bytes());
+        //no disposal, frame replaces the canvas
+        fctl.extend([0, 0]);
+        chunk(&mut out, b"fcTL", &fctl);
+        sequence += 1;
+
+        if index == 0 {
+            chunk(&mut out, b"IDAT", &image_data(frame));
+        } else {
+            let mut fdat = sequence.to_be_bytes().to_vec();
+            fdat.extend(image_data(frame));
+            chunk(&mut out, b"fdAT", &fdat);
+            sequence += 1;
+        }
+    }
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Renders the field into a PNG file
+pub fn save(path: &str, field: &Field, config: &RasterConfig) -> std::io::Result<()> {
+    std::fs::write(path, encode(&Raster::new(field, config), config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits the file into chunk kinds, checking their CRCs
+    fn chunks(png: &[u8]) -> Vec<String> {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut kinds = Vec::new();
+        let mut pos = 8;
+        while pos < png.len() {
+            let length = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+            let body = &png[pos + 4..pos + 8 + length];
+            let crc =
+                u32::from_be_bytes(png[pos + 8 + length..pos + 12 + length].try_into().unwrap());
+            assert_eq!(crc32(body), crc);
+            kinds.push(String::from_utf8(body[..4].to_vec()).unwrap());
+            pos += 12 + length;
+        }
+        kinds
+    }
+
+    #[test]
+    fn png_chunks() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+
+        let raster = Raster {
+            width: 3,
+            height: 2,
+            pixels: vec![0, 255, 0, 255, 0, 255],
+        };
+        let config = RasterConfig::default();
+        let png = encode(&raster, &config);
+        assert_eq!(chunks(&png), ["IHDR", "PLTE", "IDAT", "IEND"]);
+        assert_eq!(png[16..24], [0, 0, 0, 3, 0, 0, 0, 2]);
+
+        let apng = encode_animation(&[raster.clone(), raster.clone(), raster], &config, 100);
+        assert_eq!(
+            chunks(&apng),
+            ["IHDR", "PLTE", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "fcTL", "fdAT", "IEND"]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Contains rasterization of [fields](Field) into indexed pixel images used by PNG and GIF
+use crate::groups::{field::Field, Coord};
+
+/// Settings of raster rendering
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RasterConfig {
+    /// Size of one pixel of the downsampled image in image pixels
+    pub zoom: u32,
+    /// Side of the square of cells in one pixel. Pixel color shows the share of alive cells
+    pub density: u32,
+    pub background: [u8; 3],
+    pub cell_color: [u8; 3],
+    /// Bottom left and top right cells of the drawn window. Whole field is drawn if [None]
+    pub crop: Option<(Coord, Coord)>,
+}
+
+impl Default for RasterConfig {
+    fn default() -> Self {
+        RasterConfig {
+            zoom: 1,
+            density: 1,
+            background: [0x80, 0x80, 0x80],
+            cell_color: [0x00, 0x80, 0x00],
+            crop: None,
+        }
+    }
+}
+
+impl RasterConfig {
+    /// Colors of the 256 density levels, from background to cell color
+    pub fn palette(&self) -> Vec<[u8; 3]> {
+        (0..=255u32)
+            .map(|level| {
+                let mut color = [0; 3];
+                for (i, channel) in color.iter_mut().enumerate() {
+                    let (from, to) = (self.background[i] as u32, self.cell_color[i] as u32);
+                    *channel = ((from * (255 - level) + to * level + 127) / 255) as u8;
+                }
+                color
+            })
+            .collect()
+    }
+
+    /// Returns the drawn window: crop or the envelope of the field
+    pub fn window(&self, field: &Field) -> (Coord, Coord) {
+        match self.crop {
+            Some(val) => val,
+            None => (
+                field.bottom_left().unwrap_or(Coord { x: 0, y: 0 }),
+                field.top_right().unwrap_or(Coord { x: 0, y: 0 }),
+            ),
+        }
+    }
+}
+
+/// Image of density levels, one byte per pixel, rows go from the smallest y up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// Rasterizes the field inside of the configured window
+    pub fn new(field: &Field, config: &RasterConfig) -> Raster {
+        Raster::from_cells(alive_cells(field), config.window(field), config)
+    }
+
+    /// Rasterizes alive *cells* inside of *window*
+    pub fn from_cells(cells: Vec<Coord>, window: (Coord, Coord), config: &RasterConfig) -> Raster {
+        let (bl, tr) = window;
+        let density = std::cmp::max(config.density, 1) as i64;
+        let zoom = std::cmp::max(config.zoom, 1);
+        let columns = ((tr.x - bl.x) / density + 1) as usize;
+        let rows = ((tr.y - bl.y) / density + 1) as usize;
+
+        let mut counts = vec![0u32; columns * rows];
+        for cell in cells {
+            if cell.x < bl.x || cell.x > tr.x || cell.y < bl.y || cell.y > tr.y {
+                continue;
+            }
+            let column = ((cell.x - bl.x) / density) as usize;
+            let row = ((cell.y - bl.y) / density) as usize;
+            counts[row * columns + column] += 1;
+        }
+
+        let area = (density * density) as u32;
+        let width = columns as u32 * zoom;
+        let height = rows as u32 * zoom;
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for row in counts.chunks(columns) {
+            let mut line = Vec::with_capacity(width as usize);
+            for count in row {
+                let level = (count * 255 / area) as u8;
+                line.extend(std::iter::repeat_n(level, zoom as usize));
+            }
+            for _ in 0..zoom {
+                pixels.extend_from_slice(&line);
+            }
+        }
+        Raster {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// Collects alive cells of all groups of the field
+pub fn alive_cells(field: &Field) -> Vec<Coord> {
+    field
+        .field
+        .iter()
+        .flat_map(|group| group.alive_cells())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raster_density() {
+        let cells = vec![
+            Coord { x: 0, y: 0 },
+            Coord { x: 1, y: 0 },
+            Coord { x: 3, y: 1 },
+            Coord { x: 9, y: 9 },
+        ];
+        let window = (Coord { x: 0, y: 0 }, Coord { x: 3, y: 1 });
+
+        let plain = Raster::from_cells(cells.clone(), window, &RasterConfig::default());
+        assert_eq!((plain.width, plain.height), (4, 2));
+        assert_eq!(plain.pixels, vec![255, 255, 0, 0, 0, 0, 0, 255]);
+
+        let config = RasterConfig {
+            density: 2,
+            zoom: 2,
+            ..RasterConfig::default()
+        };
+        let dense = Raster::from_cells(cells, window, &config);
+        assert_eq!((dense.width, dense.height), (4, 2));
+        assert_eq!(dense.pixels, vec![127, 127, 63, 63, 127, 127, 63, 63]);
+
+        let palette = RasterConfig::default().palette();
+        assert_eq!(palette[0], [0x80, 0x80, 0x80]);
+        assert_eq!(palette[255], [0x00, 0x80, 0x00]);
+    }
+}
@@ -0,0 +1,502 @@
+//! Contains configurable SVG rendering of [fields](Field) and [groups](Group)
+use crate::groups::{field::Field, group::Group, Coord};
+use rstar::AABB;
+use std::collections::BTreeMap;
+use svg::node::element::{Animate, Group as SvgGroup, Path, Rectangle};
+use svg::node::Node;
+use svg::Document;
+
+/// How alive cells are written to the document
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SvgMode {
+    /// One `<rect>` per cell
+    #[default]
+    Cells,
+    /// One `<rect>` per horizontal run of alive cells
+    Runs,
+    /// One `<path>` holding all horizontal runs of the field
+    Path,
+}
+
+/// Parses mode names: `cells`, `runs` and `path`
+impl std::str::FromStr for SvgMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cells" => Ok(SvgMode::Cells),
+            "runs" => Ok(SvgMode::Runs),
+            "path" => Ok(SvgMode::Path),
+            _ => Err(format!(
+                "unknown SVG mode \"{}\", expected cells, runs or path",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for SvgMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SvgMode::Cells => write!(f, "cells"),
+            SvgMode::Runs => write!(f, "runs"),
+            SvgMode::Path => write!(f, "path"),
+        }
+    }
+}
+
+/// Settings of SVG rendering. Coordinates are field coordinates, one cell is **cell_size** units
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvgConfig {
+    pub cell_size: u32,
+    pub background: String,
+    pub cell_color: String,
+    /// Outline of cells (or runs), [None] to draw without it
+    pub cell_stroke: Option<String>,
+    /// Draw outlines of [groups](Group)
+    pub show_envelopes: bool,
+    pub envelope_color: String,
+    /// Mark the cell at (0, 0)
+    pub show_origin: bool,
+    pub origin_color: String,
+    /// Bottom left and top right cells of the drawn window. Whole field is drawn if [None]
+    pub crop: Option<(Coord, Coord)>,
+    pub mode: SvgMode,
+}
+
+impl Default for SvgConfig {
+    fn default() -> Self {
+        SvgConfig {
+            cell_size: 10,
+            background: String::from("grey"),
+            cell_color: String::from("green"),
+            cell_stroke: Some(String::from("black")),
+            show_envelopes: true,
+            envelope_color: String::from("red"),
+            show_origin: true,
+            origin_color: String::from("blue"),
+            crop: None,
+            mode: SvgMode::Cells,
+        }
+    }
+}
+
+impl SvgConfig {
+    /// Width of outlines, scaled with cells
+    fn stroke_width(&self) -> f64 {
+        self.cell_size as f64 * 0.03
+    }
+
+    /// Returns the drawn window: crop or the envelope of the field
+    fn window(&self, field: &Field) -> (Coord, Coord) {
+        match self.crop {
+            Some(val) => val,
+            None => (
+                field.bottom_left().unwrap_or(Coord { x: -50, y: -50 }),
+                field.top_right().unwrap_or(Coord { x: 50, y: 50 }),
+            ),
+        }
+    }
+
+    /// Checks if the cell is inside of the crop window
+    fn contains(&self, cell: Coord) -> bool {
+        match self.crop {
+            None => true,
+            Some((bl, tr)) => cell.x >= bl.x && cell.x <= tr.x && cell.y >= bl.y && cell.y <= tr.y,
+        }
+    }
+}
+
+/// Renders the field into a new document
+pub fn render(field: &Field, config: &SvgConfig) -> Document {
+    let doc = prepare(Document::new(), field, config);
+    origin(draw(doc, field, config), config)
+}
+
+/// Marks the cell at (0, 0) if enabled
+fn origin(doc: Document, config: &SvgConfig) -> Document {
+    if !config.show_origin {
+        return doc;
+    }
+    doc.add(
+        Rectangle::new()
+            .set("x", 0)
+            .set("y", 0)
+            .set("width", config.cell_size)
+            .set("height", config.cell_size)
+            .set("fill", config.origin_color.as_str()),
+    )
+}
+
+/// Sets view box to the drawn window and fills the background
+pub fn prepare(doc: Document, field: &Field, config: &SvgConfig) -> Document {
+    prepare_window(doc, config.window(field), config)
+}
+
+/// Sets view box to *window* and fills the background
+fn prepare_window(doc: Document, window: (Coord, Coord), config: &SvgConfig) -> Document {
+    let size = config.cell_size as i64;
+    let (bl, tr) = window;
+    let background = Rectangle::new()
+        .set("x", bl.x * size)
+        .set("y", bl.y * size)
+        .set("width", "100%")
+        .set("height", "100%")
+        .set("fill", config.background.as_str());
+    doc.add(background).set(
+        "viewBox",
+        (
+            bl.x * size,
+            bl.y * size,
+            (tr.x - bl.x + 1) * size,
+            (tr.y - bl.y + 1) * size,
+        ),
+    )
+}
+
+/// Draws groups of the field visible in the window
+pub fn draw(mut doc: Document, field: &Field, config: &SvgConfig) -> Document {
+    let (bl, tr) = config.window(field);
+    let window = AABB::from_corners(bl.into(), tr.into());
+    let groups = field.field.locate_in_envelope_intersecting(&window);
+
+    if config.mode != SvgMode::Path {
+        for group in groups {
+            doc = draw_group(doc, group, config);
+        }
+        return doc;
+    }
+
+    let mut data = String::new();
+    let mut envelopes = Vec::new();
+    for group in groups {
+        for (start, length) in runs(group, config) {
+            data += &run_path(start, length, config.cell_size as i64);
+        }
+        if config.show_envelopes {
+            envelopes.push(envelope(group, config));
+        }
+    }
+    if !data.is_empty() {
+        let mut path = Path::new()
+            .set("d", data)
+            .set("fill", config.cell_color.as_str());
+        if let Some(stroke) = &config.cell_stroke {
+            path = path
+                .set("stroke", stroke.as_str())
+                .set("stroke-width", config.stroke_width());
+        }
+        doc = doc.add(path);
+    }
+    for rect in envelopes {
+        doc = doc.add(rect);
+    }
+    doc
+}
+
+/// Draws cells of one group and its envelope. [SvgMode::Path] is drawn as [SvgMode::Runs] here,
+/// use [draw()] to join all groups in one path
+pub fn draw_group(mut doc: Document, group: &Group, config: &SvgConfig) -> Document {
+    append_group(&mut doc, group, config);
+    doc
+}
+
+/// Appends cells of one group and its envelope to *parent*
+fn append_group<T: Node>(parent: &mut T, group: &Group, config: &SvgConfig) {
+    let size = config.cell_size as i64;
+    let cells: Vec<(Coord, i64)> = match config.mode {
+        SvgMode::Cells => group
+            .alive_cells()
+            .into_iter()
+            .filter(|cell| config.contains(*cell))
+            .map(|cell| (cell, 1))
+            .collect(),
+        SvgMode::Runs | SvgMode::Path => runs(group, config),
+    };
+
+    for (start, length) in cells {
+        let mut cell = Rectangle::new()
+            .set("x", start.x * size)
+            .set("y", start.y * size)
+            .set("width", length * size)
+            .set("height", size)
+            .set("fill", config.cell_color.as_str());
+        if let Some(stroke) = &config.cell_stroke {
+            cell = cell
+                .set("stroke", stroke.as_str())
+                .set("stroke-width", config.stroke_width());
+        }
+        parent.append(cell);
+    }
+
+    if config.show_envelopes {
+        parent.append(envelope(group, config));
+    }
+}
+
+/// Outline of the group block
+fn envelope(group: &Group, config: &SvgConfig) -> Rectangle {
+    let size = config.cell_size as i64;
+    Rectangle::new()
+        .set("x", group.global_coord.x * size)
+        .set("y", group.global_coord.y * size)
+        .set("width", group.block.x_size as i64 * size)
+        .set("height", group.block.y_size as i64 * size)
+        .set("stroke", config.envelope_color.as_str())
+        .set("stroke-width", config.stroke_width())
+        .set("fill", "black")
+        .set("fill-opacity", "0.01")
+}
+
+/// Returns horizontal runs of alive cells inside of the crop window as start cell and length
+fn runs(group: &Group, config: &SvgConfig) -> Vec<(Coord, i64)> {
+    let mut runs = Vec::new();
+    for y in 0..group.block.y_size {
+        let mut start: Option<Coord> = None;
+        for x in 0..=group.block.x_size {
+            let cell = group.global_coord
+                + Coord {
+                    x: x as i64,
+                    y: y as i64,
+                };
+            let alive = x < group.block.x_size && group.block[(x, y)] == 1 && config.contains(cell);
+            match (alive, start) {
+                (true, None) => start = Some(cell),
+                (false, Some(val)) => {
+                    runs.push((val, cell.x - val.x));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+    }
+    runs
+}
+
+/// Path data of a run: move to its corner and draw the rectangle
+fn run_path(start: Coord, length: i64, size: i64) -> String {
+    format!(
+        "M{} {}h{}v{}h{}z",
+        start.x * size,
+        start.y * size,
+        length * size,
+        size,
+        -length * size
+    )
+}
+
+/// Distinct group: bottom left and top right corners with alive cells
+type GroupKey = (Coord, Coord, Vec<Coord>);
+
+/// Collects generations of a field into one animated document. Every distinct [Group] is drawn
+/// once and shown with SMIL `<animate>` only in the frames it is present in, so still lifes and
+/// oscillators cost a few elements for the whole run
+pub struct SvgAnimation {
+    pub config: SvgConfig,
+    /// Time each frame is shown
+    pub frame_ms: u32,
+    frames: usize,
+    /// Distinct groups by position, size and cells with the frames they were captured in
+    groups: BTreeMap<GroupKey, (Group, Vec<usize>)>,
+    window: Option<(Coord, Coord)>,
+}
+
+impl SvgAnimation {
+    pub fn new(config: SvgConfig, frame_ms: u32) -> Self {
+        SvgAnimation {
+            config,
+            frame_ms,
+            frames: 0,
+            groups: BTreeMap::new(),
+            window: None,
+        }
+    }
+
+    /// Captures groups of the current generation visible in the drawn window
+    pub fn capture(&mut self, field: &Field) {
+        let (bl, tr) = self.config.window(field);
+        self.window = Some(match self.window {
+            None => (bl, tr),
+            Some((old_bl, old_tr)) => (
+                Coord {
+                    x: std::cmp::min(bl.x, old_bl.x),
+                    y: std::cmp::min(bl.y, old_bl.y),
+                },
+                Coord {
+                    x: std::cmp::max(tr.x, old_tr.x),
+                    y: std::cmp::max(tr.y, old_tr.y),
+                },
+            ),
+        });
+
+        let window = AABB::from_corners(bl.into(), tr.into());
+        for group in field.field.locate_in_envelope_intersecting(&window) {
+            let key = (group.global_coord, group.top_right(), group.alive_cells());
+            let entry = self
+                .groups
+                .entry(key)
+                .or_insert_with(|| (group.clone(), Vec::new()));
+            entry.1.push(self.frames);
+        }
+        self.frames += 1;
+    }
+
+    /// Returns count of captured frames
+    pub fn len(&self) -> usize {
+        self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames == 0
+    }
+
+    /// Renders captured frames into a document looping over all of them
+    pub fn render(&self) -> Document {
+        let window = self
+            .window
+            .unwrap_or((Coord { x: -50, y: -50 }, Coord { x: 50, y: 50 }));
+        let mut doc = prepare_window(Document::new(), window, &self.config);
+        let duration = format!("{}ms", self.frames as u64 * self.frame_ms as u64);
+
+        for (group, frames) in self.groups.values() {
+            let mut node = SvgGroup::new();
+            append_group(&mut node, group, &self.config);
+            if frames.len() < self.frames {
+                let (values, times) = self.key_frames(frames);
+                node = node.set("visibility", "hidden").add(
+                    Animate::new()
+                        .set("attributeName", "visibility")
+                        .set("calcMode", "discrete")
+                        .set("values", values)
+                        .set("keyTimes", times)
+                        .set("dur", duration.as_str())
+                        .set("repeatCount", "indefinite"),
+                );
+            }
+            doc = doc.add(node);
+        }
+        origin(doc, &self.config)
+    }
+
+    /// Returns `values` and `keyTimes` of visibility switching at starts and ends of runs of
+    /// consecutive *frames*
+    fn key_frames(&self, frames: &[usize]) -> (String, String) {
+        let mut switches: Vec<(usize, &str)> = Vec::new();
+        for (index, frame) in frames.iter().enumerate() {
+            if index == 0 || frames[index - 1] + 1 != *frame {
+                switches.push((*frame, "visible"));
+            }
+            let last = index + 1 == frames.len() || frames[index + 1] != frame + 1;
+            if last && frame + 1 < self.frames {
+                switches.push((frame + 1, "hidden"));
+            }
+        }
+        if switches[0].0 != 0 {
+            switches.insert(0, (0, "hidden"));
+        }
+
+        let values: Vec<&str> = switches.iter().map(|(_, value)| *value).collect();
+        let times: Vec<String> = switches
+            .iter()
+            .map(|(frame, _)| format!("{}", *frame as f64 / self.frames as f64))
+            .collect();
+        (values.join(";"), times.join(";"))
+    }
+
+    /// Writes the animation to a file
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        svg::save(path, &self.render())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::rle;
+    use rstar::RTree;
+
+    fn field() -> Field {
+        let mut tree = RTree::new();
+        tree.insert(Group::new(
+            Coord { x: 0, y: 0 },
+            rle::parse("x = 5, y = 2\n3obo$obo!").unwrap().block,
+        ));
+        Field::new(tree)
+    }
+
+    #[test]
+    fn svg_modes() {
+        let mut config = SvgConfig {
+            show_envelopes: false,
+            show_origin: false,
+            ..SvgConfig::default()
+        };
+        let cells = render(&field(), &config).to_string();
+        assert_eq!(cells.matches("<rect").count(), 1 + 6);
+
+        config.mode = SvgMode::Runs;
+        let runs = render(&field(), &config).to_string();
+        assert_eq!(runs.matches("<rect").count(), 1 + 4);
+        assert!(runs.contains("width=\"30\""));
+
+        config.mode = SvgMode::Path;
+        config.cell_stroke = None;
+        let path = render(&field(), &config).to_string();
+        assert_eq!(path.matches("<rect").count(), 1);
+        assert!(
+            path.contains("d=\"M0 0h30v10h-30zM40 0h10v10h-10zM0 10h10v10h-10zM20 10h10v10h-10z\"")
+        );
+        assert!(!path.contains("stroke"));
+    }
+
+    #[test]
+    fn svg_crop() {
+        let config = SvgConfig {
+            crop: Some((Coord { x: 1, y: 0 }, Coord { x: 3, y: 0 })),
+            mode: SvgMode::Runs,
+            show_origin: false,
+            show_envelopes: false,
+            ..SvgConfig::default()
+        };
+        let doc = render(&field(), &config).to_string();
+        assert!(doc.contains("viewBox=\"10 0 30 10\""));
+        assert_eq!(doc.matches("<rect").count(), 1 + 1);
+        assert!(doc.contains("x=\"10\" y=\"0\""));
+
+        let defaults = render(&field(), &SvgConfig::default()).to_string();
+        assert!(defaults.contains("fill=\"blue\""));
+        assert!(defaults.contains("stroke=\"red\""));
+    }
+
+    #[test]
+    fn svg_animation() {
+        let mut tree = RTree::new();
+        tree.insert(Group::new(
+            Coord { x: 0, y: 0 },
+            rle::parse("x = 2, y = 2\n2o$2o!").unwrap().block,
+        ));
+        tree.insert(Group::new(
+            Coord { x: 10, y: 0 },
+            rle::parse("x = 3, y = 1\n3o!").unwrap().block,
+        ));
+        let mut field = Field::new(tree);
+        //give groups their empty borders so they keep envelopes between generations
+        field.step();
+
+        let mut animation = SvgAnimation::new(SvgConfig::default(), 100);
+        for _ in 0..4 {
+            animation.capture(&field);
+            field.step();
+        }
+        assert_eq!(animation.len(), 4);
+
+        let doc = animation.render().to_string();
+        //block is always visible, blinker has two phases
+        assert_eq!(doc.matches("<g").count(), 3);
+        assert_eq!(doc.matches("<animate").count(), 2);
+        assert!(doc.contains("dur=\"400ms\""));
+        assert!(doc.contains("keyTimes=\"0;0.25;0.5;0.75\""));
+        assert!(doc.contains("values=\"visible;hidden;visible;hidden\""));
+        assert!(doc.contains("values=\"hidden;visible;hidden;visible\""));
+    }
+}
@@ -0,0 +1,234 @@
+//! Contains rendering of [fields](Field) into lines of terminal text with braille or half-block
+//! characters
+use crate::groups::{field::Field, Coord};
+use rstar::AABB;
+
+/// Characters used to draw dots
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Glyphs {
+    /// 2x4 dots per character
+    #[default]
+    Braille,
+    /// 1x2 dots per character (`▀`, `▄`, `█`)
+    HalfBlock,
+}
+
+impl Glyphs {
+    /// Dots per character horizontally and vertically
+    pub fn dots(&self) -> (u32, u32) {
+        match self {
+            Glyphs::Braille => (2, 4),
+            Glyphs::HalfBlock => (1, 2),
+        }
+    }
+
+    /// Character with dots of *mask* lit, bit `y * width + x` is the dot (x, y) from top left
+    fn glyph(&self, mask: u8) -> char {
+        match self {
+            Glyphs::Braille => {
+                const BITS: [u8; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+                let code = (0..8)
+                    .filter(|dot| mask >> dot & 1 == 1)
+                    .fold(0, |code, dot| code | BITS[dot]);
+                char::from_u32(0x2800 + code as u32).unwrap()
+            }
+            Glyphs::HalfBlock => [' ', '▀', '▄', '█'][mask as usize & 3],
+        }
+    }
+}
+
+/// Part of the field shown in the terminal
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Viewport {
+    /// Cell in the middle of the screen
+    pub center: Coord,
+    /// Side of the square of cells shown as one dot. A dot is lit if any of the cells is alive
+    pub scale: u32,
+    /// Size of the screen in characters
+    pub columns: u32,
+    pub rows: u32,
+    pub glyphs: Glyphs,
+    /// Draw outlines of groups in red
+    pub show_envelopes: bool,
+}
+
+impl Viewport {
+    /// Bottom left and top right cells shown on the screen
+    pub fn window(&self) -> (Coord, Coord) {
+        let (dx, dy) = self.glyphs.dots();
+        //scale may be large enough to overflow u32
+        let width = self.columns as i64 * dx as i64 * self.scale as i64;
+        let height = self.rows as i64 * dy as i64 * self.scale as i64;
+        let top_left = Coord {
+            x: self.center.x - width / 2,
+            y: self.center.y + height / 2,
+        };
+        (
+            Coord {
+                x: top_left.x,
+                y: top_left.y - height + 1,
+            },
+            Coord {
+                x: top_left.x + width - 1,
+                y: top_left.y,
+            },
+        )
+    }
+}
+
+/// Dots of the screen, rows go from the top
+struct Dots {
+    width: usize,
+    height: usize,
+    data: Vec<bool>,
+    /// Top left cell
+    origin: Coord,
+    scale: i64,
+}
+
+impl Dots {
+    fn new(viewport: &Viewport) -> Dots {
+        let (dx, dy) = viewport.glyphs.dots();
+        let (bl, tr) = viewport.window();
+        let (width, height) = (
+            (viewport.columns * dx) as usize,
+            (viewport.rows * dy) as usize,
+        );
+        Dots {
+            width,
+            height,
+            data: vec![false; width * height],
+            origin: Coord { x: bl.x, y: tr.y },
+            scale: viewport.scale as i64,
+        }
+    }
+
+    /// Lights the dot showing *cell* if it is on the screen
+    fn set(&mut self, cell: Coord) {
+        let x = (cell.x - self.origin.x).div_euclid(self.scale);
+        let y = (self.origin.y - cell.y).div_euclid(self.scale);
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.data[y as usize * self.width + x as usize] = true;
+        }
+    }
+
+    /// Mask of dots drawn by the character at *column*, *row*
+    fn mask(&self, column: usize, row: usize, glyphs: Glyphs) -> u8 {
+        let (dx, dy) = glyphs.dots();
+        let mut mask = 0;
+        for y in 0..dy as usize {
+            for x in 0..dx as usize {
+                if self.data[(row * dy as usize + y) * self.width + column * dx as usize + x] {
+                    mask |= 1 << (y * dx as usize + x);
+                }
+            }
+        }
+        mask
+    }
+}
+
+/// Renders the visible part of the field, one string per row of the screen
+pub fn render(field: &Field, viewport: &Viewport) -> Vec<String> {
+    let (bl, tr) = viewport.window();
+    let groups = field
+        .field
+        .locate_in_envelope_intersecting(&AABB::from_corners(bl.into(), tr.into()));
+
+    let mut cells = Dots::new(viewport);
+    let mut envelopes = Dots::new(viewport);
+    for group in groups {
+        for cell in group.alive_cells() {
+            cells.set(cell);
+        }
+        if viewport.show_envelopes {
+            let (lower, upper) = (group.global_coord, group.top_right());
+            for x in std::cmp::max(lower.x, bl.x)..=std::cmp::min(upper.x, tr.x) {
+                envelopes.set(Coord { x, y: lower.y });
+                envelopes.set(Coord { x, y: upper.y });
+            }
+            for y in std::cmp::max(lower.y, bl.y)..=std::cmp::min(upper.y, tr.y) {
+                envelopes.set(Coord { x: lower.x, y });
+                envelopes.set(Coord { x: upper.x, y });
+            }
+        }
+    }
+
+    let mut lines = Vec::with_capacity(viewport.rows as usize);
+    for row in 0..viewport.rows as usize {
+        let mut line = String::new();
+        for column in 0..viewport.columns as usize {
+            let alive = cells.mask(column, row, viewport.glyphs);
+            let outline = envelopes.mask(column, row, viewport.glyphs);
+            if alive == 0 && outline != 0 {
+                line.push_str("\x1b[31m");
+                line.push(viewport.glyphs.glyph(outline));
+                line.push_str("\x1b[0m");
+            } else {
+                line.push(viewport.glyphs.glyph(alive));
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::{group::Group, rle};
+    use rstar::RTree;
+
+    #[test]
+    fn terminal_render() {
+        let mut tree = RTree::new();
+        tree.insert(Group::new(
+            Coord { x: 0, y: 0 },
+            rle::parse("x = 3, y = 2\n3o$o!").unwrap().block,
+        ));
+        let field = Field::new(tree);
+
+        let mut viewport = Viewport {
+            center: Coord { x: 2, y: 0 },
+            scale: 1,
+            columns: 2,
+            rows: 1,
+            glyphs: Glyphs::Braille,
+            show_envelopes: false,
+        };
+        //window is x in 0..=3 and y in -1..=2, row y = 1 is drawn above y = 0
+        assert_eq!(
+            viewport.window(),
+            (Coord { x: 0, y: -1 }, Coord { x: 3, y: 2 })
+        );
+        assert_eq!(render(&field, &viewport), ["⠦⠄"]);
+
+        viewport.glyphs = Glyphs::HalfBlock;
+        viewport.columns = 4;
+        assert_eq!(render(&field, &viewport), ["█▄▄ "]);
+
+        viewport.scale = 2;
+        viewport.columns = 2;
+        assert_eq!(render(&field, &viewport), ["█▄"]);
+
+        //window wider than u32 at the largest scale
+        let wide = Viewport {
+            scale: 1 << 20,
+            columns: 5000,
+            ..viewport
+        };
+        assert_eq!(wide.window().1.x - wide.window().0.x + 1, 5000 << 20);
+
+        //top edge of the envelope has no alive cells
+        let mut tree = RTree::new();
+        tree.insert(Group::new(
+            Coord { x: 0, y: 0 },
+            rle::parse("x = 3, y = 3\n3o!").unwrap().block,
+        ));
+        let field = Field::new(tree);
+        viewport.show_envelopes = true;
+        viewport.scale = 1;
+        viewport.center = Coord { x: 1, y: 2 };
+        viewport.columns = 3;
+        assert_eq!(render(&field, &viewport), ["\x1b[31m▄\x1b[0m".repeat(3)]);
+    }
+}
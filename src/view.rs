@@ -0,0 +1,354 @@
+//! Contains interactive terminal viewer of a running [Field]
+use crate::groups::{field::Field, Coord};
+use crate::render::terminal::{self, Glyphs, Viewport};
+use crossbeam_channel as channel;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Largest side of the square of cells shown as one dot
+const MAX_SCALE: u32 = 1 << 20;
+/// Slowest automatic stepping
+const MAX_DELAY: Duration = Duration::from_secs(5);
+/// Time between checks of the terminal size, each of them runs `stty`
+const RESIZE_POLL: Duration = Duration::from_millis(250);
+
+/// Keys understood by [Viewer]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Char(u8),
+}
+
+/// State of the viewer: the field, visible part of it and stepping mode
+pub struct Viewer {
+    pub field: Field,
+    pub viewport: Viewport,
+    /// Step automatically every *delay*
+    pub running: bool,
+    pub delay: Duration,
+    /// Use [Field::step_parallel()]
+    pub parallel: bool,
+    /// Generations left to run before pausing again
+    queued: u64,
+    /// Count typed before a command
+    count: Option<u64>,
+    /// Bytes of an unfinished escape sequence
+    escape: Vec<u8>,
+    quit: bool,
+}
+
+impl Viewer {
+    pub fn new(field: Field, parallel: bool) -> Self {
+        let mut viewer = Viewer {
+            field,
+            viewport: Viewport {
+                center: Coord { x: 0, y: 0 },
+                scale: 1,
+                columns: 80,
+                rows: 23,
+                glyphs: Glyphs::Braille,
+                show_envelopes: false,
+            },
+            running: false,
+            delay: Duration::from_millis(100),
+            parallel,
+            queued: 0,
+            count: None,
+            escape: Vec::new(),
+            quit: false,
+        };
+        viewer.center();
+        viewer
+    }
+
+    /// Checks if the user asked to quit
+    pub fn quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Checks if steps are due without waiting for input
+    pub fn busy(&self) -> bool {
+        self.running || self.queued > 0
+    }
+
+    /// Moves the view to the middle of the pattern
+    pub fn center(&mut self) {
+        if let (Some(bl), Some(tr)) = (self.field.bottom_left(), self.field.top_right()) {
+            self.viewport.center = Coord {
+                x: (bl.x + tr.x) / 2,
+                y: (bl.y + tr.y) / 2,
+            };
+        }
+    }
+
+    /// Feeds one byte of terminal input, decoding arrow key escape sequences
+    pub fn feed(&mut self, byte: u8) {
+        if byte == 0x1b || !self.escape.is_empty() {
+            self.escape.push(byte);
+            let key = match self.escape[..] {
+                [0x1b] | [0x1b, b'['] => return,
+                [0x1b, b'[', b'A'] => Some(Key::Up),
+                [0x1b, b'[', b'B'] => Some(Key::Down),
+                [0x1b, b'[', b'C'] => Some(Key::Right),
+                [0x1b, b'[', b'D'] => Some(Key::Left),
+                _ => None,
+            };
+            self.escape.clear();
+            if let Some(key) = key {
+                self.key(key);
+            }
+            return;
+        }
+        self.key(Key::Char(byte));
+    }
+
+    /// Applies a key press
+    pub fn key(&mut self, key: Key) {
+        //pan by an eighth of the screen, the window is computed in i64 so it does not overflow
+        let (bl, tr) = self.viewport.window();
+        let step_x = std::cmp::max((tr.x - bl.x + 1) / 8, 1);
+        let step_y = std::cmp::max((tr.y - bl.y + 1) / 8, 1);
+        let count = self.count.take();
+        match key {
+            Key::Up | Key::Char(b'k') | Key::Char(b'w') => self.viewport.center.y += step_y,
+            Key::Down | Key::Char(b'j') | Key::Char(b's') => self.viewport.center.y -= step_y,
+            Key::Left | Key::Char(b'h') | Key::Char(b'a') => self.viewport.center.x -= step_x,
+            Key::Right | Key::Char(b'l') | Key::Char(b'd') => self.viewport.center.x += step_x,
+            Key::Char(b'+') | Key::Char(b'=') => {
+                self.viewport.scale = std::cmp::max(self.viewport.scale / 2, 1)
+            }
+            Key::Char(b'-') => {
+                self.viewport.scale = std::cmp::min(self.viewport.scale * 2, MAX_SCALE)
+            }
+            Key::Char(b' ') => {
+                self.running = !self.running;
+                self.queued = 0;
+            }
+            Key::Char(b'n') | Key::Char(b'.') => self.queued += count.unwrap_or(1),
+            Key::Char(b'g') => self.queued += count.unwrap_or(1),
+            Key::Char(b']') => self.delay = std::cmp::max(self.delay / 2, Duration::from_millis(1)),
+            Key::Char(b'[') => self.delay = std::cmp::min(self.delay * 2, MAX_DELAY),
+            Key::Char(b'e') => self.viewport.show_envelopes = !self.viewport.show_envelopes,
+            Key::Char(b'b') => {
+                self.viewport.glyphs = match self.viewport.glyphs {
+                    Glyphs::Braille => Glyphs::HalfBlock,
+                    Glyphs::HalfBlock => Glyphs::Braille,
+                }
+            }
+            Key::Char(b'c') => self.center(),
+            //ctrl-c arrives as a byte in raw mode
+            Key::Char(b'q') | Key::Char(3) => self.quit = true,
+            Key::Char(digit @ b'0'..=b'9') => {
+                let value = count.unwrap_or(0).saturating_mul(10);
+                self.count = Some(value.saturating_add((digit - b'0') as u64));
+            }
+            Key::Char(_) => (),
+        }
+    }
+
+    /// Steps the field if it is running or has queued generations. Returns true if it stepped
+    pub fn tick(&mut self) -> bool {
+        if !self.busy() {
+            return false;
+        }
+        self.queued = self.queued.saturating_sub(1);
+        if self.parallel {
+            self.field.step_parallel();
+        } else {
+            self.field.step();
+        }
+        true
+    }
+
+    /// Line with generation, population, group count, view and stepping state
+    pub fn status(&self) -> String {
+        let mode = match (self.running, self.queued) {
+            (true, _) => String::from("running"),
+            (false, 0) => String::from("paused"),
+            (false, queued) => format!("{} left", queued),
+        };
+        format!(
+            "gen {} | pop {} | groups {} | 1:{} at ({}, {}) | {} {}ms{} | q quit, space run, \
+             n step, N g run N, arrows pan, +- zoom, [] speed, e envelopes, b glyphs, c center",
+            self.field.generation(),
+            self.field.population(),
+            self.field.group_count(),
+            self.viewport.scale,
+            self.viewport.center.x,
+            self.viewport.center.y,
+            mode,
+            self.delay.as_millis(),
+            match self.count {
+                Some(count) => format!(" | {}", count),
+                None => String::new(),
+            },
+        )
+    }
+
+    /// Renders the screen: field rows and the status line
+    pub fn frame(&self) -> String {
+        let mut out = String::from("\x1b[H");
+        for line in terminal::render(&self.field, &self.viewport) {
+            out += &line;
+            out += "\x1b[K\r\n";
+        }
+        let status: String = self
+            .status()
+            .chars()
+            .take(self.viewport.columns as usize)
+            .collect();
+        out += "\x1b[7m";
+        out += &status;
+        out += "\x1b[K\x1b[0m";
+        out
+    }
+}
+
+/// Runs `stty` on the controlling terminal
+fn stty(args: &[&str]) -> std::io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(File::open("/dev/tty")?))
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Terminal in raw mode on the alternate screen. Restores the previous state when dropped
+struct RawTerminal {
+    saved: String,
+}
+
+impl RawTerminal {
+    fn enter() -> std::io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+        std::io::stdout().flush()?;
+        Ok(RawTerminal { saved })
+    }
+
+    /// Size of the terminal as columns and rows
+    fn size(&self) -> (u32, u32) {
+        let size = stty(&["size"]).unwrap_or_default();
+        let mut values = size.split_whitespace().map(|val| val.parse::<u32>().ok());
+        match (values.next().flatten(), values.next().flatten()) {
+            (Some(rows), Some(columns)) if rows > 1 && columns > 0 => (columns, rows),
+            _ => (80, 24),
+        }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Shows the viewer in the terminal until the user quits. Keys are read from the controlling
+/// terminal, so the pattern itself may come from stdin
+pub fn run(mut viewer: Viewer) -> std::io::Result<()> {
+    let mut tty = File::open("/dev/tty")?;
+    let terminal = RawTerminal::enter()?;
+
+    let (sender, receiver) = channel::unbounded();
+    std::thread::spawn(move || {
+        let mut byte = [0u8];
+        while let Ok(1) = tty.read(&mut byte) {
+            if sender.send(byte[0]).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut stdout = std::io::stdout();
+    let mut size = terminal.size();
+    let mut checked = Instant::now();
+    while !viewer.quit() {
+        if checked.elapsed() >= RESIZE_POLL {
+            size = terminal.size();
+            checked = Instant::now();
+        }
+        viewer.viewport.columns = size.0;
+        viewer.viewport.rows = size.1 - 1;
+        stdout.write_all(viewer.frame().as_bytes())?;
+        stdout.flush()?;
+
+        let timeout = match viewer.busy() {
+            true => viewer.delay,
+            //wake up now and then to follow terminal resizes
+            false => RESIZE_POLL,
+        };
+        match receiver.recv_timeout(timeout) {
+            Ok(byte) => {
+                viewer.feed(byte);
+                while let Ok(byte) = receiver.try_recv() {
+                    viewer.feed(byte);
+                }
+            }
+            Err(channel::RecvTimeoutError::Timeout) => {
+                viewer.tick();
+            }
+            Err(channel::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::{group::Group, rle};
+    use rstar::RTree;
+
+    #[test]
+    fn viewer_keys() {
+        let mut tree = RTree::new();
+        tree.insert(Group::new(
+            Coord { x: 10, y: 10 },
+            rle::parse("x = 3, y = 1\n3o!").unwrap().block,
+        ));
+        let mut viewer = Viewer::new(Field::new(tree), false);
+        assert_eq!(viewer.viewport.center, Coord { x: 11, y: 10 });
+
+        for byte in b"\x1b[C-" {
+            viewer.feed(*byte);
+        }
+        assert_eq!(viewer.viewport.center, Coord { x: 31, y: 10 });
+        assert_eq!(viewer.viewport.scale, 2);
+
+        for byte in b"12g" {
+            viewer.feed(*byte);
+        }
+        assert!(viewer.busy());
+        while viewer.tick() {}
+        assert_eq!(viewer.field.generation(), 12);
+        assert!(viewer
+            .status()
+            .starts_with("gen 12 | pop 3 | groups 1 | 1:2 at (31, 10) | paused"));
+
+        //pan step of a wide terminal at the largest scale is larger than u32
+        viewer.viewport.columns = 5000;
+        viewer.viewport.scale = MAX_SCALE;
+        let center = viewer.viewport.center;
+        viewer.key(Key::Right);
+        assert_eq!(
+            viewer.viewport.center.x - center.x,
+            5000 * 2 * (1 << 20) / 8
+        );
+
+        viewer.feed(b'e');
+        assert!(viewer.viewport.show_envelopes);
+        viewer.feed(b'q');
+        assert!(viewer.quit());
+    }
+}